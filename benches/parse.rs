@@ -0,0 +1,33 @@
+#![feature(test)]
+extern crate test;
+
+use test::Bencher;
+
+/// A single-line flow sequence of maps with about 4k nodes in all.
+fn flow_document() -> String {
+    let items: Vec<String> = (0..1_000)
+        .map(|i| format!("{{id: {i}, name: item{i}, tags: [a]}}"))
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// The same data as [`flow_document`] in block style.
+fn block_document() -> String {
+    (0..1_000)
+        .map(|i| format!("- id: {i}\n  name: item{i}\n  tags:\n    - a\n"))
+        .collect()
+}
+
+#[bench]
+fn parse_flow(b: &mut Bencher) {
+    let src = flow_document();
+    b.bytes = src.len() as u64;
+    b.iter(|| ryml::Tree::parse(&src).unwrap());
+}
+
+#[bench]
+fn parse_block(b: &mut Bencher) {
+    let src = block_document();
+    b.bytes = src.len() as u64;
+    b.iter(|| ryml::Tree::parse(&src).unwrap());
+}
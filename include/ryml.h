@@ -8,6 +8,13 @@
 // DO NOT EDIT. This file is generated automatically.
 // This is an amalgamated single-header version of the library.
 //
+// LOCAL PATCH (ryml crate): the Emitter has been changed to take the number
+// of spaces per indentation level from indent_width() instead of always
+// using 2, for EmitOptions::indent, and to start the output with a document
+// marker when doc_start() is set, for EmitOptions::doc_start. The arena
+// also grows by enough to fit a copy when it already has some slack. Patched
+// code is marked "ryml crate patch"; reapply it when updating this header.
+//
 // INSTRUCTIONS:
 //   - Include at will in any header of your project
 //   - In one (and only one) of your project source files,
@@ -18107,7 +18114,9 @@ private:
 
     substr _grow_arena(size_t more)
     {
-        size_t cap = m_arena_pos + more;
+        // ryml crate patch: grow by `more` past the current capacity, as
+        // callers pass the size needed minus the existing slack
+        size_t cap = m_arena.len + more;
         cap = cap < 2 * m_arena.len ? 2 * m_arena.len : cap;
         cap = cap < 64 ? 64 : cap;
         reserve_arena(cap);
@@ -19920,6 +19929,18 @@ public:
 
     using Writer::Writer;
 
+    // ryml crate patch: configurable indentation
+    /** set the number of spaces per indentation level (default 2, clamped
+     * to 2..9 so that it fits a block scalar's indentation indicator) */
+    void indent_width(size_t width) { m_indent_width = width < 2 ? 2 : (width > 9 ? 9 : width); }
+    size_t indent_width() const { return m_indent_width; }
+
+    // ryml crate patch: optional document start
+    /** start the output with "---" when emitting a root which is not a
+     * stream (streams already mark each of their documents) */
+    void doc_start(bool enabled) { m_doc_start = enabled; }
+    bool doc_start() const { return m_doc_start; }
+
     /** emit!
      *
      * When writing to a buffer, returns a substr of the emitted YAML.
@@ -19938,6 +19959,12 @@ public:
 private:
 
     Tree const* C4_RESTRICT m_tree;
+    // ryml crate patch: configurable indentation
+    size_t m_indent_width = 2;
+    // ryml crate patch: optional document start
+    bool m_doc_start = false;
+
+    RepC _indent_to(size_t num_levels) const { return {' ', m_indent_width * num_levels}; }
 
     void _emit_yaml(size_t id);
     void _do_visit_flow_sl(size_t id, size_t ilevel=0);
@@ -20388,6 +20415,10 @@ void Emitter<Writer>::_emit_yaml(size_t id)
         }
     }
 
+    // ryml crate patch: optional document start
+    if(m_doc_start && m_tree->is_root(id) && !m_tree->is_stream(id) && m_tree->type(id) != NOTYPE)
+        this->Writer::_do_write("---\n");
+
     auto *btd = m_tree->tag_directives().b;
     auto *etd = m_tree->tag_directives().e;
     auto write_tag_directives = [&btd, etd, this](size_t next_node){
@@ -20591,7 +20622,7 @@ void Emitter<Writer>::_do_visit_flow_ml(size_t id, size_t ilevel, size_t do_inde
 template<class Writer>
 void Emitter<Writer>::_do_visit_block_container(size_t node, size_t next_level, size_t do_indent)
 {
-    RepC ind = indent_to(do_indent * next_level);
+    RepC ind = _indent_to(do_indent * next_level);
 
     if(m_tree->is_seq(node))
     {
@@ -20629,7 +20660,7 @@ void Emitter<Writer>::_do_visit_block_container(size_t node, size_t next_level,
                 }
             }
             do_indent = true;
-            ind = indent_to(do_indent * next_level);
+            ind = _indent_to(do_indent * next_level);
         }
     }
     else // map
@@ -20668,7 +20699,7 @@ void Emitter<Writer>::_do_visit_block_container(size_t node, size_t next_level,
                 }
             }
             do_indent = true;
-            ind = indent_to(do_indent * next_level);
+            ind = _indent_to(do_indent * next_level);
         }
     }
 }
@@ -20679,7 +20710,7 @@ void Emitter<Writer>::_do_visit_block(size_t node, size_t ilevel, size_t do_inde
     RYML_ASSERT(!m_tree->is_stream(node));
     RYML_ASSERT(m_tree->is_container(node) || m_tree->is_doc(node));
     RYML_ASSERT(m_tree->is_root(node) || (m_tree->parent_is_map(node) || m_tree->parent_is_seq(node)));
-    RepC ind = indent_to(do_indent * ilevel);
+    RepC ind = _indent_to(do_indent * ilevel);
 
     if(m_tree->is_doc(node))
     {
@@ -20745,7 +20776,14 @@ void Emitter<Writer>::_do_visit_block(size_t node, size_t ilevel, size_t do_inde
         }
 
         if(spc && !nl)
-            this->Writer::_do_write(' ');
+        {
+            // ryml crate patch: pad a sequence item so that its children
+            // line up with the indentation of the following lines
+            if(!m_tree->has_key(node))
+                this->Writer::_do_write(RepC{' ', m_indent_width - 1});
+            else
+                this->Writer::_do_write(' ');
+        }
 
         do_indent = 0;
         if(nl)
@@ -20891,7 +20929,8 @@ void Emitter<Writer>::_write_json(NodeScalar const& C4_RESTRICT sc, NodeType fla
     _write_scalar_json(sc.scalar, flags.has_key(), flags.is_quoted());
 }
 
-#define _rymlindent_nextline() for(size_t lv = 0; lv < ilevel+1; ++lv) { this->Writer::_do_write(' '); this->Writer::_do_write(' '); }
+// ryml crate patch: configurable indentation
+#define _rymlindent_nextline() this->Writer::_do_write(RepC{' ', m_indent_width * (ilevel+1)});
 
 template<class Writer>
 void Emitter<Writer>::_write_scalar_literal(csubstr s, size_t ilevel, bool explicit_key, bool explicit_indentation)
@@ -20904,7 +20943,11 @@ void Emitter<Writer>::_write_scalar_literal(csubstr s, size_t ilevel, bool expli
     if(!explicit_indentation)
         this->Writer::_do_write('|');
     else
-        this->Writer::_do_write("|2");
+    {
+        // ryml crate patch: the indicator is the indentation width
+        this->Writer::_do_write('|');
+        this->Writer::_do_write((char)('0' + m_indent_width));
+    }
     //
     if(numnewlines_at_end > 1 || (trimmed.len == 0 && s.len > 0)/*only newlines*/)
         this->Writer::_do_write("+\n");
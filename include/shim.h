@@ -2,6 +2,8 @@
 #include <rust/cxx.h>
 #include <mutex>
 #include <memory>
+#include <new>
+#include <cstdint>
 #pragma once
 namespace shimmy
 {
@@ -16,13 +18,44 @@ namespace shimmy
         using std::runtime_error::runtime_error;
     };
 
-    void init_ryml_once()
+    /// The details of the last error raised by rapidyaml on this thread,
+    /// kept so they can be read back once the failing call has returned.
+    struct ErrorLocation
+    {
+        bool set = false;
+        std::string msg;
+        size_t offset = 0;
+        size_t line = 0;
+        size_t col = 0;
+    };
+
+    inline ErrorLocation &last_error_location()
+    {
+        thread_local ErrorLocation loc;
+        return loc;
+    }
+
+    inline bool take_error_location(rust::String &msg, size_t &offset, size_t &line, size_t &col)
+    {
+        ErrorLocation &loc = last_error_location();
+        if (!loc.set)
+            return false;
+        msg = rust::String(loc.msg);
+        offset = loc.offset;
+        line = loc.line;
+        col = loc.col;
+        loc = ErrorLocation{};
+        return true;
+    }
+
+    inline void init_ryml_once()
     {
         static std::once_flag s_flag;
         std::call_once(s_flag, []
                        {
     ryml::Callbacks callbacks = ryml::get_callbacks();
     callbacks.m_error = [](const char* msg, size_t msg_len, ryml::Location loc, void*) {
+      last_error_location() = ErrorLocation{true, std::string(msg, msg_len), loc.offset, loc.line, loc.col};
       throw RymlError(std::string(msg, msg_len) + "\n    at " + std::string(loc.name.data(), loc.name.len) + ":" + std::to_string(loc.line));
     };
     ryml::set_callbacks(callbacks);
@@ -43,18 +76,81 @@ namespace shimmy
         return std::make_unique<ryml::Tree>(tree);
     }
 
-    inline std::unique_ptr<ryml::Tree> parse(rust::Str text)
+    inline std::unique_ptr<ryml::Tree> parse(rust::Str text, size_t node_capacity)
+    {
+        init_ryml_once();
+        last_error_location() = ErrorLocation{};
+        auto tree = std::make_unique<ryml::Tree>(node_capacity, text.size());
+        ryml::parse_in_arena(c4::csubstr(text.data(), text.size()), tree.get());
+        return tree;
+    }
+
+    inline void parse_into(ryml::Tree &tree, rust::Str text)
     {
         init_ryml_once();
-        ryml::Tree tree = c4::yml::parse_in_arena(c4::csubstr(text.data(), text.size()));
-        return std::make_unique<ryml::Tree>(std::move(tree));
+        last_error_location() = ErrorLocation{};
+        ryml::parse_in_arena(c4::csubstr(text.data(), text.size()), &tree);
     }
 
-    inline std::unique_ptr<ryml::Tree> parse_in_place(char *text, size_t len)
+    inline void validate(rust::Str text)
     {
         init_ryml_once();
-        ryml::Tree tree = c4::yml::parse_in_place(c4::substr(text, len));
-        return std::make_unique<ryml::Tree>(std::move(tree));
+        last_error_location() = ErrorLocation{};
+        ryml::Tree tree;
+        ryml::parse_in_arena(c4::csubstr(text.data(), text.size()), &tree);
+    }
+
+    inline std::unique_ptr<ryml::Tree> parse_in_place(char *text, size_t len, size_t node_capacity)
+    {
+        init_ryml_once();
+        last_error_location() = ErrorLocation{};
+        auto tree = std::make_unique<ryml::Tree>(node_capacity);
+        ryml::parse_in_place(c4::substr(text, len), tree.get());
+        return tree;
+    }
+
+    /// Copy a string into the tree's arena. Returns false, leaving the tree
+    /// unchanged, if the arena could not grow to fit it without allocating
+    /// more than `limit` bytes at once.
+    inline bool try_copy_to_arena(ryml::Tree &tree, c4::csubstr s, size_t limit, c4::substr &copied)
+    {
+        // rapidyaml's own allocator aborts when malloc fails, so use one
+        // which throws instead, for this tree and only while copying.
+        ryml::Callbacks saved = tree.callbacks();
+        ryml::Callbacks throwing = saved;
+        throwing.m_user_data = &limit;
+        throwing.m_allocate = [](size_t len, void*, void *user_data) -> void* {
+            void *mem = len > *static_cast<size_t*>(user_data) ? nullptr : ::malloc(len);
+            if (mem == nullptr)
+                throw std::bad_alloc();
+            return mem;
+        };
+        tree.callbacks(throwing);
+        bool fits = true;
+        try
+        {
+            copied = tree.copy_to_arena(s);
+        }
+        catch (const std::bad_alloc &)
+        {
+            fits = false;
+        }
+        tree.callbacks(saved);
+        return fits;
+    }
+
+    inline c4::substr emit_with_options(const ryml::Tree &tree, c4::substr buffer, size_t indent, bool doc_start, bool error_on_excess)
+    {
+        c4::yml::EmitterBuf em(buffer);
+        em.indent_width(indent);
+        em.doc_start(doc_start);
+        return em.emit(c4::yml::EMIT_YAML, tree, error_on_excess);
+    }
+
+    inline c4::substr emit_node(const ryml::Tree &tree, size_t node, c4::substr buffer, bool error_on_excess)
+    {
+        c4::yml::EmitterBuf em(buffer);
+        return em.emit(c4::yml::EMIT_YAML, tree, node, error_on_excess);
     }
 
     inline c4::yml::NodeType tree_node_type(const ryml::Tree &tree, size_t node)
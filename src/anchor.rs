@@ -0,0 +1,352 @@
+use crate::{Error, Result, Tree, NONE};
+use std::collections::{HashMap, HashSet};
+
+impl<'a> Tree<'a> {
+    /// Find every anchor name which is defined more than once, along with the
+    /// indices of all the nodes defining it, in document order.
+    ///
+    /// Defining an anchor again is legal YAML, with aliases referring to the
+    /// most recent definition, but in hand-written documents it is usually a
+    /// mistake.
+    pub fn find_duplicate_anchors(&self) -> Result<Vec<(String, Vec<usize>)>> {
+        let mut anchors: Vec<(String, Vec<usize>)> = Vec::new();
+        if self.is_empty() {
+            return Ok(anchors);
+        }
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            let mut names = Vec::with_capacity(2);
+            if data.node_type.has_key_anchor() {
+                names.push(data.key.anchor);
+            }
+            if data.node_type.has_val_anchor() {
+                names.push(data.value.anchor);
+            }
+            for name in names {
+                let pos = *positions.entry(name).or_insert_with(|| {
+                    anchors.push((name.to_owned(), Vec::new()));
+                    anchors.len() - 1
+                });
+                anchors[pos].1.push(index);
+            }
+            Ok(true)
+        })?;
+        anchors.retain(|(_, nodes)| nodes.len() > 1);
+        Ok(anchors)
+    }
+
+    /// Check that every alias refers to an anchor, returning the names of
+    /// any which do not, once each in document order. Useful after building
+    /// a tree with [`set_val_ref`](#method.set_val_ref) and the like, since
+    /// an alias to a missing anchor is emitted without complaint, but the
+    /// YAML cannot be [resolved](#method.resolve) or read by stricter
+    /// parsers.
+    ///
+    /// As when parsing, an anchor only counts if it is defined before the
+    /// alias in document order.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse("a: &x 1\nb: 2\nc: 3\n")?;
+    /// tree.set_val_ref(2, "x")?;
+    /// tree.set_val_ref(3, "y")?;
+    /// assert_eq!(Err(vec!["y".to_owned()]), tree.check_refs());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_refs(&self) -> std::result::Result<(), Vec<String>> {
+        let Ok(root) = self.root_id() else {
+            return Ok(());
+        };
+        let mut anchors = HashSet::new();
+        let mut missing: Vec<String> = Vec::new();
+        // Nothing in the closure fails for nodes reached by the walk.
+        let _ = self.walk_preorder(root, &mut |index| {
+            let data = self.node_data(index)?;
+            let node_type = data.node_type;
+            // The parser marks merge keys (`<<`) as aliases.
+            let is_key_ref = node_type.is_key_ref() && data.key.scalar != "<<";
+            for (scalar, is_anchor, is_ref) in [
+                (&data.key, node_type.has_key_anchor(), is_key_ref),
+                (
+                    &data.value,
+                    node_type.has_val_anchor(),
+                    node_type.is_val_ref(),
+                ),
+            ] {
+                if is_anchor {
+                    anchors.insert(scalar.anchor);
+                }
+                if is_ref
+                    && !anchors.contains(scalar.anchor)
+                    && !missing.iter().any(|name| name == scalar.anchor)
+                {
+                    missing.push(scalar.anchor.to_owned());
+                }
+            }
+            Ok(true)
+        });
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
+    }
+
+    /// Rename every repeated definition of an anchor to a new unique name
+    /// (e.g. the second `&base` becomes `&base_2`), returning the number of
+    /// anchors renamed. The first definition keeps its name.
+    ///
+    /// Aliases following a renamed definition are renamed with it, so each
+    /// alias still refers to the same node as before and the resolved tree is
+    /// unchanged.
+    pub fn dedup_anchors(&mut self) -> Result<usize> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+        let mut all_names = HashSet::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            if data.node_type.has_key_anchor() {
+                all_names.insert(data.key.anchor.to_owned());
+            }
+            if data.node_type.has_val_anchor() {
+                all_names.insert(data.value.anchor.to_owned());
+            }
+            Ok(true)
+        })?;
+        self.rename_anchors(|name, seen| {
+            if !seen {
+                return None;
+            }
+            let mut n = 2;
+            loop {
+                let candidate = format!("{}_{}", name, n);
+                if all_names.insert(candidate.clone()) {
+                    return Some(candidate);
+                }
+                n += 1;
+            }
+        })
+    }
+
+    /// Rename every anchor to `a1`, `a2`, ... in document order, along with
+    /// the aliases referring to it, so that documents which differ only in
+    /// their anchor names emit identically. Each alias still refers to the
+    /// same node as before, even where an anchor name was defined more than
+    /// once.
+    pub fn canonicalize_anchors(&mut self) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut count = 0;
+        self.rename_anchors(|_, _| {
+            count += 1;
+            Some(format!("a{}", count))
+        })?;
+        Ok(())
+    }
+
+    /// Apply every [merge key](https://yaml.org/type/merge.html) in the
+    /// tree: the entries of the map (or maps) referred to by a `<<` key are
+    /// copied into the map holding it, and the `<<` entry is removed. Keys
+    /// already in the map take precedence over merged ones, and where a `<<`
+    /// key refers to a sequence of maps, earlier maps take precedence over
+    /// later ones. Anchors on the copied nodes are dropped, so that aliases
+    /// elsewhere still refer to the originals.
+    ///
+    /// [`resolve`](#method.resolve) does this before resolving aliases, so
+    /// this only needs to be called to apply merge keys without resolving
+    /// anything else.
+    ///
+    /// Fails with [`Error::AnchorNotFound`] if a merge key refers to an
+    /// anchor which is not defined before it, and with [`Error::NotAMap`] if
+    /// it is not in a map or does not refer to a map.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse(
+    ///     "defaults: &defaults {port: 80, host: localhost}\nserver:\n  <<: *defaults\n  port: 8080\n",
+    /// )?;
+    /// tree.resolve_merge_keys()?;
+    /// let server = tree.root_ref()?.get("server")?;
+    /// assert_eq!(server.get("host")?.val()?, "localhost");
+    /// assert_eq!(server.get("port")?.val()?, "8080");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_merge_keys(&mut self) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        // Find the maps each merge key refers to while walking the tree, as
+        // an alias refers to the most recent definition of its anchor.
+        let mut anchors: HashMap<&str, usize> = HashMap::new();
+        let mut merges = Vec::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            let node_type = data.node_type;
+            if node_type.has_key_anchor() {
+                anchors.insert(data.key.anchor, index);
+            }
+            if node_type.has_val_anchor() {
+                anchors.insert(data.value.anchor, index);
+            }
+            if !node_type.has_key() || node_type.is_key_quoted() || data.key.scalar != "<<" {
+                return Ok(true);
+            }
+            let lookup = |name: &str| {
+                anchors
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| Error::AnchorNotFound(name.to_owned()))
+            };
+            let mut sources = Vec::new();
+            if node_type.is_val_ref() {
+                sources.push(lookup(data.value.anchor)?);
+            } else if node_type.is_seq() {
+                let mut child = data.first_child;
+                while child != NONE {
+                    let child_data = self.node_data(child)?;
+                    sources.push(if child_data.node_type.is_val_ref() {
+                        lookup(child_data.value.anchor)?
+                    } else {
+                        child
+                    });
+                    child = child_data.next_sibling;
+                }
+            } else {
+                sources.push(index);
+            }
+            merges.push((index, sources));
+            Ok(true)
+        })?;
+        for (merge, sources) in merges {
+            let data = self.node_data(merge)?;
+            let parent = data.parent;
+            let mut after = data.prev_sibling;
+            if !self.is_map(parent)? {
+                return Err(Error::NotAMap);
+            }
+            for source in sources {
+                if !self.is_map(source)? {
+                    return Err(Error::NotAMap);
+                }
+                let mut entries = Vec::new();
+                let mut child = self.node_data(source)?.first_child;
+                while child != NONE {
+                    entries.push(child);
+                    child = self.node_data(child)?.next_sibling;
+                }
+                for entry in entries {
+                    if self.has_entry(parent, self.node_data(entry)?.key.scalar)? {
+                        continue;
+                    }
+                    after = self.duplicate(entry, parent, after)?;
+                    self.remove_anchors(after)?;
+                }
+            }
+            self.remove(merge)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the map has an entry with the given key, other than a merge
+    /// key.
+    fn has_entry(&self, map: usize, key: &str) -> Result<bool> {
+        let mut child = self.node_data(map)?.first_child;
+        while child != NONE {
+            let data = self.node_data(child)?;
+            if data.key.scalar == key && key != "<<" {
+                return Ok(true);
+            }
+            child = data.next_sibling;
+        }
+        Ok(false)
+    }
+
+    /// Remove the anchors from the node and its descendants.
+    fn remove_anchors(&mut self, node: usize) -> Result<()> {
+        let mut anchored = Vec::new();
+        self.walk_preorder(node, &mut |index| {
+            let node_type = self.node_type(index)?;
+            anchored.push((
+                index,
+                node_type.has_key_anchor(),
+                node_type.has_val_anchor(),
+            ));
+            Ok(true)
+        })?;
+        for (index, key, val) in anchored {
+            if key {
+                self.rem_key_anchor(index)?;
+            }
+            if val {
+                self.rem_val_anchor(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Visit every anchor definition in document order, renaming it to the
+    /// name returned by `rename` (which is also told whether the name was
+    /// already defined earlier), and update the aliases to match. Returns
+    /// the number of anchors renamed.
+    fn rename_anchors(
+        &mut self,
+        mut rename: impl FnMut(&str, bool) -> Option<String>,
+    ) -> Result<usize> {
+        // The name each anchor currently resolves to, and every change to
+        // make as (node, is_key, is_ref, new name).
+        let mut current: HashMap<String, String> = HashMap::new();
+        let mut changes = Vec::new();
+        let mut renamed = 0;
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            let node_type = data.node_type;
+            let parts = [
+                (
+                    true,
+                    node_type.has_key_anchor(),
+                    node_type.is_key_ref(),
+                    data.key.anchor,
+                ),
+                (
+                    false,
+                    node_type.has_val_anchor(),
+                    node_type.is_val_ref(),
+                    data.value.anchor,
+                ),
+            ];
+            for (is_key, is_anchor, is_ref, name) in parts {
+                if is_anchor {
+                    match rename(name, current.contains_key(name)) {
+                        Some(new_name) => {
+                            changes.push((index, is_key, false, new_name.clone()));
+                            current.insert(name.to_owned(), new_name);
+                            renamed += 1;
+                        }
+                        None => {
+                            current.insert(name.to_owned(), name.to_owned());
+                        }
+                    }
+                } else if is_ref {
+                    if let Some(target) = current.get(name).filter(|target| *target != name) {
+                        changes.push((index, is_key, true, target.clone()));
+                    }
+                }
+            }
+            Ok(true)
+        })?;
+        for (node, is_key, is_ref, name) in changes {
+            match (is_key, is_ref) {
+                (true, false) => self.set_key_anchor(node, &name)?,
+                (false, false) => self.set_val_anchor(node, &name)?,
+                (true, true) => self.set_key_ref(node, &name)?,
+                (false, true) => self.set_val_ref(node, &name)?,
+            }
+        }
+        Ok(renamed)
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{Error, NodeType, Result, Tree};
+
+/// Builds a new [`Tree`] from nested closures, rather than by creating
+/// nodes by index. Keys and values are copied into the tree's arena, and
+/// written as plain scalars.
+///
+/// Each closure is given a [`MapBuilder`] or a [`SeqBuilder`] for the
+/// container being built. Their methods do not return errors, so that
+/// calls can be chained: the first error stops the rest of the build, and
+/// is returned when the tree is finished.
+///
+/// ```rust
+/// # fn main() -> Result<(), ryml::Error> {
+/// use ryml::TreeBuilder;
+///
+/// let tree = TreeBuilder::map(|m| {
+///     m.entry("hello").value("world");
+///     m.entry("list").seq(|s| {
+///         s.push("a").push("b");
+///     });
+///     m.entry("nested").map(|m| {
+///         m.entry("x").value("1").entry("y").value("2");
+///     });
+/// })?;
+/// assert_eq!(
+///     "hello: world\nlist:\n  - a\n  - b\nnested:\n  x: 1\n  y: 2\n",
+///     tree.emit()?
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TreeBuilder {
+    tree: Tree<'static>,
+    error: Option<Error>,
+}
+
+impl TreeBuilder {
+    /// Build a tree whose root is a map.
+    pub fn map(build: impl FnOnce(&mut MapBuilder<'_>)) -> Result<Tree<'static>> {
+        let mut builder = Self::new()?;
+        let root = builder.tree.root_id()?;
+        builder.container(root, NodeType::Map);
+        build(&mut MapBuilder {
+            builder: &mut builder,
+            node: root,
+        });
+        builder.finish()
+    }
+
+    /// Build a tree whose root is a sequence.
+    pub fn seq(build: impl FnOnce(&mut SeqBuilder<'_>)) -> Result<Tree<'static>> {
+        let mut builder = Self::new()?;
+        let root = builder.tree.root_id()?;
+        builder.container(root, NodeType::Seq);
+        build(&mut SeqBuilder {
+            builder: &mut builder,
+            node: root,
+        });
+        builder.finish()
+    }
+
+    fn new() -> Result<Self> {
+        let mut tree = Tree::default();
+        tree.reserve(16);
+        Ok(Self { tree, error: None })
+    }
+
+    fn finish(self) -> Result<Tree<'static>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.tree),
+        }
+    }
+
+    /// Apply a change to the tree unless an earlier one failed, keeping the
+    /// first error.
+    fn apply<T>(&mut self, change: impl FnOnce(&mut Tree<'static>) -> Result<T>) -> Option<T> {
+        if self.error.is_some() {
+            return None;
+        }
+        change(&mut self.tree)
+            .map_err(|e| self.error = Some(e))
+            .ok()
+    }
+
+    fn container(&mut self, node: usize, kind: NodeType) -> Option<()> {
+        self.apply(|tree| {
+            let node_type = tree.node_type(node)?;
+            tree.set_flags(node, node_type | kind)
+        })
+    }
+
+    /// Add a child, with a key if given.
+    fn child(&mut self, parent: usize, key: Option<&str>) -> Option<usize> {
+        self.apply(|tree| {
+            let child = tree.append_child(parent)?;
+            if let Some(key) = key {
+                tree.set_key(child, key)?;
+            }
+            Ok(child)
+        })
+    }
+
+    fn nested_map(&mut self, node: Option<usize>, build: impl FnOnce(&mut MapBuilder<'_>)) {
+        if let Some(node) = node.filter(|&node| self.container(node, NodeType::Map).is_some()) {
+            build(&mut MapBuilder {
+                builder: self,
+                node,
+            });
+        }
+    }
+
+    fn nested_seq(&mut self, node: Option<usize>, build: impl FnOnce(&mut SeqBuilder<'_>)) {
+        if let Some(node) = node.filter(|&node| self.container(node, NodeType::Seq).is_some()) {
+            build(&mut SeqBuilder {
+                builder: self,
+                node,
+            });
+        }
+    }
+}
+
+/// Adds entries to a map being built by a [`TreeBuilder`].
+#[derive(Debug)]
+pub struct MapBuilder<'b> {
+    builder: &'b mut TreeBuilder,
+    node: usize,
+}
+
+impl<'b> MapBuilder<'b> {
+    /// Start an entry with the given key. Its value must be given with one
+    /// of the methods of the returned [`EntryBuilder`].
+    pub fn entry<'m>(&'m mut self, key: &str) -> EntryBuilder<'m, 'b> {
+        let node = self.builder.child(self.node, Some(key));
+        EntryBuilder { map: self, node }
+    }
+}
+
+/// The value of a map entry being built by a [`MapBuilder`]. Each method
+/// returns the map, to add further entries.
+#[derive(Debug)]
+#[must_use = "an entry needs a value"]
+pub struct EntryBuilder<'m, 'b> {
+    map: &'m mut MapBuilder<'b>,
+    node: Option<usize>,
+}
+
+impl<'m, 'b> EntryBuilder<'m, 'b> {
+    /// Give the entry a scalar value.
+    pub fn value(self, val: &str) -> &'m mut MapBuilder<'b> {
+        if let Some(node) = self.node {
+            self.map.builder.apply(|tree| tree.set_val(node, val));
+        }
+        self.map
+    }
+
+    /// Make the entry's value a map, built by the given closure.
+    pub fn map(self, build: impl FnOnce(&mut MapBuilder<'_>)) -> &'m mut MapBuilder<'b> {
+        self.map.builder.nested_map(self.node, build);
+        self.map
+    }
+
+    /// Make the entry's value a sequence, built by the given closure.
+    pub fn seq(self, build: impl FnOnce(&mut SeqBuilder<'_>)) -> &'m mut MapBuilder<'b> {
+        self.map.builder.nested_seq(self.node, build);
+        self.map
+    }
+}
+
+/// Adds items to a sequence being built by a [`TreeBuilder`].
+#[derive(Debug)]
+pub struct SeqBuilder<'b> {
+    builder: &'b mut TreeBuilder,
+    node: usize,
+}
+
+impl<'b> SeqBuilder<'b> {
+    /// Add a scalar item.
+    pub fn push(&mut self, val: &str) -> &mut Self {
+        if let Some(node) = self.builder.child(self.node, None) {
+            self.builder.apply(|tree| tree.set_val(node, val));
+        }
+        self
+    }
+
+    /// Add a map item, built by the given closure.
+    pub fn map(&mut self, build: impl FnOnce(&mut MapBuilder<'_>)) -> &mut Self {
+        let node = self.builder.child(self.node, None);
+        self.builder.nested_map(node, build);
+        self
+    }
+
+    /// Add a sequence item, built by the given closure.
+    pub fn seq(&mut self, build: impl FnOnce(&mut SeqBuilder<'_>)) -> &mut Self {
+        let node = self.builder.child(self.node, None);
+        self.builder.nested_seq(node, build);
+        self
+    }
+}
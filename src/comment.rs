@@ -0,0 +1,386 @@
+use crate::{Error, NodeType, Result, Tree, NONE};
+use std::collections::HashMap;
+
+/// The value tag of a comment node written on a line of its own, which
+/// belongs before the next sibling node (or at the end of its container if
+/// it is the last child). See
+/// [`retain_comments`](crate::ParseOptions#structfield.retain_comments).
+pub const COMMENT_TAG: &str = "!comment";
+/// The value tag of a comment node written at the end of the line of the
+/// node before it: its previous sibling, or its parent's key if it is the
+/// first child. See
+/// [`retain_comments`](crate::ParseOptions#structfield.retain_comments).
+pub const TRAILING_COMMENT_TAG: &str = "!trailing-comment";
+
+/// The key given to comment nodes in maps, which is otherwise ignored.
+const COMMENT_KEY: &str = "#";
+const MARKER_PREFIX: &str = "__ryml_comment_";
+const MARKER_SUFFIX: &str = "__";
+
+/// A comment found in YAML source.
+struct SourceComment<'s> {
+    /// The offset of the `#`.
+    offset: usize,
+    /// The offset of the start of its line.
+    line_start: usize,
+    /// Whether nothing but whitespace comes before it on the line.
+    own_line: bool,
+    /// The text after the `#`, less one leading space.
+    text: &'s str,
+}
+
+/// A comment node replaced by a marker for emitting.
+pub(crate) struct Marker {
+    text: String,
+    trailing: bool,
+    seq_item: bool,
+}
+
+/// Find the comments in YAML source, skipping any `#` within quoted or block
+/// scalars.
+fn scan_comments(source: &str) -> Vec<SourceComment<'_>> {
+    let mut comments = Vec::new();
+    let mut quote = None;
+    let mut block_parent = None;
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let indent = content.len() - content.trim_start_matches(' ').len();
+        if let Some(parent) = block_parent {
+            if content.trim().is_empty() || indent > parent {
+                continue;
+            }
+            block_parent = None;
+        }
+        let mut comment_at = None;
+        let mut prev: Option<char> = None;
+        let mut chars = content.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match quote {
+                // A doubled quote is an escaped one.
+                Some('\'') if c == '\'' && chars.next_if(|&(_, next)| next == '\'').is_none() => {
+                    quote = None;
+                }
+                Some('"') if c == '\\' => {
+                    chars.next();
+                }
+                Some('"') if c == '"' => quote = None,
+                Some(_) => {}
+                None if c == '#' && prev.is_none_or(char::is_whitespace) => {
+                    comment_at = Some(i);
+                    break;
+                }
+                None if matches!(c, '\'' | '"')
+                    && prev.is_none_or(|p| p.is_whitespace() || matches!(p, '[' | '{' | ',')) =>
+                {
+                    quote = Some(c);
+                }
+                None => {}
+            }
+            prev = Some(c);
+        }
+        let code = content[..comment_at.unwrap_or(content.len())].trim_end();
+        if let Some(at) = comment_at {
+            let text = &content[at + 1..];
+            comments.push(SourceComment {
+                offset: start + at,
+                line_start: start,
+                own_line: code.trim_start().is_empty(),
+                text: text.strip_prefix(' ').unwrap_or(text).trim_end(),
+            });
+        }
+        if quote.is_none() && is_block_header(code) {
+            // Block scalar content is indented further than the key which
+            // owns it, or the dash of the sequence entry it makes up.
+            let mut col = indent;
+            let mut dash = indent;
+            while code[col..].starts_with("- ") {
+                dash = col;
+                col = code.len() - code[col + 1..].trim_start().len();
+            }
+            let is_item = col > indent && !code[col..].contains(": ");
+            block_parent = Some(if is_item { dash } else { col });
+        }
+    }
+    comments
+}
+
+/// Whether the line ends with the header of a literal or folded block
+/// scalar, e.g. `|` or `>-`.
+fn is_block_header(code: &str) -> bool {
+    let last = code.rsplit([' ', '\t']).next().unwrap_or("");
+    let mut chars = last.chars();
+    matches!(chars.next(), Some('|' | '>'))
+        && chars.all(|c| c.is_ascii_digit() || c == '+' || c == '-')
+}
+
+impl<'a> Tree<'a> {
+    /// Get the comment written on the lines before a node, as kept by
+    /// [`retain_comments`](crate::ParseOptions#structfield.retain_comments):
+    /// the text of the [`COMMENT_TAG`] nodes directly before it, one line
+    /// each. Returns `None` if there are none, including for the root and
+    /// for trees parsed without retaining comments.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let options = ryml::ParseOptions {
+    ///     retain_comments: true,
+    ///     ..Default::default()
+    /// };
+    /// let tree = ryml::Tree::parse_with("# The port\n# to use\nport: 80\n", &options)?;
+    /// let port = tree.root_ref()?.get("port")?;
+    /// assert_eq!(Some("The port\nto use".into()), port.leading_comment()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn leading_comment(&self, node: usize) -> Result<Option<String>> {
+        let mut lines = Vec::new();
+        let mut prev = self.node_data(node)?.prev_sibling;
+        while prev != NONE {
+            let Some(text) = self.comment_text(prev, COMMENT_TAG)? else {
+                break;
+            };
+            lines.push(text);
+            prev = self.node_data(prev)?.prev_sibling;
+        }
+        if lines.is_empty() {
+            return Ok(None);
+        }
+        lines.reverse();
+        Ok(Some(lines.join("\n")))
+    }
+
+    /// Replace the comment written on the lines before a node (see
+    /// [`leading_comment`](#method.leading_comment)) with the given text,
+    /// inserting a [`COMMENT_TAG`] node for each of its lines. An empty text
+    /// removes the comment. The text is copied into the tree's arena.
+    ///
+    /// The comment is written as `#` lines when emitting with
+    /// [`comments`](crate::EmitOptions#structfield.comments) set, and as a
+    /// tagged scalar otherwise. So that a comment is not mistaken for data,
+    /// this fails with [`Error::CommentsNotRetained`] unless the tree was
+    /// parsed with
+    /// [`retain_comments`](crate::ParseOptions#structfield.retain_comments).
+    /// Fails with [`Error::NodeNotFound`] for the root, which has no parent to
+    /// hold the comment.
+    ///
+    /// [`Error::CommentsNotRetained`]: crate::Error::CommentsNotRetained
+    /// [`Error::NodeNotFound`]: crate::Error::NodeNotFound
+    pub fn set_leading_comment(&mut self, node: usize, text: &str) -> Result<()> {
+        if !self.comments {
+            return Err(Error::CommentsNotRetained);
+        }
+        let parent = self.parent(node)?;
+        loop {
+            let prev = self.node_data(node)?.prev_sibling;
+            if prev == NONE || self.comment_text(prev, COMMENT_TAG)?.is_none() {
+                break;
+            }
+            self.remove(prev)?;
+        }
+        for line in text.lines() {
+            let after = self.node_data(node)?.prev_sibling;
+            self.insert_comment(parent, after, COMMENT_TAG, line)?;
+        }
+        Ok(())
+    }
+
+    /// The text of a comment node with the given tag.
+    fn comment_text(&self, node: usize, tag: &str) -> Result<Option<&str>> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        Ok(
+            (node_type.has_val_tag() && !node_type.is_container() && data.value.tag == tag)
+                .then_some(data.value.scalar),
+        )
+    }
+
+    /// Insert a comment node for each comment in the source the tree was
+    /// just parsed from.
+    pub(crate) fn insert_comments(&mut self, source: &str) -> Result<()> {
+        let comments = scan_comments(source);
+        if comments.is_empty() || self.is_empty() {
+            return Ok(());
+        }
+        // The source is the first thing copied to the arena, so offsets into
+        // the arena are offsets into the source.
+        let base = self.inner.arena().ptr as usize;
+        let mut starts = Vec::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            let scalar = if data.node_type.has_key() {
+                data.key.scalar
+            } else if data.node_type.has_val() && !data.node_type.is_container() {
+                data.value.scalar
+            } else {
+                return Ok(true);
+            };
+            let offset = (scalar.as_ptr() as usize).wrapping_sub(base);
+            if offset < source.len() {
+                starts.push((offset, index));
+            }
+            Ok(true)
+        })?;
+        for comment in comments {
+            let on_line = starts
+                .iter()
+                .rev()
+                .find(|&&(start, _)| start >= comment.line_start && start < comment.offset);
+            match on_line {
+                Some(&(_, node)) if !comment.own_line => {
+                    let node_type = self.node_type(node)?;
+                    let (parent, after) = if node_type.is_container() {
+                        (node, NONE)
+                    } else {
+                        (self.parent(node)?, node)
+                    };
+                    self.insert_comment(parent, after, TRAILING_COMMENT_TAG, comment.text)?;
+                }
+                _ => match starts.iter().find(|&&(start, _)| start > comment.offset) {
+                    Some(&(_, node)) => {
+                        let data = self.node_data(node)?;
+                        let (parent, after) = (data.parent, data.prev_sibling);
+                        self.insert_comment(parent, after, COMMENT_TAG, comment.text)?;
+                    }
+                    None => {
+                        let mut parent = self.root_id()?;
+                        if self.is_stream(parent)? {
+                            parent = self.node_data(parent)?.last_child;
+                        }
+                        if parent != NONE && self.node_type(parent)?.is_container() {
+                            let after = self.node_data(parent)?.last_child;
+                            self.insert_comment(parent, after, COMMENT_TAG, comment.text)?;
+                        }
+                    }
+                },
+            }
+        }
+        self.mark_clean();
+        Ok(())
+    }
+
+    fn insert_comment(&mut self, parent: usize, after: usize, tag: &str, text: &str) -> Result<()> {
+        let node = self.insert_child(parent, after)?;
+        if self.is_map(parent)? {
+            self.to_keyval(node, COMMENT_KEY, text)?;
+        } else {
+            self.to_val(node, text)?;
+        }
+        self.set_val_tag(node, tag)
+    }
+
+    /// Replace every comment node with a marker to be found in the emitted
+    /// YAML by [`restore_comments`], and make sure their containers are in
+    /// block style.
+    pub(crate) fn mark_comments(&mut self) -> Result<HashMap<usize, Marker>> {
+        let mut comments = Vec::new();
+        if !self.is_empty() {
+            self.walk_preorder(self.root_id()?, &mut |index| {
+                let data = self.node_data(index)?;
+                let node_type = data.node_type;
+                if node_type.has_val_tag() && !node_type.is_container() {
+                    let tag = data.value.tag;
+                    if tag == COMMENT_TAG || tag == TRAILING_COMMENT_TAG {
+                        // A trailing comment cannot follow a multi-line scalar.
+                        let trailing = tag == TRAILING_COMMENT_TAG
+                            && (data.prev_sibling == NONE
+                                || !self
+                                    .node_data(data.prev_sibling)?
+                                    .value
+                                    .scalar
+                                    .contains('\n'));
+                        comments.push((index, data.value.scalar.to_owned(), trailing));
+                    }
+                }
+                Ok(true)
+            })?;
+        }
+        let mut markers = HashMap::with_capacity(comments.len());
+        for (id, (node, text, trailing)) in comments.into_iter().enumerate() {
+            let marker = format!("{MARKER_PREFIX}{id}{MARKER_SUFFIX}");
+            let mut parent = self.parent(node)?;
+            let seq_item = !self.is_map(parent)?;
+            if seq_item {
+                self.to_val(node, &marker)?;
+            } else {
+                self.to_keyval(node, &marker, &marker)?;
+            }
+            while parent != NONE {
+                let node_type = self.node_type(parent)?;
+                self.set_flags(
+                    parent,
+                    node_type & !(NodeType::WipStyleFlowSl | NodeType::WipStyleFlowMl),
+                )?;
+                parent = self.node_data(parent)?.parent;
+            }
+            markers.insert(
+                id,
+                Marker {
+                    text,
+                    trailing,
+                    seq_item,
+                },
+            );
+        }
+        Ok(markers)
+    }
+}
+
+/// Replace the lines holding comment markers in emitted YAML with the
+/// comments themselves.
+pub(crate) fn restore_comments(yaml: &str, markers: &HashMap<usize, Marker>) -> String {
+    let mut out = String::with_capacity(yaml.len());
+    for line in yaml.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        let indent = content.len() - content.trim_start_matches(' ').len();
+        let mut prefix = &content[indent..indent];
+        let mut rest = &content[indent..];
+        while let Some(item) = rest.strip_prefix("- ") {
+            rest = item;
+            prefix = &content[indent..content.len() - rest.len()];
+        }
+        let Some(marker) = parse_marker(rest).and_then(|id| markers.get(&id)) else {
+            out.push_str(line);
+            continue;
+        };
+        if marker.seq_item {
+            prefix = &prefix[..prefix.len() - 2];
+        }
+        let comment = if marker.text.is_empty() {
+            "#".to_owned()
+        } else {
+            format!("# {}", marker.text)
+        };
+        if marker.trailing && prefix.is_empty() && out.ends_with('\n') {
+            out.pop();
+            out.push(' ');
+            out.push_str(&comment);
+            out.push('\n');
+        } else {
+            out.push_str(&content[..indent]);
+            out.push_str(prefix);
+            out.push_str(&comment);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Find the id of the marker making up an emitted map entry or sequence
+/// item.
+fn parse_marker(line: &str) -> Option<usize> {
+    let (marker, val) = match line.split_once(": ") {
+        Some((key, val)) => (key, Some(val)),
+        None => (line, None),
+    };
+    if val.is_some_and(|val| val != marker) {
+        return None;
+    }
+    marker
+        .strip_prefix(MARKER_PREFIX)?
+        .strip_suffix(MARKER_SUFFIX)?
+        .parse()
+        .ok()
+}
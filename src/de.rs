@@ -0,0 +1,390 @@
+//! Deserialize any [`Deserialize`] type from a parsed [`Tree`].
+//!
+//! Maps and sequences are read as such, and scalars are read as whatever the
+//! target type asks for. Where the type does not say (e.g. for
+//! `#[serde(untagged)]` enums), plain scalars are interpreted as null, bools,
+//! integers or floats where possible by the tree's
+//! [schema](crate::Tree::schema), and quoted scalars are always strings.
+//! Enum variants are read from either a plain scalar naming a unit variant or
+//! a single-entry map from the variant name to its content.
+//! ```rust
+//! # fn main() -> Result<(), ryml::Error> {
+//! #[derive(serde::Deserialize, PartialEq, Debug)]
+//! struct Server {
+//!     host: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let server: Server = ryml::de::from_str("host: localhost\nports: [80, 443]")?;
+//! assert_eq!(server, Server { host: "localhost".into(), ports: vec![80, 443] });
+//! # Ok(())
+//! # }
+//! ```
+use crate::inner::NodeType;
+use crate::{diff::NormalizedVal, Error, Result, Tree, YamlSchema, NONE};
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use std::fmt::Display;
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Other {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Parse YAML text and deserialize a value from it. Aliases are resolved
+/// before deserializing.
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T> {
+    let mut tree = Tree::parse(text)?;
+    tree.resolve()?;
+    from_tree(&tree)
+}
+
+/// Deserialize a value from the root of a tree. Strings may be borrowed
+/// from the tree. Aliases are not followed, so fail to deserialize unless
+/// the tree has been [resolved](crate::Tree::resolve) first.
+pub fn from_tree<'de, T: Deserialize<'de>>(tree: &'de Tree<'_>) -> Result<T> {
+    T::deserialize(NodeDeserializer {
+        tree,
+        node: tree.root_id()?,
+    })
+}
+
+/// Reads a value from a node of a tree.
+#[derive(Clone, Copy)]
+struct NodeDeserializer<'de, 'a> {
+    tree: &'de Tree<'a>,
+    node: usize,
+}
+
+impl<'de, 'a> NodeDeserializer<'de, 'a> {
+    /// Get the node's value as a scalar, failing for containers.
+    fn scalar(&self) -> Result<Scalar<'de>> {
+        let node_type = self.tree.node_type(self.node)?;
+        if node_type.is_container() {
+            return Err(de::Error::invalid_type(
+                if node_type.is_map() {
+                    de::Unexpected::Map
+                } else {
+                    de::Unexpected::Seq
+                },
+                &"a scalar",
+            ));
+        }
+        if node_type.is_val_ref() {
+            return Err(de::Error::custom(format_args!(
+                "unresolved alias *{}",
+                self.tree.val_ref(self.node)?
+            )));
+        }
+        Ok(Scalar {
+            text: self.tree.val(self.node)?,
+            quoted: self.tree.is_val_quoted(self.node)?
+                || (node_type & (NodeType::WipValSquo | NodeType::WipValDquo)) != NodeType::NoType,
+            schema: self.tree.schema(),
+        })
+    }
+
+    fn children(&self) -> Result<Children<'de, 'a>> {
+        Ok(Children {
+            tree: self.tree,
+            next: self.tree.node_data(self.node)?.first_child,
+            len: self.tree.num_children(self.node)?,
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let node_type = self.tree.node_type(self.node)?;
+        if node_type.is_map() {
+            visitor.visit_map(self.children()?)
+        } else if node_type.is_seq() {
+            visitor.visit_seq(self.children()?)
+        } else if !node_type.has_val() {
+            // An empty document has no value at all.
+            visitor.visit_unit()
+        } else {
+            self.scalar()?.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.scalar()?.text)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let node_type = self.tree.node_type(self.node)?;
+        if !node_type.is_container()
+            && !node_type.is_val_ref()
+            && (!node_type.has_val() || self.scalar()?.is_null())
+        {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let node_type = self.tree.node_type(self.node)?;
+        if !node_type.is_container() {
+            return visitor.visit_enum(self.scalar()?.text.into_deserializer());
+        }
+        let entry = self.tree.node_data(self.node)?.first_child;
+        if !node_type.is_map() || self.tree.num_children(self.node)? != 1 {
+            return Err(de::Error::invalid_type(
+                de::Unexpected::Other("container"),
+                &"a variant name or a map with a single entry",
+            ));
+        }
+        visitor.visit_enum(Variant {
+            tree: self.tree,
+            node: entry,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// A scalar, which is read as whatever type is asked for.
+struct Scalar<'de> {
+    text: &'de str,
+    quoted: bool,
+    schema: YamlSchema,
+}
+
+impl Scalar<'_> {
+    fn is_null(&self) -> bool {
+        !self.quoted && self.schema.is_null(self.text)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Scalar<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.quoted {
+            return visitor.visit_borrowed_str(self.text);
+        }
+        match NormalizedVal::from_plain_in(self.text, self.schema) {
+            NormalizedVal::Null => visitor.visit_unit(),
+            NormalizedVal::Bool(b) => visitor.visit_bool(b),
+            NormalizedVal::Int(int) => {
+                if let Ok(int) = i64::try_from(int) {
+                    visitor.visit_i64(int)
+                } else if let Ok(int) = u64::try_from(int) {
+                    visitor.visit_u64(int)
+                } else {
+                    visitor.visit_i128(int)
+                }
+            }
+            NormalizedVal::Float(float) => visitor.visit_f64(float),
+            _ => visitor.visit_borrowed_str(self.text),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.text)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self.text.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// Walks the children of a map or sequence node.
+struct Children<'de, 'a> {
+    tree: &'de Tree<'a>,
+    next: usize,
+    len: usize,
+}
+
+impl<'de, 'a> Children<'de, 'a> {
+    fn advance(&mut self) -> Result<Option<usize>> {
+        if self.next == NONE {
+            return Ok(None);
+        }
+        let node = self.next;
+        self.next = self.tree.node_data(node)?.next_sibling;
+        self.len -= 1;
+        Ok(Some(node))
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for Children<'de, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        match self.advance()? {
+            Some(node) => seed
+                .deserialize(NodeDeserializer {
+                    tree: self.tree,
+                    node,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Children<'de, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.next == NONE {
+            return Ok(None);
+        }
+        let node = self.next;
+        let node_type = self.tree.node_type(node)?;
+        seed.deserialize(Scalar {
+            text: self.tree.key(node)?,
+            quoted: self.tree.is_key_quoted(node)?
+                || (node_type & (NodeType::WipKeySquo | NodeType::WipKeyDquo)) != NodeType::NoType,
+            schema: self.tree.schema(),
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let node = self
+            .advance()?
+            .ok_or_else(|| <Error as de::Error>::custom("map value read before its key"))?;
+        seed.deserialize(NodeDeserializer {
+            tree: self.tree,
+            node,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+/// The single entry of a map holding an enum variant.
+struct Variant<'de, 'a> {
+    tree: &'de Tree<'a>,
+    node: usize,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'de, 'a> {
+    type Error = Error;
+    type Variant = NodeDeserializer<'de, 'a>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, NodeDeserializer<'de, 'a>)> {
+        let name = seed.deserialize(Scalar {
+            text: self.tree.key(self.node)?,
+            quoted: true,
+            schema: self.tree.schema(),
+        })?;
+        Ok((
+            name,
+            NodeDeserializer {
+                tree: self.tree,
+                node: self.node,
+            },
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for NodeDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -0,0 +1,503 @@
+use crate::{path, NodeType, Result, Tree, NONE};
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// How sequence items contribute to the key paths compared by
+/// [`Tree::key_diff_with`](crate::Tree::key_diff_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeqIndices {
+    /// Every item is a distinct path segment, e.g. `servers[1].host`, so
+    /// sequences of different lengths differ.
+    #[default]
+    ByPosition,
+    /// All items share one path segment, e.g. `servers[].host`, so only the
+    /// keys used anywhere in a sequence are compared.
+    Ignore,
+}
+
+/// The first place where the content of two trees differs, as found by
+/// [`Tree::first_difference`](crate::Tree::first_difference).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// The path of the node which differs, written as for
+    /// [`Tree::key_diff`](crate::Tree::key_diff), or empty for the root.
+    pub path: String,
+    /// A description of the node in the first tree.
+    pub ours: String,
+    /// A description of the node in the second tree.
+    pub theirs: String,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "at the root: {} != {}", self.ours, self.theirs)
+        } else {
+            write!(f, "at {}: {} != {}", self.path, self.ours, self.theirs)
+        }
+    }
+}
+
+impl<'a> Tree<'a> {
+    /// Check if two trees have the same content: the same structure, keys,
+    /// tags and anchors, with values compared as by
+    /// [`val_eq_normalized`](#method.val_eq_normalized). Formatting, such as
+    /// quoting and flow or block style, is ignored where it does not change
+    /// the meaning.
+    #[inline(always)]
+    pub fn content_eq(&self, other: &Tree<'_>) -> Result<bool> {
+        Ok(self.first_difference(other)?.is_none())
+    }
+
+    /// Check if two trees are structurally identical, wherever their nodes and
+    /// scalars happen to be stored: starting from both roots, the nodes must
+    /// have the same kinds, keys, values, tags, anchors and aliases, with
+    /// their children in the same order. Unlike
+    /// [`content_eq`](#method.content_eq), scalars are compared as text, so
+    /// `1` and `"1"` are equal but `1` and `1.0` are not. Formatting, such as
+    /// quoting and flow or block style, is ignored.
+    ///
+    /// This is also how `==` compares trees.
+    ///
+    /// ```
+    /// # use ryml::Tree;
+    /// let a = Tree::parse("a: [1, 2]")?;
+    /// let b = Tree::parse("a:\n  - 1\n  - '2'\n")?;
+    /// assert!(a.deep_eq(&b));
+    /// assert!(a == b);
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn deep_eq(&self, other: &Tree<'_>) -> bool {
+        match (self.is_empty(), other.is_empty()) {
+            (true, true) => true,
+            (false, false) => match (self.root_id(), other.root_id()) {
+                (Ok(root), Ok(other_root)) => self.node_deep_eq(root, other, other_root),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Feed a node and its descendants to a hasher, hashing exactly what
+    /// [`node_deep_eq`](#method.node_deep_eq) compares.
+    fn hash_node<H: Hasher>(&self, node: usize, state: &mut H) {
+        let Ok(data) = self.node_data(node) else {
+            return;
+        };
+        (data.node_type & STRUCTURE_FLAGS).0.hash(state);
+        data.key.hash(state);
+        if data.node_type.is_container() {
+            (data.value.tag, data.value.anchor).hash(state);
+        } else {
+            data.value.hash(state);
+        }
+        let mut child = data.first_child;
+        let mut count = 0usize;
+        while child != NONE {
+            self.hash_node(child, state);
+            count += 1;
+            child = match self.node_data(child) {
+                Ok(data) => data.next_sibling,
+                Err(_) => break,
+            };
+        }
+        // Mark where the children end, so that moving a node up or down a
+        // level changes the hash.
+        count.hash(state);
+    }
+
+    pub(crate) fn node_deep_eq(&self, node: usize, other: &Tree<'_>, other_node: usize) -> bool {
+        let (Ok(ours), Ok(theirs)) = (self.node_data(node), other.node_data(other_node)) else {
+            return false;
+        };
+        let kind = |node_type: NodeType| node_type & STRUCTURE_FLAGS;
+        if kind(ours.node_type) != kind(theirs.node_type)
+            || ours.key != theirs.key
+            || (!ours.node_type.is_container() && ours.value != theirs.value)
+            || (ours.node_type.is_container()
+                && (ours.value.tag, ours.value.anchor) != (theirs.value.tag, theirs.value.anchor))
+        {
+            return false;
+        }
+        let (mut child, mut other_child) = (ours.first_child, theirs.first_child);
+        while child != NONE && other_child != NONE {
+            if !self.node_deep_eq(child, other, other_child) {
+                return false;
+            }
+            match (self.node_data(child), other.node_data(other_child)) {
+                (Ok(data), Ok(other_data)) => {
+                    child = data.next_sibling;
+                    other_child = other_data.next_sibling;
+                }
+                _ => return false,
+            }
+        }
+        child == NONE && other_child == NONE
+    }
+
+    /// Find the first node in document order where the content of two trees
+    /// differs, as compared by [`content_eq`](#method.content_eq).
+    pub fn first_difference(&self, other: &Tree<'_>) -> Result<Option<Difference>> {
+        match (self.is_empty(), other.is_empty()) {
+            (true, true) => Ok(None),
+            (false, false) => self.node_difference(self.root_id()?, other, other.root_id()?, ""),
+            _ => Ok(Some(Difference {
+                path: String::new(),
+                ours: self.describe_node(if self.is_empty() { NONE } else { 0 })?,
+                theirs: other.describe_node(if other.is_empty() { NONE } else { 0 })?,
+            })),
+        }
+    }
+
+    fn node_difference(
+        &self,
+        node: usize,
+        other: &Tree<'_>,
+        other_node: usize,
+        path: &str,
+    ) -> Result<Option<Difference>> {
+        let difference = || -> Result<Option<Difference>> {
+            Ok(Some(Difference {
+                path: path.to_owned(),
+                ours: self.describe_node(node)?,
+                theirs: other.describe_node(other_node)?,
+            }))
+        };
+        let (ours, theirs) = (self.node_data(node)?, other.node_data(other_node)?);
+        let (our_type, their_type) = (ours.node_type, theirs.node_type);
+        if our_type.is_map() != their_type.is_map()
+            || our_type.is_seq() != their_type.is_seq()
+            || our_type.has_val_tag() != their_type.has_val_tag()
+            || (our_type.has_val_tag() && ours.value.tag != theirs.value.tag)
+            || our_type.has_val_anchor() != their_type.has_val_anchor()
+            || (our_type.has_val_anchor() && ours.value.anchor != theirs.value.anchor)
+        {
+            return difference();
+        }
+        if !our_type.is_container() {
+            if self.val_eq_normalized(node, other, other_node)?
+                || (self.normalized_val(node)?.is_none()
+                    && other.normalized_val(other_node)?.is_none())
+            {
+                return Ok(None);
+            }
+            return difference();
+        }
+        let is_seq = our_type.is_seq();
+        let (mut child, mut other_child) = (ours.first_child, theirs.first_child);
+        let mut pos = 0;
+        while child != NONE || other_child != NONE {
+            let key = if is_seq || child == NONE {
+                None
+            } else {
+                Some(self.key(child)?)
+            };
+            let mut child_path = path.to_owned();
+            match key {
+                None if is_seq => path::push_index(&mut child_path, pos),
+                None => path::push_key(&mut child_path, other.key(other_child)?),
+                Some(key) => path::push_key(&mut child_path, key),
+            }
+            let child_path = child_path.as_str();
+            let keys_differ = !is_seq
+                && child != NONE
+                && other_child != NONE
+                && self.key(child)? != other.key(other_child)?;
+            if child == NONE || other_child == NONE || keys_differ {
+                return Ok(Some(Difference {
+                    path: child_path.to_owned(),
+                    ours: self.describe_node(child)?,
+                    theirs: other.describe_node(other_child)?,
+                }));
+            }
+            if let Some(difference) = self.node_difference(child, other, other_child, child_path)? {
+                return Ok(Some(difference));
+            }
+            child = self.inner.next_sibling(child)?;
+            other_child = other.inner.next_sibling(other_child)?;
+            pos += 1;
+        }
+        Ok(None)
+    }
+
+    /// Describe a node briefly for a [`Difference`].
+    fn describe_node(&self, node: usize) -> Result<String> {
+        if node == NONE {
+            return Ok("nothing".into());
+        }
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        let mut description = String::new();
+        if node_type.has_key() {
+            description += &format!("{:?}: ", self.key(node)?);
+        }
+        if node_type.has_val_tag() {
+            description += &format!("{} ", data.value.tag);
+        }
+        if node_type.has_val_anchor() {
+            description += &format!("&{} ", data.value.anchor);
+        }
+        if node_type.is_map() {
+            description += &format!("a map of {} entries", self.num_children(node)?);
+        } else if node_type.is_seq() {
+            description += &format!("a sequence of {} items", self.num_children(node)?);
+        } else if node_type.is_val_ref() {
+            description += &format!("*{}", data.value.anchor);
+        } else {
+            description += &format!("{:?}", data.value.scalar);
+        }
+        Ok(description)
+    }
+
+    /// Compare the key structure of two trees, ignoring values. Returns the
+    /// paths present only in this tree and the paths present only in the
+    /// other, each in document order.
+    ///
+    /// Paths are written with map keys joined by `.` and sequence items as
+    /// `[index]`, e.g. `servers[0].host`. Every node gets a path, so a
+    /// missing section is reported along with everything inside it.
+    ///
+    /// Sequence items are compared by position; see
+    /// [`key_diff_with`](#method.key_diff_with) to ignore positions.
+    #[inline(always)]
+    pub fn key_diff(&self, other: &Tree<'_>) -> Result<(Vec<String>, Vec<String>)> {
+        self.key_diff_with(other, SeqIndices::ByPosition)
+    }
+
+    /// Compare the key structure of two trees, ignoring values, like
+    /// [`key_diff`](#method.key_diff) but with the given handling of sequence
+    /// indices.
+    pub fn key_diff_with(
+        &self,
+        other: &Tree<'_>,
+        seq_indices: SeqIndices,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let ours = self.key_paths(seq_indices)?;
+        let theirs = other.key_paths(seq_indices)?;
+        let only_in = |paths: Vec<String>, other: &[String]| {
+            let other: HashSet<&String> = other.iter().collect();
+            paths
+                .into_iter()
+                .filter(|path| !other.contains(path))
+                .collect::<Vec<_>>()
+        };
+        Ok((only_in(ours.clone(), &theirs), only_in(theirs, &ours)))
+    }
+
+    /// Check if the values of two nodes, which may belong to different trees,
+    /// mean the same thing even if they are written differently. Each value
+    /// is normalized as follows before comparing:
+    ///
+    /// - Quoted and block scalars are strings, kept exactly as parsed.
+    /// - Plain scalars are trimmed of surrounding whitespace, then read as null
+    ///   (`~`, `null`, `Null`, `NULL` or empty), a boolean (`true`, `yes`, `on`
+    ///   and `false`, `no`, `off`, in lower, title or upper case), an integer
+    ///   (decimal, `0x` hex or `0o` octal), a float (including `.inf` and
+    ///   `.nan`), or else a string.
+    /// - Aliases are compared by the name of the anchor they refer to; call
+    ///   [`resolve`](#method.resolve) first to compare their targets.
+    ///
+    /// Values of different kinds never compare equal, so `1` differs from
+    /// `1.0` and `"true"` from `true`. Tags are ignored. Containers compare
+    /// unequal to everything.
+    pub fn val_eq_normalized(
+        &self,
+        node: usize,
+        other: &Tree<'_>,
+        other_node: usize,
+    ) -> Result<bool> {
+        match (
+            self.normalized_val(node)?,
+            other.normalized_val(other_node)?,
+        ) {
+            (Some(ours), Some(theirs)) => Ok(ours == theirs),
+            _ => Ok(false),
+        }
+    }
+
+    fn normalized_val(&self, node: usize) -> Result<Option<NormalizedVal<'_>>> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.is_val_ref() {
+            Ok(Some(NormalizedVal::Alias(data.value.anchor)))
+        } else if node_type.is_container() || !node_type.has_val() {
+            Ok(None)
+        } else if node_type.is_val_quoted()
+            || (node_type & (NodeType::WipValLiteral | NodeType::WipValFolded)) != NodeType::NoType
+        {
+            Ok(Some(NormalizedVal::Str(data.value.scalar)))
+        } else {
+            Ok(Some(NormalizedVal::from_plain(data.value.scalar.trim())))
+        }
+    }
+
+    /// Collect the path of every node except the root, in document order and
+    /// without duplicates.
+    fn key_paths(&self, seq_indices: SeqIndices) -> Result<Vec<String>> {
+        let mut paths = Vec::new();
+        if !self.is_empty() {
+            let mut seen = HashSet::new();
+            self.collect_key_paths(self.root_id()?, "", seq_indices, &mut seen, &mut paths)?;
+        }
+        Ok(paths)
+    }
+
+    fn collect_key_paths(
+        &self,
+        node: usize,
+        prefix: &str,
+        seq_indices: SeqIndices,
+        seen: &mut HashSet<String>,
+        paths: &mut Vec<String>,
+    ) -> Result<()> {
+        let is_seq = self.is_seq(node)?;
+        let mut child = self.inner.first_child(node)?;
+        let mut pos = 0;
+        while child != NONE {
+            let mut path = prefix.to_owned();
+            match seq_indices {
+                _ if !is_seq => path::push_key(&mut path, self.key(child)?),
+                SeqIndices::ByPosition => path::push_index(&mut path, pos),
+                SeqIndices::Ignore => path.push_str("[]"),
+            }
+            if seen.insert(path.clone()) {
+                paths.push(path.clone());
+            }
+            self.collect_key_paths(child, &path, seq_indices, seen, paths)?;
+            child = self.inner.next_sibling(child)?;
+            pos += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Hashes the content of the tree, consistently with `==` and
+/// [`Tree::deep_eq`], so trees with the same content can be used as keys of
+/// a [`HashMap`](std::collections::HashMap) whatever their memory layout.
+///
+/// **Note**: This visits every node of the tree, so it costs as much as a
+/// full traversal. Avoid hashing large trees repeatedly, e.g. by hashing
+/// once and keeping the result.
+impl Hash for Tree<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.root_id() {
+            Ok(root) if !self.is_empty() => {
+                true.hash(state);
+                self.hash_node(root, state);
+            }
+            _ => false.hash(state),
+        }
+    }
+}
+
+/// The node type flags compared by [`Tree::deep_eq`], leaving out the style
+/// flags.
+const STRUCTURE_FLAGS: NodeType = NodeType(
+    NodeType::Val.0
+        | NodeType::Key.0
+        | NodeType::Map.0
+        | NodeType::Seq.0
+        | NodeType::Doc.0
+        | NodeType::Stream.0
+        | NodeType::KeyRef.0
+        | NodeType::ValRef.0
+        | NodeType::KeyAnch.0
+        | NodeType::ValAnch.0
+        | NodeType::KeyTag.0
+        | NodeType::ValTag.0,
+);
+
+/// The meaning of a scalar value, as compared by
+/// [`Tree::val_eq_normalized`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum NormalizedVal<'s> {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str(&'s str),
+    Alias(&'s str),
+}
+
+impl<'s> NormalizedVal<'s> {
+    /// Read a plain scalar by the rules of the given schema, rather than the
+    /// lenient mix of YAML 1.1 and 1.2 rules used for comparisons.
+    pub(crate) fn from_plain_in(scalar: &'s str, schema: crate::YamlSchema) -> Self {
+        if schema.is_null(scalar) {
+            Self::Null
+        } else if let Some(b) = schema.to_bool(scalar) {
+            Self::Bool(b)
+        } else if let Some(int) = schema.to_int(scalar) {
+            Self::Int(int)
+        } else {
+            match Self::from_plain(scalar) {
+                Self::Float(float) if float.is_finite() || schema.has_special_floats() => {
+                    Self::Float(float)
+                }
+                _ => Self::Str(scalar),
+            }
+        }
+    }
+
+    pub(crate) fn from_plain(scalar: &'s str) -> Self {
+        match scalar {
+            "" | "~" | "null" | "Null" | "NULL" => return Self::Null,
+            "true" | "True" | "TRUE" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => {
+                return Self::Bool(true)
+            }
+            "false" | "False" | "FALSE" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => {
+                return Self::Bool(false)
+            }
+            ".nan" | ".NaN" | ".NAN" => return Self::Float(f64::NAN),
+            _ => {}
+        }
+        let (negative, unsigned) = match scalar.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, scalar.strip_prefix('+').unwrap_or(scalar)),
+        };
+        if let ".inf" | ".Inf" | ".INF" = unsigned {
+            return Self::Float(if negative {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            });
+        }
+        let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x") {
+            (16, hex)
+        } else if let Some(octal) = unsigned.strip_prefix("0o") {
+            (8, octal)
+        } else {
+            (10, unsigned)
+        };
+        if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) {
+            if let Ok(int) = i128::from_str_radix(digits, radix) {
+                return Self::Int(if negative { -int } else { int });
+            }
+        }
+        // Rust also accepts spellings like `inf` and `NaN`, which YAML does not.
+        if radix == 10
+            && unsigned.contains(|c: char| c.is_ascii_digit())
+            && unsigned
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))
+        {
+            if let Ok(float) = scalar.parse::<f64>() {
+                return Self::Float(float);
+            }
+        }
+        Self::Str(scalar)
+    }
+}
+
+impl PartialEq for NormalizedVal<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Self::Str(a), Self::Str(b)) | (Self::Alias(a), Self::Alias(b)) => a == b,
+            _ => false,
+        }
+    }
+}
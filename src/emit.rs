@@ -0,0 +1,721 @@
+use crate::{
+    comment::restore_comments, inner, Error, NodeRef, NodeType, Result, Tree, YamlType,
+    COMMENT_TAG, NONE, TRAILING_COMMENT_TAG,
+};
+use std::fmt::Write;
+
+/// Options for customizing how a [`Tree`] is emitted as YAML.
+///
+/// The default options produce exactly the same output as
+/// [`Tree::emit`](crate::Tree::emit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmitOptions {
+    /// Extra characters which should never appear literally in the output,
+    /// e.g. `%` or `@` when the YAML will be embedded in a shell script. Any
+    /// key or value scalar containing one of them is emitted double quoted,
+    /// with each occurrence written as an escape sequence (`%` becomes
+    /// `\x25`).
+    pub escape: Vec<char>,
+    /// Escape every non-ASCII character in key and value scalars, for
+    /// consumers which only handle ASCII. As with
+    /// [`escape`](#structfield.escape), affected scalars are emitted double
+    /// quoted, with characters written as `\uXXXX`, or `\UXXXXXXXX` outside
+    /// the Basic Multilingual Plane (YAML has no surrogate pairs). JSON
+    /// output uses `\uXXXX` escapes, with surrogate pairs where needed.
+    pub escape_non_ascii: bool,
+    /// Emit the scalar entries of every map before its nested maps and
+    /// sequences, keeping the relative order within each group. Only the
+    /// output is affected; the tree itself is left as it is.
+    pub group_scalars_first: bool,
+    /// Emit the entries of every map sorted by key, as with
+    /// [`sort_keys`](crate::Tree::sort_keys), for output which does not
+    /// depend on the order the entries were added in. Only the output is
+    /// affected; the tree itself is left as it is. With
+    /// [`group_scalars_first`](#structfield.group_scalars_first), each group
+    /// is sorted.
+    pub sort_keys: bool,
+    /// How tags are handled when emitting JSON with
+    /// [`emit_json_with`](crate::Tree::emit_json_with).
+    pub json_tags: JsonTagEncoding,
+    /// The line ending written after each line of YAML.
+    pub line_ending: LineEnding,
+    /// The number of spaces per level of indentation in block maps and
+    /// sequences. Defaults to 2. Values below 2 are treated as 2, and above
+    /// 9 as 9, since a block scalar may need its indentation written as a
+    /// single digit.
+    pub indent: usize,
+    /// Start the output with a `---` document marker. Streams already mark
+    /// each of their documents, so are unaffected.
+    pub doc_start: bool,
+    /// The widest a single-line flow map or sequence (e.g. `[1, 2, 3]`) may
+    /// be, including its indentation and key. Wider ones are emitted in
+    /// block style instead. The width is estimated from the scalars, so
+    /// quoting and escaping may make the actual line slightly longer.
+    pub max_flow_width: Option<usize>,
+    /// Write comment nodes, as produced by
+    /// [`retain_comments`](crate::ParseOptions#structfield.retain_comments),
+    /// as `#` comments rather than tagged scalars. Any map or sequence
+    /// holding a comment is emitted in block style.
+    pub comments: bool,
+}
+
+impl Default for EmitOptions {
+    fn default() -> Self {
+        Self {
+            escape: Vec::new(),
+            escape_non_ascii: false,
+            group_scalars_first: false,
+            sort_keys: false,
+            json_tags: JsonTagEncoding::default(),
+            line_ending: LineEnding::default(),
+            indent: 2,
+            doc_start: false,
+            max_flow_width: None,
+            comments: false,
+        }
+    }
+}
+
+/// The line ending used when emitting YAML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// Unix line endings (`\n`), as rapidyaml writes them.
+    #[default]
+    Lf,
+    /// Windows line endings (`\r\n`).
+    CrLf,
+}
+
+/// The key holding the tag of a tagged value wrapped by
+/// [`JsonTagEncoding::WrapObject`].
+pub const JSON_TAG_KEY: &str = "$tag";
+/// The key holding the original value of a tagged value wrapped by
+/// [`JsonTagEncoding::WrapObject`].
+pub const JSON_VALUE_KEY: &str = "value";
+
+/// How tags, which JSON has no way to represent, are handled when emitting
+/// JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonTagEncoding {
+    /// Fail with [`Error::JsonUnsupported`] on any tag.
+    #[default]
+    Error,
+    /// Silently drop all tags.
+    Drop,
+    /// Replace every tagged value with an object holding the tag under
+    /// [`JSON_TAG_KEY`] and the value itself under [`JSON_VALUE_KEY`], e.g.
+    /// `{"$tag": "!str","value": "d"}`. Tags on keys are dropped, since JSON
+    /// keys can only be strings.
+    WrapObject,
+}
+
+impl EmitOptions {
+    #[inline(always)]
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether scalars are transformed to escape any characters.
+    #[inline(always)]
+    fn escapes_any(&self) -> bool {
+        !self.escape.is_empty() || self.escape_non_ascii
+    }
+
+    /// Whether the character has to be written as an escape sequence.
+    #[inline(always)]
+    fn escapes(&self, c: char) -> bool {
+        self.escape.contains(&c) || (self.escape_non_ascii && !c.is_ascii())
+    }
+}
+
+impl<'a> Tree<'a> {
+    /// Emit tree as YAML to an owned string, using the given options.
+    ///
+    /// **Note**: The `escape`, `escape_non_ascii`, `group_scalars_first`,
+    /// `sort_keys`, `max_flow_width` and `comments` options require the tree
+    /// to be cloned, so are more expensive than [`emit`](#method.emit).
+    pub fn emit_with(&self, options: &EmitOptions) -> Result<String> {
+        if options.is_default() {
+            return self.emit();
+        }
+        let indent = options.indent.clamp(2, 9);
+        let mut yaml = if !options.escapes_any()
+            && !options.group_scalars_first
+            && !options.sort_keys
+            && options.max_flow_width.is_none()
+            && !options.comments
+        {
+            self.emit_formatted(indent, options.doc_start)?
+        } else {
+            let mut tree = self.clone();
+            if options.escapes_any() {
+                tree.escape_scalars(options)?;
+            }
+            if options.sort_keys {
+                tree.sort_keys()?;
+            }
+            if options.group_scalars_first {
+                tree.group_scalars_first()?;
+            }
+            if let Some(max_width) = options.max_flow_width {
+                tree.break_wide_flow(max_width, indent)?;
+            }
+            if options.comments {
+                let markers = tree.mark_comments()?;
+                restore_comments(&tree.emit_formatted(indent, options.doc_start)?, &markers)
+            } else {
+                tree.emit_formatted(indent, options.doc_start)?
+            }
+        };
+        if options.line_ending == LineEnding::CrLf {
+            // Double quoted scalars are written with line breaks escaped, and
+            // the line breaks of any other scalar are normalized back to `\n`
+            // when parsed, so every line break can be converted.
+            yaml = yaml.replace('\n', "\r\n");
+        }
+        self.mark_clean();
+        Ok(yaml)
+    }
+
+    /// Emit tree as YAML to an owned string, letting a callback render scalar
+    /// values. The callback is called with each node which has a scalar
+    /// value (aliases excepted), in document order, along with a writer for
+    /// its replacement text. It returns `None` to keep the default output, or
+    /// `Some` once it has written the replacement, which is emitted verbatim
+    /// with no quoting or escaping. Errors returned by the callback abort the
+    /// emit. Keys and containers are always emitted as usual.
+    ///
+    /// ```rust
+    /// # use std::fmt::Write;
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("mode: 420\nname: app\n")?;
+    /// let yaml = tree.emit_with_formatter(|node, out| {
+    ///     if node.key().ok()? != "mode" {
+    ///         return None;
+    ///     }
+    ///     let mode: u32 = node.val().ok()?.parse().ok()?;
+    ///     Some(write!(out, "0o{:o}", mode).map_err(|e| ryml::Error::Other {
+    ///         message: e.to_string(),
+    ///     }))
+    /// })?;
+    /// assert_eq!(yaml, "mode: 0o644\nname: app\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// **Note**: The tree is cloned in order to apply the replacements, so
+    /// this is more expensive than [`emit`](#method.emit).
+    pub fn emit_with_formatter<F>(&self, mut f: F) -> Result<String>
+    where
+        F: FnMut(&NodeRef<'a, '_, '_, &Tree<'a>>, &mut dyn Write) -> Option<Result<()>>,
+    {
+        let mut replacements = Vec::new();
+        if !self.is_empty() {
+            self.walk_preorder(self.root_id()?, &mut |node| {
+                let node_type = self.node_type(node)?;
+                if node_type.has_val() && !node_type.is_container() && !node_type.is_val_ref() {
+                    let mut text = String::new();
+                    if let Some(result) = f(&self.get(node)?, &mut text) {
+                        result?;
+                        replacements.push((node, text));
+                    }
+                }
+                Ok(true)
+            })?;
+        }
+        let mut tree = self.clone();
+        for (node, text) in replacements {
+            let node_type = tree.node_type(node)?;
+            tree.set_val(node, &text)?;
+            tree.set_flags(
+                node,
+                (node_type & !NodeType::WipValStyle) | NodeType::WipValPlain,
+            )?;
+        }
+        let yaml = tree.emit()?;
+        self.mark_clean();
+        Ok(yaml)
+    }
+
+    /// Emit tree as JSON to an owned string, using the given options.
+    ///
+    /// Unlike [`emit_json`](#method.emit_json), this always writes valid
+    /// JSON: strings are fully escaped, and each scalar is written as the
+    /// JSON value of its [resolved type](#method.resolved_tag), as with
+    /// [`to_json_value`](#method.to_json_value), so `~` is `null` and `0x1f`
+    /// is `31`. Unlike `to_json_value`, which builds new values and so can
+    /// copy an anchored node in place of each alias, this writes out the
+    /// tree as it is, and fails with [`Error::JsonUnsupported`] on anchors
+    /// and aliases, as on `.inf` and `.nan`. Characters in the
+    /// [`escape`](EmitOptions#structfield.escape) set, and non-ASCII
+    /// characters with
+    /// [`escape_non_ascii`](EmitOptions#structfield.escape_non_ascii), are
+    /// written as `\u` escapes.
+    ///
+    /// The JSON is written on a single line, so a non-default
+    /// [`indent`](EmitOptions#structfield.indent),
+    /// [`line_ending`](EmitOptions#structfield.line_ending) or
+    /// [`max_flow_width`](EmitOptions#structfield.max_flow_width) fails with
+    /// [`Error::UnsupportedEmitOption`], as do
+    /// [`doc_start`](EmitOptions#structfield.doc_start) and
+    /// [`comments`](EmitOptions#structfield.comments), which JSON has no
+    /// syntax for.
+    /// An empty tree is written as `null`.
+    pub fn emit_json_with(&self, options: &EmitOptions) -> Result<String> {
+        if options.indent > 2 {
+            return Err(Error::UnsupportedEmitOption("indent"));
+        }
+        if options.line_ending != LineEnding::Lf {
+            return Err(Error::UnsupportedEmitOption("line_ending"));
+        }
+        if options.max_flow_width.is_some() {
+            return Err(Error::UnsupportedEmitOption("max_flow_width"));
+        }
+        if options.doc_start {
+            return Err(Error::UnsupportedEmitOption("doc_start"));
+        }
+        if options.comments {
+            return Err(Error::UnsupportedEmitOption("comments"));
+        }
+        let mut out = String::new();
+        if self.is_empty() {
+            out.push_str("null");
+        } else if options.group_scalars_first || options.sort_keys {
+            let mut tree = self.clone();
+            if options.sort_keys {
+                tree.sort_keys()?;
+            }
+            if options.group_scalars_first {
+                tree.group_scalars_first()?;
+            }
+            tree.write_json(tree.root_id()?, options, &mut out)?;
+        } else {
+            self.write_json(self.root_id()?, options, &mut out)?;
+        }
+        self.mark_clean();
+        Ok(out)
+    }
+
+    /// Check the tree only uses features which rapidyaml can emit as JSON.
+    pub(crate) fn check_json_support(&self) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        self.walk_preorder(self.root_id()?, &mut |node| {
+            let node_type = self.node_data(node)?.node_type;
+            if node_type.has_anchor() || node_type.is_ref() {
+                return Err(Error::JsonUnsupported("anchors"));
+            }
+            // Tags on containers are dropped rather than rejected.
+            if node_type.has_key_tag() || (node_type.has_val_tag() && !node_type.is_container()) {
+                return Err(Error::JsonUnsupported("tags"));
+            }
+            Ok(true)
+        })?;
+        Ok(())
+    }
+
+    /// Emit the tree as YAML with the given number of spaces per level of
+    /// indentation, optionally starting with a document marker.
+    fn emit_formatted(&self, indent: usize, doc_start: bool) -> Result<String> {
+        if indent == 2 && !doc_start {
+            return self.emit();
+        }
+        // Deeply nested trees can outgrow the usual estimate when indented
+        // further, so ask for the needed size if it is too small.
+        let mut buf = vec![0; self.estimate_emit_len()];
+        loop {
+            let written = inner::ffi::emit_with_options(
+                self.inner.as_ref().unwrap(),
+                inner::Substr {
+                    ptr: buf.as_mut_ptr(),
+                    len: buf.len(),
+                },
+                indent,
+                doc_start,
+                false,
+            )?;
+            // Nothing to write comes back as a null slice too.
+            if !written.ptr.is_null() || written.len == 0 {
+                return Ok(written.to_string());
+            }
+            buf.resize(written.len, 0);
+        }
+    }
+
+    /// Mark every single-line flow container whose estimated width exceeds
+    /// `max_width` to be emitted in block style instead.
+    fn break_wide_flow(&mut self, max_width: usize, indent: usize) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut wide = Vec::new();
+        let mut depths = vec![(self.root_id()?, 0)];
+        while let Some((node, depth)) = depths.pop() {
+            let data = self.node_data(node)?;
+            if (data.node_type & NodeType::WipStyleFlowSl) != NodeType::NoType {
+                if data.first_child == NONE || depth * indent + self.flow_width(node)? <= max_width
+                {
+                    continue;
+                }
+                wide.push((node, data.node_type));
+            }
+            let mut child = data.first_child;
+            while child != NONE {
+                depths.push((child, depth + 1));
+                child = self.node_data(child)?.next_sibling;
+            }
+        }
+        for (node, node_type) in wide {
+            self.set_flags(node, node_type & !NodeType::WipStyleFlowSl)?;
+        }
+        Ok(())
+    }
+
+    /// Estimate the width of a node written on a single line in flow style,
+    /// including its key.
+    fn flow_width(&self, node: usize) -> Result<usize> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        let mut width = 0;
+        if node_type.has_key() {
+            width += data.key.scalar.len() + 2;
+            if node_type.is_key_quoted() {
+                width += 2;
+            }
+        }
+        if node_type.has_val_tag() {
+            width += data.value.tag.len() + 1;
+        }
+        if node_type.has_val_anchor() {
+            width += data.value.anchor.len() + 2;
+        }
+        if node_type.is_val_ref() {
+            width += data.value.anchor.len() + 1;
+        } else if node_type.is_container() {
+            width += 2;
+            let mut child = data.first_child;
+            while child != NONE {
+                width += self.flow_width(child)?;
+                child = self.node_data(child)?.next_sibling;
+                if child != NONE {
+                    width += 1;
+                }
+            }
+        } else {
+            width += data.value.scalar.len();
+            if node_type.is_val_quoted() {
+                width += 2;
+            }
+        }
+        Ok(width)
+    }
+
+    /// A buffer size large enough to emit the whole tree.
+    pub(crate) fn estimate_emit_len(&self) -> usize {
+        self.inner.capacity() * 32 + self.inner.arena_capacity()
+    }
+
+    fn write_json(&self, node: usize, options: &EmitOptions, out: &mut String) -> Result<()> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.is_stream() {
+            return Err(Error::JsonUnsupported("streams"));
+        }
+        if node_type.has_anchor() || node_type.is_ref() {
+            return Err(Error::JsonUnsupported("anchors"));
+        }
+        let fail_on_tags = options.json_tags == JsonTagEncoding::Error;
+        if node_type.has_key() {
+            if node_type.has_key_tag() && fail_on_tags {
+                return Err(Error::JsonUnsupported("tags"));
+            }
+            write_json_string(data.key.scalar, options, out);
+            out.push_str(": ");
+        }
+        let wrap = node_type.has_val_tag() && options.json_tags == JsonTagEncoding::WrapObject;
+        if node_type.has_val_tag() && fail_on_tags {
+            return Err(Error::JsonUnsupported("tags"));
+        } else if wrap {
+            out.push('{');
+            write_json_string(JSON_TAG_KEY, options, out);
+            out.push_str(": ");
+            write_json_string(data.value.tag, options, out);
+            out.push(',');
+            write_json_string(JSON_VALUE_KEY, options, out);
+            out.push_str(": ");
+        }
+        if node_type.is_container() {
+            let (open, close) = if node_type.is_seq() {
+                ('[', ']')
+            } else {
+                ('{', '}')
+            };
+            out.push(open);
+            let mut child = data.first_child;
+            while child != NONE {
+                if child != data.first_child {
+                    out.push(',');
+                }
+                self.write_json(child, options, out)?;
+                child = self.node_data(child)?.next_sibling;
+            }
+            out.push(close);
+        } else {
+            let scalar = if node_type.has_val() {
+                data.value.scalar
+            } else {
+                ""
+            };
+            let schema = self.tag_schema();
+            let int = schema
+                .to_int(scalar)
+                .filter(|&int| i64::try_from(int).is_ok() || u64::try_from(int).is_ok());
+            match (self.resolved_tag(node)?, schema.to_bool(scalar), int) {
+                (YamlType::Null, ..) => out.push_str("null"),
+                (YamlType::Bool, Some(boolean), _) => write!(out, "{}", boolean).unwrap(),
+                (YamlType::Int, _, Some(int)) => write!(out, "{}", int).unwrap(),
+                (YamlType::Float, ..) => match self.val_f64(node) {
+                    Ok(float) if !float.is_finite() => {
+                        return Err(Error::JsonUnsupported("infinite or NaN floats"));
+                    }
+                    Ok(_) if is_json_number(scalar) => out.push_str(scalar),
+                    Ok(float) => write!(out, "{:?}", float).unwrap(),
+                    Err(_) => write_json_string(scalar, options, out),
+                },
+                _ => write_json_string(scalar, options, out),
+            }
+        }
+        if wrap {
+            out.push('}');
+        }
+        Ok(())
+    }
+
+    /// Emit tree as YAML to an owned string, with the entries of every map
+    /// sorted by key. A shorthand for [`emit_with`](#method.emit_with) with
+    /// [`sort_keys`](EmitOptions#structfield.sort_keys) set.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("b: 1\na:\n  z: 2\n  y: 3\n")?;
+    /// assert_eq!("a:\n  y: 3\n  z: 2\nb: 1\n", tree.emit_sorted()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn emit_sorted(&self) -> Result<String> {
+        self.emit_with(&EmitOptions {
+            sort_keys: true,
+            ..Default::default()
+        })
+    }
+
+    /// Sort the entries of every map in the tree by key, comparing the key
+    /// text byte by byte. The sort is stable, so entries with duplicate keys
+    /// keep their order. Sequences keep their order, though maps inside them
+    /// are sorted.
+    ///
+    /// Comment nodes, as produced by
+    /// [`retain_comments`](crate::ParseOptions#structfield.retain_comments),
+    /// move with the entry they belong to: [`COMMENT_TAG`] comments with the
+    /// entry after them, and [`TRAILING_COMMENT_TAG`] comments with the entry
+    /// before them. Comments after the last entry stay at the end.
+    pub fn sort_keys(&mut self) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut maps = Vec::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            if self.is_map(index)? {
+                maps.push(index);
+            }
+            Ok(true)
+        })?;
+        for map in maps {
+            // Group each entry with its comments, keyed by the entry's key.
+            let mut entries: Vec<(String, Vec<usize>)> = Vec::new();
+            let mut pending = Vec::new();
+            let mut child = self.inner.first_child(map)?;
+            while child != NONE {
+                let data = self.node_data(child)?;
+                let tag = data.value.tag;
+                match entries.last_mut() {
+                    Some((_, nodes)) if tag == TRAILING_COMMENT_TAG && pending.is_empty() => {
+                        nodes.push(child)
+                    }
+                    _ if tag == COMMENT_TAG || tag == TRAILING_COMMENT_TAG => pending.push(child),
+                    _ => {
+                        pending.push(child);
+                        entries.push((data.key.scalar.to_owned(), std::mem::take(&mut pending)));
+                    }
+                }
+                child = data.next_sibling;
+            }
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            // Move each node to the end in turn, leaving them in order.
+            for node in entries
+                .into_iter()
+                .flat_map(|(_, nodes)| nodes)
+                .chain(pending)
+            {
+                let last = self.inner.last_child(map)?;
+                if node != last {
+                    self.move_node(node, last)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the container entries of every map after its scalar entries.
+    fn group_scalars_first(&mut self) -> Result<()> {
+        let mut maps = Vec::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            if self.is_map(index)? {
+                maps.push(index);
+            }
+            Ok(true)
+        })?;
+        for map in maps {
+            let mut containers = Vec::new();
+            let mut child = self.inner.first_child(map)?;
+            while child != NONE {
+                if self.is_container(child)? {
+                    containers.push(child);
+                }
+                child = self.inner.next_sibling(child)?;
+            }
+            for container in containers {
+                let last = self.inner.last_child(map)?;
+                if container != last {
+                    self.move_node(container, last)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace every scalar containing a character the options escape with
+    /// its escaped, double quoted form, marked to be written out verbatim.
+    fn escape_scalars(&mut self, options: &EmitOptions) -> Result<()> {
+        let mut nodes = Vec::new();
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            nodes.push(index);
+            Ok(true)
+        })?;
+        for node in nodes {
+            let (node_type, key, val) = {
+                let data = self.node_data(node)?;
+                let node_type = data.node_type;
+                let key = (node_type.has_key() && !node_type.is_key_ref())
+                    .then(|| escape_scalar(data.key.scalar, options))
+                    .flatten();
+                let val = (node_type.has_val() && !node_type.is_val_ref())
+                    .then(|| escape_scalar(data.value.scalar, options))
+                    .flatten();
+                (node_type, key, val)
+            };
+            let mut new_type = node_type;
+            if let Some(key) = key {
+                self.set_key(node, &key)?;
+                new_type = (new_type & !NodeType::WipKeyStyle) | NodeType::WipKeyPlain;
+            }
+            if let Some(val) = val {
+                self.set_val(node, &val)?;
+                new_type = (new_type & !NodeType::WipValStyle) | NodeType::WipValPlain;
+            }
+            if new_type != node_type {
+                self.set_flags(node, new_type)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the scalar as a JSON string, escaping the given characters as well
+/// as any which JSON requires to be escaped.
+fn write_json_string(scalar: &str, options: &EmitOptions, out: &mut String) {
+    out.push('"');
+    for c in scalar.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' || options.escapes(c) => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    let _ = write!(out, "\\u{:04x}", unit);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Check if the scalar is a number as JSON defines it.
+fn is_json_number(scalar: &str) -> bool {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = scalar.strip_prefix('-').unwrap_or(scalar);
+    let int_len = digits(rest);
+    if int_len == 0 || (int_len > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[int_len..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let frac_len = digits(frac);
+        if frac_len == 0 {
+            return false;
+        }
+        rest = &frac[frac_len..];
+    }
+    if let Some(exp) = rest.strip_prefix(['e', 'E']) {
+        let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+        let exp_len = digits(exp);
+        if exp_len == 0 {
+            return false;
+        }
+        rest = &exp[exp_len..];
+    }
+    rest.is_empty()
+}
+
+/// Return the double quoted form of the scalar, with the characters the
+/// options escape (as well as any control characters) escaped, if it contains
+/// any of them.
+fn escape_scalar(scalar: &str, options: &EmitOptions) -> Option<String> {
+    if !scalar.chars().any(|c| options.escapes(c)) {
+        return None;
+    }
+    let mut quoted = String::with_capacity(scalar.len() + 2);
+    quoted.push('"');
+    for c in scalar.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() || options.escapes(c) => push_escaped(&mut quoted, c),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Some(quoted)
+}
+
+/// Write a YAML escape sequence for the given character, using the shortest
+/// of the `\x`, `\u` and `\U` forms which can represent it. `\x` is kept to
+/// ASCII, since some consumers read it as a raw byte rather than a code point.
+fn push_escaped(buf: &mut String, c: char) {
+    let code = c as u32;
+    let _ = if code <= 0x7f {
+        write!(buf, "\\x{:02x}", code)
+    } else if code <= 0xffff {
+        write!(buf, "\\u{:04x}", code)
+    } else {
+        write!(buf, "\\U{:08x}", code)
+    };
+}
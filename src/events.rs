@@ -0,0 +1,321 @@
+use crate::{schema::expand_tag, NodeScalar, NodeType, Result, Tree, NONE};
+
+/// Receives the structure of a parsed tree one event at a time, from
+/// [`parse_events`] or [`Tree::visit_events`]. Every method does nothing by
+/// default, so a handler only implements the events it cares about.
+///
+/// A map's entries are given as its key's event followed by its value's,
+/// and a key or value which is an alias is given as [`on_alias`] in place of
+/// [`on_scalar`]. Tags are given as written, e.g. `!!str`, and empty tags and
+/// anchors as `None`.
+///
+/// [`on_alias`]: EventHandler::on_alias
+/// [`on_scalar`]: EventHandler::on_scalar
+pub trait EventHandler {
+    /// The start of a document.
+    fn on_doc_start(&mut self) {}
+    /// The end of a document.
+    fn on_doc_end(&mut self) {}
+    /// The start of a map, before its entries.
+    fn on_map_start(&mut self, _tag: Option<&str>, _anchor: Option<&str>) {}
+    /// The end of a map, after its entries.
+    fn on_map_end(&mut self) {}
+    /// The start of a sequence, before its items.
+    fn on_seq_start(&mut self, _tag: Option<&str>, _anchor: Option<&str>) {}
+    /// The end of a sequence, after its items.
+    fn on_seq_end(&mut self) {}
+    /// A key or value scalar. A node with neither a value nor children is
+    /// given as an empty scalar.
+    fn on_scalar(&mut self, _value: &str, _tag: Option<&str>, _anchor: Option<&str>) {}
+    /// A reference to the anchor with the given name, e.g. `*name`.
+    fn on_alias(&mut self, _anchor: &str) {}
+}
+
+/// Parse YAML source, passing each event in it to the handler in document
+/// order. This is the same as [`Tree::parse`] followed by
+/// [`Tree::visit_events`].
+///
+/// This is not a streaming parser: rapidyaml builds trees directly rather
+/// than producing events, so the whole source is parsed into a tree before
+/// the first event is passed on, and memory use grows with the size of the
+/// source.
+///
+/// ```
+/// struct Count(usize);
+///
+/// impl ryml::EventHandler for Count {
+///     fn on_scalar(&mut self, _: &str, _: Option<&str>, _: Option<&str>) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let mut count = Count(0);
+/// ryml::parse_events("{a: 1, b: [2, 3]}", &mut count)?;
+/// assert_eq!(5, count.0);
+/// # Ok::<(), ryml::Error>(())
+/// ```
+pub fn parse_events(src: &str, handler: &mut impl EventHandler) -> Result<()> {
+    Tree::parse(src)?.visit_events(handler)
+}
+
+/// The tag or anchor of a scalar, if it has one.
+fn non_empty(text: &str) -> Option<&str> {
+    (!text.is_empty()).then_some(text)
+}
+
+impl<'a> Tree<'a> {
+    /// Pass each node of the tree to the handler as the events which would
+    /// have produced it, in document order. See [`EventHandler`].
+    ///
+    /// The tree has to be parsed in full first: rapidyaml builds trees
+    /// directly rather than producing events, so there is no way to stream
+    /// events from a source in constant memory.
+    ///
+    /// ```
+    /// struct Count(usize);
+    ///
+    /// impl ryml::EventHandler for Count {
+    ///     fn on_scalar(&mut self, _: &str, _: Option<&str>, _: Option<&str>) {
+    ///         self.0 += 1;
+    ///     }
+    /// }
+    ///
+    /// let mut count = Count(0);
+    /// ryml::Tree::parse("{a: 1, b: [2, 3]}")?.visit_events(&mut count)?;
+    /// assert_eq!(5, count.0);
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn visit_events(&self, handler: &mut impl EventHandler) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let root = self.root_id()?;
+        let root_type = self.node_data(root)?.node_type;
+        if root_type.is_stream() {
+            let mut doc = self.node_data(root)?.first_child;
+            while doc != NONE {
+                handler.on_doc_start();
+                self.visit_node_events(doc, handler)?;
+                handler.on_doc_end();
+                doc = self.node_data(doc)?.next_sibling;
+            }
+        } else if root_type != NodeType::NoType {
+            handler.on_doc_start();
+            self.visit_node_events(root, handler)?;
+            handler.on_doc_end();
+        }
+        Ok(())
+    }
+
+    /// Pass the events for a node's key, if it has one, and its value.
+    fn visit_node_events(&self, node: usize, handler: &mut impl EventHandler) -> Result<()> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.has_key() {
+            visit_scalar(handler, &data.key, node_type.is_key_ref());
+        }
+        let (tag, anchor) = (non_empty(data.value.tag), non_empty(data.value.anchor));
+        if node_type.is_container() {
+            let is_map = node_type.is_map();
+            if is_map {
+                handler.on_map_start(tag, anchor);
+            } else {
+                handler.on_seq_start(tag, anchor);
+            }
+            let mut child = data.first_child;
+            while child != NONE {
+                self.visit_node_events(child, handler)?;
+                child = self.node_data(child)?.next_sibling;
+            }
+            if is_map {
+                handler.on_map_end();
+            } else {
+                handler.on_seq_end();
+            }
+        } else if node_type.has_val() {
+            visit_scalar(handler, &data.value, node_type.is_val_ref());
+        } else {
+            handler.on_scalar("", tag, anchor);
+        }
+        Ok(())
+    }
+
+    /// Emit the tree as a stream of parse events, in the format used by the
+    /// [YAML test suite](https://github.com/yaml/yaml-test-suite), e.g.
+    ///
+    /// ```text
+    /// +STR
+    /// +DOC
+    /// +MAP
+    /// =VAL :a
+    /// =VAL &x <tag:yaml.org,2002:str> :1
+    /// =VAL :b
+    /// =ALI *x
+    /// -MAP
+    /// -DOC
+    /// -STR
+    /// ```
+    ///
+    /// This shows how the YAML was interpreted, which helps when tracking
+    /// down surprises with anchors, aliases and tags. Since the events are
+    /// rebuilt from the tree rather than recorded while parsing, details the
+    /// tree does not keep are approximated: every document of a multi-document
+    /// stream starts with `---` and none end with `...`, and scalars are
+    /// plain unless the tree records a style for them.
+    ///
+    /// ```
+    /// # use ryml::Tree;
+    /// let tree = Tree::parse("- &a !!int 1\n- *a\n")?;
+    /// assert_eq!(
+    ///     "+STR\n+DOC\n+SEQ\n=VAL &a <tag:yaml.org,2002:int> :1\n=ALI *a\n-SEQ\n-DOC\n-STR\n",
+    ///     tree.emit_events()?
+    /// );
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn emit_events(&self) -> Result<String> {
+        let mut out = String::from("+STR\n");
+        if !self.is_empty() {
+            let root = self.root_id()?;
+            let root_type = self.node_data(root)?.node_type;
+            if root_type.is_stream() {
+                let mut doc = self.node_data(root)?.first_child;
+                while doc != NONE {
+                    out.push_str("+DOC ---\n");
+                    self.write_events(doc, &mut out)?;
+                    out.push_str("-DOC\n");
+                    doc = self.node_data(doc)?.next_sibling;
+                }
+            } else if root_type != NodeType::NoType {
+                out.push_str("+DOC\n");
+                self.write_events(root, &mut out)?;
+                out.push_str("-DOC\n");
+            }
+        }
+        out.push_str("-STR\n");
+        Ok(out)
+    }
+
+    /// Write the events for a node's key, if it has one, and its value.
+    fn write_events(&self, node: usize, out: &mut String) -> Result<()> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.has_key() {
+            let style = key_style(node_type);
+            write_scalar(out, &data.key, node_type.is_key_ref(), style);
+        }
+        if node_type.is_container() {
+            let (start, end, flow) = if node_type.is_map() {
+                ("+MAP", "-MAP\n", " {}")
+            } else {
+                ("+SEQ", "-SEQ\n", " []")
+            };
+            out.push_str(start);
+            if (node_type & (NodeType::WipStyleFlowSl | NodeType::WipStyleFlowMl))
+                != NodeType::NoType
+            {
+                out.push_str(flow);
+            }
+            write_props(out, &data.value);
+            out.push('\n');
+            let mut child = data.first_child;
+            while child != NONE {
+                self.write_events(child, out)?;
+                child = self.node_data(child)?.next_sibling;
+            }
+            out.push_str(end);
+        } else if node_type.has_val() {
+            let style = val_style(node_type);
+            write_scalar(out, &data.value, node_type.is_val_ref(), style);
+        } else {
+            // A node with neither a value nor children is an empty scalar.
+            out.push_str("=VAL :\n");
+        }
+        Ok(())
+    }
+}
+
+/// Pass an alias or a scalar to the handler.
+fn visit_scalar(handler: &mut impl EventHandler, scalar: &NodeScalar, is_ref: bool) {
+    if is_ref {
+        handler.on_alias(scalar.anchor);
+    } else {
+        handler.on_scalar(
+            scalar.scalar,
+            non_empty(scalar.tag),
+            non_empty(scalar.anchor),
+        );
+    }
+}
+
+/// The event style indicator for a key scalar.
+fn key_style(node_type: NodeType) -> char {
+    style(
+        node_type,
+        [
+            NodeType::WipKeySquo,
+            NodeType::WipKeyDquo,
+            NodeType::WipKeyLiteral,
+            NodeType::WipKeyFolded,
+        ],
+    )
+}
+
+/// The event style indicator for a value scalar.
+fn val_style(node_type: NodeType) -> char {
+    style(
+        node_type,
+        [
+            NodeType::WipValSquo,
+            NodeType::WipValDquo,
+            NodeType::WipValLiteral,
+            NodeType::WipValFolded,
+        ],
+    )
+}
+
+fn style(node_type: NodeType, flags: [NodeType; 4]) -> char {
+    flags
+        .into_iter()
+        .zip(['\'', '"', '|', '>'])
+        .find(|&(flag, _)| (node_type & flag) != NodeType::NoType)
+        .map_or(':', |(_, indicator)| indicator)
+}
+
+/// Write an `=ALI` event for an alias, otherwise a `=VAL` event.
+fn write_scalar(out: &mut String, scalar: &NodeScalar, is_ref: bool, style: char) {
+    if is_ref {
+        out.push_str("=ALI *");
+        out.push_str(scalar.anchor);
+        out.push('\n');
+        return;
+    }
+    out.push_str("=VAL");
+    write_props(out, scalar);
+    out.push(' ');
+    out.push(style);
+    for c in scalar.scalar.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\0' => out.push_str("\\0"),
+            '\x08' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('\n');
+}
+
+/// Write the anchor and tag of a node, each preceded by a space.
+fn write_props(out: &mut String, scalar: &NodeScalar) {
+    if !scalar.anchor.is_empty() {
+        out.push_str(" &");
+        out.push_str(scalar.anchor);
+    }
+    if !scalar.tag.is_empty() {
+        out.push_str(" <");
+        out.push_str(&expand_tag(scalar.tag));
+        out.push('>');
+    }
+}
@@ -20,10 +20,25 @@ impl Deref for CSubstr {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
+        // Empty scalars may not point anywhere.
+        if self.ptr.is_null() {
+            return "";
+        }
         unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.ptr, self.len)) }
     }
 }
 
+impl CSubstr {
+    /// The raw bytes of the string, which may not be valid UTF-8 if they
+    /// were produced from `\x` escapes in double quoted scalars.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
 impl AsRef<str> for CSubstr {
     fn as_ref(&self) -> &str {
         self.deref()
@@ -70,12 +85,18 @@ impl core::ops::Deref for Substr {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
+        if self.ptr.is_null() {
+            return "";
+        }
         unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.ptr, self.len)) }
     }
 }
 
 impl core::ops::DerefMut for Substr {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.ptr.is_null() {
+            return Default::default();
+        }
         unsafe { core::mem::transmute(*self) }
     }
 }
@@ -143,6 +164,69 @@ impl NodeData<'_> {
     pub fn prev_sibling(&self) -> Option<usize> {
         (self.prev_sibling != super::NONE).then(|| self.prev_sibling)
     }
+
+    /// Summarize the node on one line for logging, e.g.
+    /// `KEYVAL key="name" val="app"`, from the data alone. The type is named
+    /// as rapidyaml names it, and the key and value are cut short after 32
+    /// characters.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("name: app\nitems: [1, 2]\n")?;
+    /// let root = tree.root_ref()?;
+    /// let name = *root.get("name")?.data().unwrap();
+    /// assert_eq!(name.describe(), r#"KEYVAL key="name" val="app""#);
+    /// let items = *root.get("items")?.data().unwrap();
+    /// assert_eq!(items.describe(), r#"KEYSEQ key="items""#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn describe(&self) -> String {
+        let has = |flag: NodeType| (self.node_type & flag) != NodeType::NoType;
+        let keyed = has(NodeType::Key);
+        let kind = if (self.node_type & NodeType::Stream) == NodeType::Stream {
+            "STREAM"
+        } else if has(NodeType::Map) {
+            if keyed {
+                "KEYMAP"
+            } else {
+                "MAP"
+            }
+        } else if has(NodeType::Seq) {
+            if keyed {
+                "KEYSEQ"
+            } else {
+                "SEQ"
+            }
+        } else if has(NodeType::Val) {
+            if keyed {
+                "KEYVAL"
+            } else {
+                "VAL"
+            }
+        } else if has(NodeType::Doc) {
+            "DOC"
+        } else {
+            "NOTYPE"
+        };
+        let mut description = kind.to_owned();
+        if keyed {
+            description.push_str(&format!(" key={:?}", truncate(self.key.scalar)));
+        }
+        if has(NodeType::Val) {
+            description.push_str(&format!(" val={:?}", truncate(self.value.scalar)));
+        }
+        description
+    }
+}
+
+/// Cut a scalar short for [`NodeData::describe`], marking where it was cut.
+fn truncate(scalar: &str) -> String {
+    const MAX_CHARS: usize = 32;
+    match scalar.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}…", &scalar[..end]),
+        None => scalar.to_owned(),
+    }
 }
 
 unsafe impl cxx::ExternType for NodeData<'_> {
@@ -231,6 +315,85 @@ unsafe impl cxx::ExternType for NodeType {
     type Kind = cxx::kind::Trivial;
 }
 
+impl NodeType {
+    /// The flags which belong to a node's key rather than its value: the key
+    /// itself, its alias, anchor and tag, and its scalar style.
+    pub(crate) const KEY_FLAGS: NodeType = NodeType(
+        NodeType::Key.0
+            | NodeType::KeyRef.0
+            | NodeType::KeyAnch.0
+            | NodeType::KeyTag.0
+            | NodeType::WipKeyStyle.0,
+    );
+
+    /// The flags shown by the [`Display`](std::fmt::Display) impl, in the
+    /// order they are shown. `Stream` comes first so that it stands in for
+    /// the `Seq` bit it includes, and the `WipKeyStyle` and `WipValStyle`
+    /// masks are left out in favour of the single styles they are made of.
+    const DISPLAY_FLAGS: [(&'static str, NodeType); 31] = [
+        ("Stream", NodeType::Stream),
+        ("Val", NodeType::Val),
+        ("Key", NodeType::Key),
+        ("Map", NodeType::Map),
+        ("Seq", NodeType::Seq),
+        ("Doc", NodeType::Doc),
+        ("KeyRef", NodeType::KeyRef),
+        ("ValRef", NodeType::ValRef),
+        ("KeyAnch", NodeType::KeyAnch),
+        ("ValAnch", NodeType::ValAnch),
+        ("KeyTag", NodeType::KeyTag),
+        ("ValTag", NodeType::ValTag),
+        ("WipStyleFlowSl", NodeType::WipStyleFlowSl),
+        ("WipStyleFlowMl", NodeType::WipStyleFlowMl),
+        ("WipStyleBlock", NodeType::WipStyleBlock),
+        ("WipKeyLiteral", NodeType::WipKeyLiteral),
+        ("WipValLiteral", NodeType::WipValLiteral),
+        ("WipKeyFolded", NodeType::WipKeyFolded),
+        ("WipValFolded", NodeType::WipValFolded),
+        ("WipKeySquo", NodeType::WipKeySquo),
+        ("WipValSquo", NodeType::WipValSquo),
+        ("WipKeyDquo", NodeType::WipKeyDquo),
+        ("WipValDquo", NodeType::WipValDquo),
+        ("WipKeyPlain", NodeType::WipKeyPlain),
+        ("WipValPlain", NodeType::WipValPlain),
+        ("WipKeyFtNl", NodeType::WipKeyFtNl),
+        ("WipValFtNl", NodeType::WipValFtNl),
+        ("WipKeyFtSq", NodeType::WipKeyFtSq),
+        ("WipValFtSq", NodeType::WipValFtSq),
+        ("WipKeyFtDq", NodeType::WipKeyFtDq),
+        ("WipValFtDq", NodeType::WipValFtDq),
+    ];
+}
+
+/// Lists the set flags by name, joined with `|`, e.g. `Key|Map|ValTag`, or
+/// `NoType` if none are set. Any unknown bits are shown in hex at the end.
+impl std::fmt::Display for NodeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == NodeType::NoType {
+            return f.write_str("NoType");
+        }
+        let mut rest = *self;
+        let mut first = true;
+        for (name, flag) in Self::DISPLAY_FLAGS {
+            if rest.is_set(flag) {
+                if !first {
+                    f.write_str("|")?;
+                }
+                f.write_str(name)?;
+                first = false;
+                rest.clear(flag);
+            }
+        }
+        if rest != NodeType::NoType {
+            if !first {
+                f.write_str("|")?;
+            }
+            write!(f, "{:#x}", rest.0)?;
+        }
+        Ok(())
+    }
+}
+
 /// A view of scalar data for a node, containing the tag, anchor, and scalar
 /// value.
 ///
@@ -266,16 +429,55 @@ unsafe impl cxx::ExternType for RepC {
 pub trait WriteSeek: io::Write + io::Seek {}
 impl<T: io::Write + io::Seek> WriteSeek for T {}
 
+/// Where an [`RWriter`] sends the emitted text.
+enum Sink<'a> {
+    /// A writer whose position is reported as the end of the output.
+    Seekable(&'a mut dyn WriteSeek),
+    /// A writer which cannot seek, so the bytes written are counted instead.
+    Unseekable(&'a mut dyn io::Write),
+}
+
 pub struct RWriter<'a> {
-    pub writer: &'a mut dyn WriteSeek,
+    sink: Sink<'a>,
+    written: usize,
+}
+
+impl<'a> RWriter<'a> {
+    pub fn new(writer: &'a mut dyn WriteSeek) -> Self {
+        Self {
+            sink: Sink::Seekable(writer),
+            written: 0,
+        }
+    }
+
+    pub fn unseekable(writer: &'a mut dyn io::Write) -> Self {
+        Self {
+            sink: Sink::Unseekable(writer),
+            written: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Seekable(writer) => writer.write_all(bytes)?,
+            Sink::Unseekable(writer) => writer.write_all(bytes)?,
+        }
+        self.written += bytes.len();
+        Ok(())
+    }
 }
 
 impl RWriter<'_> {
     #[inline(always)]
     fn _get(&mut self, _error_on_excess: bool) -> io::Result<Substr> {
+        let len = match &mut self.sink {
+            Sink::Seekable(writer) => writer.stream_position()? as usize,
+            Sink::Unseekable(_) => self.written,
+        };
         Ok(Substr {
             ptr: core::ptr::null_mut(),
-            len: self.writer.stream_position()? as usize,
+            len,
         })
     }
 
@@ -284,28 +486,13 @@ impl RWriter<'_> {
         if s.is_empty() {
             return Ok(());
         }
-        self.writer.write_all(s.as_bytes())?;
-        Ok(())
-    }
-
-    #[inline(always)]
-    fn _do_write_slice(&mut self, slice: &[core::ffi::c_char]) -> io::Result<()> {
-        for c in slice {
-            self.writer.write_all(&[*c as u8])?;
-        }
-        Ok(())
-    }
-
-    #[inline(always)]
-    fn _do_write_char(&mut self, c: core::ffi::c_char) -> io::Result<()> {
-        self.writer.write_all(&[c as u8])?;
-        Ok(())
+        self.write_bytes(s.as_bytes())
     }
 
     #[inline(always)]
     fn _do_write_repc(&mut self, recp: RepC) -> io::Result<()> {
         for _ in 0..recp.num_times {
-            self.writer.write_all(&[recp.char as u8])?;
+            self.write_bytes(&[recp.char as u8])?;
         }
         Ok(())
     }
@@ -328,8 +515,6 @@ pub(crate) mod ffi {
         type RWriter<'a>;
         fn _get(self: &mut RWriter, error_on_excess: bool) -> Result<substr>;
         fn _do_write(self: &mut RWriter, s: csubstr) -> Result<()>;
-        fn _do_write_slice(self: &mut RWriter, slice: &[c_char]) -> Result<()>;
-        fn _do_write_char(self: &mut RWriter, c: c_char) -> Result<()>;
         fn _do_write_repc(self: &mut RWriter, recp: RepC) -> Result<()>;
     }
     #[namespace = "c4::yml"]
@@ -432,7 +617,6 @@ pub(crate) mod ffi {
 
         fn has_parent(self: &Tree, node: usize) -> Result<bool>;
         fn has_child(self: &Tree, node: usize, key: csubstr) -> Result<bool>;
-        fn has_children(self: &Tree, node: usize) -> Result<bool>;
         fn has_sibling(self: &Tree, node: usize, key: csubstr) -> Result<bool>;
         // fn has_siblings(self: &Tree, node: usize) -> Result<bool>;
         fn has_other_siblings(self: &Tree, node: usize) -> Result<bool>;
@@ -579,7 +763,8 @@ pub(crate) mod ffi {
             after: usize,
         ) -> Result<usize>;
 
-        fn copy_to_arena(self: Pin<&mut Tree>, s: csubstr) -> Result<substr>;
+        fn arena(self: &Tree) -> substr;
+        fn in_arena(self: &Tree, s: csubstr) -> bool;
 
         fn emit(tree: &Tree, buffer: substr, error_on_excess: bool) -> Result<substr>;
         fn emit_json(tree: &Tree, buffer: substr, error_on_excess: bool) -> Result<substr>;
@@ -590,9 +775,39 @@ pub(crate) mod ffi {
         include!("ryml/include/shim.h");
         fn new_tree() -> UniquePtr<Tree>;
         fn clone_tree(tree: &Tree) -> UniquePtr<Tree>;
-        fn parse(text: &str) -> Result<UniquePtr<Tree>>;
-        unsafe fn parse_in_place(text: *mut c_char, len: usize) -> Result<UniquePtr<Tree>>;
-        #[cfg(not(windows))]
+        fn parse(text: &str, node_capacity: usize) -> Result<UniquePtr<Tree>>;
+        unsafe fn parse_in_place(
+            text: *mut c_char,
+            len: usize,
+            node_capacity: usize,
+        ) -> Result<UniquePtr<Tree>>;
+        fn parse_into(tree: Pin<&mut Tree>, text: &str) -> Result<()>;
+        fn validate(text: &str) -> Result<()>;
+        fn try_copy_to_arena(
+            tree: Pin<&mut Tree>,
+            s: csubstr,
+            limit: usize,
+            copied: &mut substr,
+        ) -> Result<bool>;
+        fn take_error_location(
+            msg: &mut String,
+            offset: &mut usize,
+            line: &mut usize,
+            col: &mut usize,
+        ) -> bool;
+        fn emit_with_options(
+            tree: &Tree,
+            buffer: substr,
+            indent: usize,
+            doc_start: bool,
+            error_on_excess: bool,
+        ) -> Result<substr>;
+        fn emit_node(
+            tree: &Tree,
+            node: usize,
+            buffer: substr,
+            error_on_excess: bool,
+        ) -> Result<substr>;
         fn emit_to_rwriter(tree: &Tree, writer: Box<RWriter>, json: bool) -> Result<usize>;
 
         fn tree_node_type(tree: &Tree, node: usize) -> Result<NodeType>;
@@ -629,6 +844,22 @@ mod tests {
         assert!(NodeType::ValRef.is_ref());
     }
 
+    #[test]
+    fn display_node_type() {
+        assert_eq!("NoType", NodeType::NoType.to_string());
+        assert_eq!(
+            "Key|Map|ValTag",
+            (NodeType::Map | NodeType::Key | NodeType::ValTag).to_string()
+        );
+        assert_eq!("Stream", NodeType::Stream.to_string());
+        assert_eq!("Seq|Doc", (NodeType::Seq | NodeType::Doc).to_string());
+        assert_eq!(
+            "Val|WipValLiteral|WipValFolded|WipValSquo|WipValDquo|WipValPlain",
+            (NodeType::Val | NodeType::WipValStyle).to_string()
+        );
+        assert_eq!("Val|0x3000", NodeType(1 | 1 << 12 | 1 << 13).to_string());
+    }
+
     static SRC: &str = r#"  HELLO: a
 foo: |
            b
@@ -638,7 +869,7 @@ seq: [0 ,  1, 2, 3]"#;
 
     #[test]
     fn check_tree() -> Result<(), cxx::Exception> {
-        let tree = ffi::parse(SRC)?;
+        let tree = ffi::parse(SRC, 16)?;
         assert_eq!(tree.size(), 10);
         assert_eq!(tree.root_id()?, 0);
         assert_eq!(tree.first_child(0)?, 1);
@@ -662,7 +893,7 @@ seq: [0 ,  1, 2, 3]"#;
     #[test]
     fn mut_tree() -> Result<(), cxx::Exception> {
         let mut src = SRC.to_string();
-        let mut tree = unsafe { ffi::parse_in_place(src.as_mut_ptr() as *mut i8, src.len())? };
+        let mut tree = unsafe { ffi::parse_in_place(src.as_mut_ptr() as *mut i8, src.len(), 16)? };
         let bar_val = tree.find_child(0, &("bar".into()))?;
         tree.pin_mut()._set_val(bar_val, "r353".into(), 0)?;
         println!("{}", &src);
@@ -692,14 +923,14 @@ seq: [0 ,  1, 2, 3]"#;
 
     #[test]
     fn test_exceptions() -> Result<(), cxx::Exception> {
-        let tree = ffi::parse(SRC)?;
+        let tree = ffi::parse(SRC, 16)?;
         tree.is_doc(555).expect_err("is_doc should fail");
         Ok(())
     }
 
     #[test]
     fn emit_into_buffer() -> Result<(), cxx::Exception> {
-        let tree = ffi::parse(SRC)?;
+        let tree = ffi::parse(SRC, 16)?;
         let mut buf = vec![0; SRC.len() * 2];
         ffi::emit(
             &tree,
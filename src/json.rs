@@ -0,0 +1,153 @@
+use crate::{Error, Result, Tree, YamlType};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+/// The most nodes [`Tree::to_json_value`] converts, counting each alias as
+/// the nodes it expands to.
+const ALIAS_EXPANSION_LIMIT: usize = 1_000_000;
+
+impl<'a> Tree<'a> {
+    /// Convert the tree to a [`serde_json::Value`]. Maps become objects,
+    /// sequences become arrays, and each scalar becomes the JSON value of
+    /// its [resolved type](#method.resolved_tag): `~` is `null`, `true` a
+    /// boolean, `12` a number, and quoted scalars are always strings. A
+    /// scalar tagged with a type it cannot be read as, such as
+    /// `!!int abc`, is kept as a string. Aliases are replaced by a copy of
+    /// their anchored node, and other tags and anchors are dropped. A stream
+    /// of several documents becomes an array of them, and an empty tree
+    /// `null`. Scalars are written the same way by
+    /// [`emit_json_with`](#method.emit_json_with), which instead fails on
+    /// anchors and aliases, as it writes the tree out as it is.
+    ///
+    /// Map keys are always written as their text, so `1: a` and `true: b`
+    /// become `{"1": "a", "true": "b"}`, and an alias used as a key as
+    /// `*name`. Where a map repeats a key, the last entry wins.
+    ///
+    /// Fails with [`Error::ComplexKey`] if a map has a
+    /// [complex key](#method.is_complex_key), and with
+    /// [`Error::JsonUnsupported`] for `.inf` and `.nan`, which JSON has no
+    /// numbers for. Before converting anything, fails with
+    /// [`Error::RecursiveAlias`] for an alias inside the node it refers to,
+    /// as in `a: &x [1, *x]`, and with [`Error::AliasExpansionLimit`] if
+    /// expanding the aliases would make more than a million values.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("a: [1, '2', ~]\n3: true\n")?;
+    /// assert_eq!(
+    ///     serde_json::json!({"a": [1, "2", null], "3": true}),
+    ///     tree.to_json_value()?
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_json_value(&self) -> Result<Value> {
+        if self.is_empty() {
+            return Ok(Value::Null);
+        }
+        let root = self.root_id()?;
+        let size = self.expanded_size(root, &mut HashMap::new())?;
+        if size > ALIAS_EXPANSION_LIMIT {
+            return Err(Error::AliasExpansionLimit(ALIAS_EXPANSION_LIMIT));
+        }
+        self.node_json_value(root)
+    }
+
+    /// The number of nodes in a subtree once its aliases are expanded,
+    /// remembering the size of each node reached through an alias. A node
+    /// reached through an alias while its size is still being worked out
+    /// contains that alias.
+    fn expanded_size(
+        &self,
+        node: usize,
+        sizes: &mut HashMap<usize, Option<usize>>,
+    ) -> Result<usize> {
+        let data = self.node_data(node)?;
+        if data.node_type.is_val_ref() {
+            let target = self.anchored_before(node, data.value.anchor)?;
+            return match sizes.get(&target) {
+                Some(&Some(size)) => Ok(size),
+                Some(None) => Err(Error::RecursiveAlias(data.value.anchor.to_owned())),
+                None => self.expanded_size(target, sizes),
+            };
+        }
+        sizes.insert(node, None);
+        let mut size = 1usize;
+        let mut child = data.first_child;
+        while child != crate::NONE {
+            size = size.saturating_add(self.expanded_size(child, sizes)?);
+            child = self.node_data(child)?.next_sibling;
+        }
+        sizes.insert(node, Some(size));
+        Ok(size)
+    }
+
+    /// Create a tree from a [`serde_json::Value`], as
+    /// [`ser::to_tree`](crate::ser::to_tree) would. Strings which would
+    /// read back as another type, such as `"true"` or `"12"`, are marked to
+    /// be emitted quoted, so the tree converts back to the same value.
+    pub fn from_json_value(value: &Value) -> Result<Tree<'static>> {
+        crate::ser::to_tree(value)
+    }
+
+    fn node_json_value(&self, node: usize) -> Result<Value> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.is_val_ref() {
+            return self.node_json_value(self.anchored_before(node, data.value.anchor)?);
+        }
+        if node_type.is_map() {
+            let mut map = Map::new();
+            let mut child = data.first_child;
+            while child != crate::NONE {
+                if self.is_complex_key(child)? {
+                    return Err(Error::ComplexKey);
+                }
+                let key = self.node_data(child)?.key;
+                let key = if self.is_key_ref(child)? {
+                    format!("*{}", key.anchor)
+                } else {
+                    key.scalar.to_owned()
+                };
+                map.insert(key, self.node_json_value(child)?);
+                child = self.next_sibling(child).unwrap_or(crate::NONE);
+            }
+            return Ok(Value::Object(map));
+        }
+        if node_type.is_seq() {
+            let mut items = Vec::new();
+            let mut child = data.first_child;
+            while child != crate::NONE {
+                items.push(self.node_json_value(child)?);
+                child = self.next_sibling(child).unwrap_or(crate::NONE);
+            }
+            return Ok(Value::Array(items));
+        }
+        let text = if node_type.has_val() {
+            data.value.scalar
+        } else {
+            ""
+        };
+        let schema = self.tag_schema();
+        let value = match self.resolved_tag(node)? {
+            YamlType::Null => Some(Value::Null),
+            YamlType::Bool => schema.to_bool(text).map(Value::Bool),
+            YamlType::Int => schema.to_int(text).and_then(|int| {
+                i64::try_from(int)
+                    .map(Number::from)
+                    .or_else(|_| u64::try_from(int).map(Number::from))
+                    .ok()
+                    .map(Value::Number)
+            }),
+            YamlType::Float => match self.val_f64(node) {
+                Ok(float) if !float.is_finite() => {
+                    return Err(Error::JsonUnsupported("infinite or NaN floats"))
+                }
+                Ok(float) => Number::from_f64(float).map(Value::Number),
+                Err(_) => None,
+            },
+            YamlType::Str | YamlType::Map | YamlType::Seq => None,
+        };
+        Ok(value.unwrap_or_else(|| Value::String(text.to_owned())))
+    }
+}
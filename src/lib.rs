@@ -81,12 +81,48 @@
 //! For more usage information, see the full documentation.
 #![deny(missing_docs)]
 #![feature(core_ffi_c)]
-use std::{marker::PhantomData, ops::Deref};
+use std::{
+    cell::{Cell, OnceCell},
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::{ControlFlow, Deref},
+    pin::Pin,
+};
 use thiserror::Error;
+mod anchor;
+mod builder;
+mod comment;
+#[cfg(feature = "serde")]
+pub mod de;
+mod diff;
+mod emit;
+mod events;
 mod inner;
+#[cfg(feature = "json")]
+mod json;
+mod merge;
 mod node;
+mod parse;
+mod path;
+mod repair;
+mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod validate;
+pub use builder::{EntryBuilder, MapBuilder, SeqBuilder, TreeBuilder};
+pub use comment::{COMMENT_TAG, TRAILING_COMMENT_TAG};
+pub use diff::{Difference, SeqIndices};
+pub use emit::{EmitOptions, JsonTagEncoding, LineEnding, JSON_TAG_KEY, JSON_VALUE_KEY};
+pub use events::{parse_events, EventHandler};
 pub use inner::{NodeData, NodeScalar, NodeType};
-pub use node::NodeRef;
+pub use merge::SeqMerge;
+pub use node::{ChildCounts, ContainerType, NodeRef, ScalarStyle};
+pub use parse::{Location, ParseOptions, TreeWriter, Warning};
+pub use repair::RepairReport;
+pub use schema::{YamlSchema, YamlType};
+pub use validate::{Schema, SchemaKind, ValidationError};
 
 /// Represents the pseudo-index of a node that does not exist.
 pub const NONE: usize = usize::MAX;
@@ -100,42 +136,249 @@ macro_rules! not_none {
     };
 }
 
+/// Turn the [`NONE`] pseudo-index into `None`.
+#[inline(always)]
+fn some_index(index: usize) -> Option<usize> {
+    (index != NONE).then_some(index)
+}
+
 /// Error type for this crate
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum Error {
     /// Thrown when a node lookup turns up empty.
     #[error("Node does not exist")]
     NodeNotFound,
-    /// A general exception thrown by rapidyaml over FFI.
+    /// Thrown when looking up a child by key on a node which is not a map.
+    #[error("Node is not a map")]
+    NotAMap,
+    /// Thrown when a node needs to be a sequence and is not.
+    #[error("Node is not a sequence")]
+    NotASeq,
+    /// Thrown when looking up a child by position on a scalar node.
+    #[error("Node is not a container")]
+    NotAContainer,
+    /// Thrown when reading a complex (non-scalar) key as plain text.
+    #[error("Node key is a complex key")]
+    ComplexKey,
+    /// Thrown when trying to remove the root node.
+    #[error("The root node cannot be removed")]
+    RootRemoval,
+    /// Thrown when restoring node data which does not fit the node's place in
+    /// the tree.
+    #[error("Node data does not fit the tree structure")]
+    StructureMismatch,
+    /// Thrown when reading a value as an integer type and it is not an
+    /// integer under the tree's [schema](Tree::schema).
+    #[error("Value {value:?} is not a valid {target}")]
+    InvalidInteger {
+        /// The text of the value.
+        value: String,
+        /// The integer type requested.
+        target: &'static str,
+    },
+    /// Thrown when reading a value as an integer type and it is an integer,
+    /// but does not fit the type.
+    #[error("Value {value:?} is out of range for {target}")]
+    ScalarOutOfRange {
+        /// The text of the value.
+        value: String,
+        /// The integer type requested.
+        target: &'static str,
+    },
+    /// Thrown when a value could not be parsed as the requested type.
+    #[error(
+        "Value {value:?} at {path:?} could not be parsed as {target}{}",
+        reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default()
+    )]
+    ScalarParse {
+        /// The text of the value.
+        value: String,
+        /// The type requested.
+        target: &'static str,
+        /// The path to the node from the root, as read by [`Tree::at`].
+        path: String,
+        /// The error returned by the type's [`FromStr`](std::str::FromStr)
+        /// implementation, where it is known.
+        reason: Option<String>,
+    },
+    /// Thrown when reading a sequence as a fixed-size array and it has the
+    /// wrong number of items.
+    #[error("Sequence has {actual} items, expected {expected}")]
+    WrongLength {
+        /// The number of items required.
+        expected: usize,
+        /// The number of items in the sequence.
+        actual: usize,
+    },
+    /// Thrown when reading a sequence as a typed array and one of its items
+    /// cannot be parsed as the item type.
+    #[error("Sequence item {index} ({value:?}) could not be parsed")]
+    InvalidItem {
+        /// The position of the item in the sequence.
+        index: usize,
+        /// The text of the item.
+        value: String,
+    },
+    /// Thrown when emitting JSON from a tree using a YAML feature which JSON
+    /// cannot represent.
+    #[error("JSON does not support {0}")]
+    JsonUnsupported(&'static str),
+    /// Thrown when YAML source is not valid UTF-8.
     #[error(transparent)]
-    Other(#[from] cxx::Exception),
+    Utf8(#[from] std::str::Utf8Error),
+    /// Thrown when parsing with
+    /// [`reject_control_chars`](ParseOptions#structfield.reject_control_chars)
+    /// and a scalar contains a control character.
+    #[error("Control character {character:?} in scalar at offset {offset}")]
+    ControlCharacter {
+        /// The byte offset of the character in the YAML source.
+        offset: usize,
+        /// The offending character.
+        character: char,
+    },
+    /// Thrown when a string had to be copied into the tree's arena and the
+    /// arena could not grow to fit it.
+    #[error("Arena is full: {needed} bytes needed, {available} available")]
+    ArenaFull {
+        /// The number of bytes which needed to be copied.
+        needed: usize,
+        /// The unused capacity of the arena at the time.
+        available: usize,
+    },
+    /// Thrown when YAML source cannot be parsed, with the location in the
+    /// source at which rapidyaml gave up.
+    /// The message counts lines and columns from 1, as editors do.
+    #[error("{msg} at line {}, column {}", location.line + 1, location.col + 1)]
+    Parse {
+        /// rapidyaml's description of the problem.
+        msg: String,
+        /// Where in the source the error was found.
+        location: Location,
+    },
+    /// Thrown when an alias refers to an anchor which is not defined before
+    /// it.
+    #[error("No anchor named {0:?}")]
+    AnchorNotFound(String),
+    /// Thrown when expanding an alias which refers to a node containing the
+    /// alias, which would never finish.
+    #[error("Alias *{0} refers to a node containing it")]
+    RecursiveAlias(String),
+    /// Thrown when expanding aliases would copy more nodes than the given
+    /// limit, as in a "billion laughs" document.
+    #[error("Expanding aliases would copy more than {0} nodes")]
+    AliasExpansionLimit(usize),
+    /// Thrown when adding a comment to a tree which was not parsed with
+    /// [`retain_comments`](ParseOptions#structfield.retain_comments), whose
+    /// default emit would write the comment out as data.
+    #[error("Tree was not parsed with retain_comments")]
+    CommentsNotRetained,
+    /// Thrown when a path given to [`Tree::at`] cannot be parsed.
+    #[error("Invalid path {path:?}: {reason}")]
+    InvalidPath {
+        /// The path as given.
+        path: String,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// Thrown when emitting with an [`EmitOptions`] field which does not
+    /// apply to the output, such as [`indent`](EmitOptions#structfield.indent)
+    /// for [`Tree::emit_json_with`], which writes a single line.
+    #[error("Emit option {0} is not supported for this output")]
+    UnsupportedEmitOption(&'static str),
+    /// A general exception thrown by rapidyaml over FFI.
+    #[error("{message}")]
+    Other {
+        /// The message of the exception.
+        message: String,
+    },
+}
+
+impl From<cxx::Exception> for Error {
+    fn from(e: cxx::Exception) -> Self {
+        Self::Other {
+            message: e.what().to_owned(),
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Convert an exception thrown while parsing, using the location of the
+/// error recorded by the error callback if there is one.
+fn parse_error(e: cxx::Exception) -> Error {
+    let (mut msg, mut offset, mut line, mut col) = (String::new(), 0, 0, 0);
+    if inner::ffi::take_error_location(&mut msg, &mut offset, &mut line, &mut col) {
+        // rapidyaml counts lines and columns from 1.
+        Error::Parse {
+            msg: msg.lines().next().unwrap_or_default().to_owned(),
+            location: Location {
+                offset,
+                line: line.saturating_sub(1),
+                col: col.saturating_sub(1),
+            },
+        }
+    } else {
+        e.into()
+    }
+}
+
 enum TreeData<'a> {
     Owned,
+    OwnedBuffer(#[allow(dead_code)] Vec<u8>),
     Borrowed(PhantomData<&'a mut [u8]>),
 }
 
 /// Represents a parsed YAML tree
+///
+/// # Borrowed strings
+///
+/// A tree parsed in place points into the buffer it was parsed from, but the
+/// methods which give a node a key, value, tag, anchor or ref, such as
+/// [`to_val`](#method.to_val) and [`set_val_tag`](#method.set_val_tag), copy
+/// the string into the tree's arena first, so it need not outlive the tree.
+///
+/// ```rust
+/// # fn main() -> Result<(), ryml::Error> {
+/// let mut tree = ryml::Tree::parse("a: b")?;
+/// {
+///     let tag = String::from("!custom");
+///     tree.set_val_tag(1, &tag)?;
+/// }
+/// assert_eq!("!custom", tree.val_tag(1)?);
+/// # Ok(())
+/// # }
+/// ```
 pub struct Tree<'a> {
     inner: cxx::UniquePtr<inner::ffi::Tree>,
     _data: TreeData<'a>,
+    dirty: Cell<bool>,
+    modified: bool,
+    offsets: OnceCell<Vec<(usize, usize)>>,
+    arena: Cell<(*const u8, u64)>,
+    schema: Option<YamlSchema>,
+    comments: bool,
 }
 
+/// Trees are equal if they have the same content, as compared by
+/// [`Tree::deep_eq`]. Use [`std::ptr::eq`] to check whether two references
+/// are to the same tree.
 impl PartialEq for Tree<'_> {
     fn eq(&self, other: &Self) -> bool {
-        std::ptr::eq(self.inner.deref(), other.inner.deref())
+        self.deep_eq(other)
     }
 }
 
+/// Clones the tree as with [`Tree::try_clone`].
+///
+/// # Panics
+///
+/// Panics if the tree owns the buffer it was parsed from (see
+/// [`Tree::parse_owned_buf`]) and its arena cannot grow to hold the scalars
+/// copied out of that buffer. Use [`Tree::try_clone`] to handle that case.
 impl Clone for Tree<'_> {
     fn clone(&self) -> Self {
-        Self {
-            inner: inner::ffi::clone_tree(self.inner.deref()),
-            _data: TreeData::Borrowed(PhantomData),
-        }
+        self.try_clone()
+            .expect("Failed to copy tree scalars to the arena")
     }
 }
 
@@ -169,46 +412,457 @@ impl core::fmt::Debug for Tree<'_> {
     }
 }
 
+impl std::str::FromStr for Tree<'static> {
+    type Err = Error;
+
+    /// Parse YAML text as with [`Tree::parse`], which copies the source to
+    /// the tree's arena.
+    #[inline(always)]
+    fn from_str(text: &str) -> Result<Self> {
+        Tree::parse(text)
+    }
+}
+
+/// Emits the tree as YAML, or rapidyaml's error message if that fails.
+/// Unlike the emit methods, this leaves the [dirty](Tree::is_dirty) flag as
+/// it is.
+impl std::fmt::Display for Tree<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dirty = self.is_dirty();
+        let result = {
+            let mut writer = FmtWriter {
+                f,
+                pos: 0,
+                pending: Vec::new(),
+                failed: false,
+            };
+            let result = self.emit_to_writer(&mut writer);
+            if writer.failed {
+                return Err(std::fmt::Error);
+            }
+            result.map(|_| Ok(()))
+        };
+        self.dirty.set(dirty);
+        match result {
+            Ok(result) => result,
+            Err(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Adapts a [`Formatter`](std::fmt::Formatter) to the writer interface the
+/// emitter expects, holding back incomplete UTF-8 sequences until the rest
+/// of their bytes arrive.
+struct FmtWriter<'f, 'g> {
+    f: &'f mut std::fmt::Formatter<'g>,
+    pos: u64,
+    pending: Vec<u8>,
+    failed: bool,
+}
+
+impl std::io::Write for FmtWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.pos += buf.len() as u64;
+        let mut start = 0;
+        let result = loop {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(text) => {
+                    start = self.pending.len();
+                    break self.f.write_str(text);
+                }
+                Err(e) => {
+                    let valid = start + e.valid_up_to();
+                    // Safety: the bytes up to `valid` were just checked.
+                    let text =
+                        unsafe { std::str::from_utf8_unchecked(&self.pending[start..valid]) };
+                    if let Err(e) = self.f.write_str(text) {
+                        break Err(e);
+                    }
+                    match e.error_len() {
+                        Some(len) => {
+                            if let Err(e) = self.f.write_str("\u{FFFD}") {
+                                break Err(e);
+                            }
+                            start = valid + len;
+                        }
+                        // The rest is the start of a character still to come.
+                        None => {
+                            start = valid;
+                            break Ok(());
+                        }
+                    }
+                }
+            }
+        };
+        self.pending.drain(..start);
+        if result.is_err() {
+            self.failed = true;
+            return Err(std::io::Error::other("formatter error"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Seek for FmtWriter<'_, '_> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            std::io::SeekFrom::Current(0) => Ok(self.pos),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "cannot seek in a formatter",
+            )),
+        }
+    }
+}
+
 impl Default for Tree<'_> {
     fn default() -> Self {
         Self {
             inner: inner::ffi::new_tree(),
             _data: TreeData::Owned,
+            dirty: Cell::new(false),
+            modified: false,
+            offsets: OnceCell::new(),
+            arena: Cell::new((std::ptr::null(), 0)),
+            schema: None,
+            comments: false,
         }
     }
 }
 
+impl Tree<'static> {
+    /// Leak the tree, returning a reference which lives for the rest of the
+    /// program, like [`Box::leak`]. Every key and value read from it with the
+    /// `Tree` methods is then a `&'static str`, which suits configuration
+    /// loaded once per process.
+    ///
+    /// **Note**: The tree is never freed, so only leak trees which are meant
+    /// to last until the program exits. A tree parsed in place from a
+    /// borrowed buffer has to be made [owned](#method.to_owned_tree) first.
+    pub fn leak(self) -> &'static Tree<'static> {
+        Box::leak(Box::new(self))
+    }
+}
+
 impl<'a> Tree<'a> {
     /// Create a new tree and parse into its root.  
     /// The immutable YAML source is first copied to the tree's arena, and
     /// parsed from there.
     #[inline(always)]
     pub fn parse(text: impl AsRef<str>) -> Result<Tree<'a>> {
-        let tree = inner::ffi::parse(text.as_ref())?;
+        let text = text.as_ref();
+        let tree = inner::ffi::parse(text, estimate_node_capacity(text)).map_err(parse_error)?;
         Ok(Self {
             inner: tree,
             _data: TreeData::Owned,
+            dirty: Cell::new(false),
+            modified: false,
+            offsets: OnceCell::new(),
+            arena: Cell::new((std::ptr::null(), 0)),
+            schema: YamlSchema::from_directive(text),
+            comments: false,
         })
     }
 
+    /// Clear the tree and parse into its root, reusing the memory already
+    /// allocated for its nodes and arena. The source is copied to the arena
+    /// as with [`parse`](#method.parse). Parsing many documents one after
+    /// another into the same tree avoids allocating a new tree for each.
+    ///
+    /// The previous contents of the tree are discarded, so node indices
+    /// obtained before the call no longer refer to the same nodes; any
+    /// [`NodeRef`] has to be fetched again. If parsing fails, the tree is
+    /// left [cleared](#method.clear), with nothing but an empty root.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::default();
+    /// for src in ["a: 1", "b: [2, 3]"] {
+    ///     tree.parse_into(src)?;
+    ///     assert_eq!(1, tree.root_ref()?.num_children()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_into(&mut self, text: impl AsRef<str>) -> Result<()> {
+        let text = text.as_ref();
+        self.clear();
+        self.clear_arena();
+        self.reserve(estimate_node_capacity(text));
+        self.reserve_arena(text.len());
+        // Nothing points into a buffer the tree was parsed from any more.
+        self._data = TreeData::Owned;
+        self.schema = YamlSchema::from_directive(text);
+        self.comments = false;
+        if let Err(e) = inner::ffi::parse_into(self.inner_mut(), text) {
+            self.clear();
+            self.clear_arena();
+            return Err(parse_error(e));
+        }
+        self.mark_clean();
+        self.modified = false;
+        Ok(())
+    }
+
     /// Create a new tree and parse into its root.  
     /// A mutable reference to the YAML source is passed to the tree parser,
     /// and parsed in-situ.
     #[inline(always)]
     pub fn parse_in_place(mut text: impl AsMut<str> + 'a) -> Result<Tree<'a>> {
+        let schema = YamlSchema::from_directive(text.as_mut());
         let tree = unsafe {
-            inner::ffi::parse_in_place(text.as_mut().as_mut_ptr() as *mut i8, text.as_mut().len())
-        }?;
+            let text = text.as_mut();
+            let node_capacity = estimate_node_capacity(text);
+            inner::ffi::parse_in_place(text.as_mut_ptr() as *mut i8, text.len(), node_capacity)
+        }
+        .map_err(parse_error)?;
         Ok(Self {
             inner: tree,
             _data: TreeData::Borrowed(PhantomData),
+            dirty: Cell::new(false),
+            modified: false,
+            offsets: OnceCell::new(),
+            arena: Cell::new((std::ptr::null(), 0)),
+            schema,
+            comments: false,
+        })
+    }
+
+    /// Create a new tree and parse into its root from a borrowed byte
+    /// buffer, such as the contents of a file read with [`std::fs::read`].  
+    /// The buffer is checked to be valid UTF-8 and then parsed in-situ, as
+    /// with [`parse_in_place`](#method.parse_in_place), without first copying
+    /// it into a [`String`]. Invalid UTF-8 fails with [`Error::Utf8`], whose
+    /// [`valid_up_to`](std::str::Utf8Error::valid_up_to) gives the length of
+    /// the valid prefix.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut buf = b"a: [1, 2]".to_vec();
+    /// let tree = ryml::Tree::parse_in_place_bytes(&mut buf)?;
+    /// assert_eq!("2", tree.at("a[1]")?.val()?);
+    ///
+    /// let mut bad = b"a: \xff".to_vec();
+    /// match ryml::Tree::parse_in_place_bytes(&mut bad) {
+    ///     Err(ryml::Error::Utf8(e)) => assert_eq!(3, e.valid_up_to()),
+    ///     _ => panic!("expected a UTF-8 error"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn parse_in_place_bytes(buf: &'a mut [u8]) -> Result<Tree<'a>> {
+        Self::parse_in_place(std::str::from_utf8_mut(buf)?)
+    }
+
+    /// Create a new tree and parse into its root, taking ownership of the
+    /// given buffer.  
+    /// The buffer is checked to be valid UTF-8 and then parsed in-situ, as
+    /// with [`parse_in_place`](#method.parse_in_place), but it is kept alive
+    /// inside the tree, so the tree is not tied to the lifetime of any
+    /// borrowed data.
+    pub fn parse_owned_buf(mut buf: Vec<u8>) -> Result<Tree<'static>> {
+        let text = std::str::from_utf8(&buf)?;
+        let node_capacity = estimate_node_capacity(text);
+        let schema = YamlSchema::from_directive(text);
+        let tree = unsafe {
+            inner::ffi::parse_in_place(buf.as_mut_ptr() as *mut i8, buf.len(), node_capacity)
+        }
+        .map_err(parse_error)?;
+        Ok(Tree {
+            inner: tree,
+            _data: TreeData::OwnedBuffer(buf),
+            dirty: Cell::new(false),
+            modified: false,
+            offsets: OnceCell::new(),
+            arena: Cell::new((std::ptr::null(), 0)),
+            schema,
+            comments: false,
         })
     }
 
+    /// Create a new tree and parse into its root from bytes, such as a
+    /// default configuration embedded with [`include_bytes!`].  
+    /// The bytes are checked to be valid UTF-8 and then copied to the tree's
+    /// arena, as with [`parse`](#method.parse), so the tree does not borrow
+    /// them. Use [`parse_in_place_bytes`](#method.parse_in_place_bytes) to
+    /// parse a mutable buffer without copying; static data cannot be parsed
+    /// in place, since rapidyaml rewrites escaped and folded scalars in the
+    /// source buffer as it parses, and static data is read-only.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// static DEFAULTS: &[u8] = b"port: 8080\nhost: localhost\n";
+    /// let tree = ryml::Tree::parse_bytes(DEFAULTS)?;
+    /// assert_eq!(8080, tree.root_ref()?.get("port")?.val_u64()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Tree<'a>> {
+        Tree::parse(std::str::from_utf8(bytes)?)
+    }
+
+    /// Whether the tree owns all of its data, i.e. it was not parsed in place
+    /// from a borrowed buffer.
+    #[inline(always)]
+    pub fn is_owned(&self) -> bool {
+        !matches!(self._data, TreeData::Borrowed(_))
+    }
+
+    /// Whether the tree may have changed since it was created or last
+    /// emitted (or [marked clean](#method.mark_clean)). This is a coarse flag:
+    /// any call which could modify the tree sets it, even if nothing actually
+    /// changes, such as reserving capacity. Clones keep the flag of the
+    /// original.
+    #[inline(always)]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.get()
+    }
+
+    /// Clear the [dirty](#method.is_dirty) flag, e.g. after the tree has been
+    /// saved some other way than emitting it.
+    #[inline(always)]
+    pub fn mark_clean(&self) {
+        self.dirty.set(false);
+    }
+
+    /// Get mutable access to the underlying tree, marking it
+    /// [dirty](#method.is_dirty).
+    #[inline(always)]
+    fn inner_mut(&mut self) -> Pin<&mut inner::ffi::Tree> {
+        self.dirty.set(true);
+        self.modified = true;
+        self.offsets.take();
+        self.observe_arena();
+        self.inner.pin_mut()
+    }
+
+    /// A counter of how many times the arena has been relocated, which
+    /// invalidates every `&str` previously read from the tree. Callers
+    /// holding on to scalars across mutations can compare the generation
+    /// before and after to detect that their slices are stale.
+    ///
+    /// Relocations are detected by the arena moving to a new address, which
+    /// is checked when this is called and before every mutation. In debug
+    /// builds [`key`](#method.key) and [`val`](#method.val) also check it,
+    /// and assert that a scalar they return from the arena lies entirely
+    /// within its current allocation. The first allocation of an empty arena
+    /// does not count.
+    #[inline(always)]
+    pub fn arena_generation(&self) -> u64 {
+        self.observe_arena();
+        self.arena.get().1
+    }
+
+    fn observe_arena(&self) {
+        let ptr = self.inner.arena().ptr as *const u8;
+        let (last, generation) = self.arena.get();
+        if ptr != last {
+            let moved = !last.is_null();
+            self.arena.set((ptr, generation + moved as u64));
+        }
+    }
+
+    #[inline(always)]
+    fn debug_check_arena(&self, scalar: &str) {
+        if cfg!(debug_assertions) {
+            self.observe_arena();
+            let arena = self.inner.arena();
+            let start = arena.ptr as usize;
+            let ptr = scalar.as_ptr() as usize;
+            debug_assert!(
+                !(start..start + arena.len).contains(&ptr)
+                    || ptr + scalar.len() <= start + arena.len,
+                "scalar read past the end of the arena at generation {}",
+                self.arena.get().1
+            );
+        }
+    }
+
+    /// The [schema](YamlSchema) by which the typed accessors read plain
+    /// scalars.
+    #[inline(always)]
+    pub fn schema(&self) -> YamlSchema {
+        self.schema.unwrap_or_default()
+    }
+
+    /// Set the [schema](YamlSchema) by which the typed accessors read plain
+    /// scalars.
+    #[inline(always)]
+    pub fn set_schema(&mut self, schema: YamlSchema) {
+        self.schema = Some(schema);
+    }
+
+    /// Clone the tree. A tree which owns the buffer it was parsed from (see
+    /// [`parse_owned_buf`](#method.parse_owned_buf)) is cloned as with
+    /// [`to_owned_tree`](#method.to_owned_tree), since the clone cannot
+    /// borrow from that buffer, and this fails with [`Error::ArenaFull`] if
+    /// the clone's arena cannot grow to hold the scalars copied out of it.
+    /// Cloning any other tree cannot fail.
+    pub fn try_clone(&self) -> Result<Self> {
+        match self._data {
+            TreeData::Owned => Ok(Self {
+                inner: inner::ffi::clone_tree(self.inner.deref()),
+                _data: TreeData::Owned,
+                dirty: Cell::new(self.dirty.get()),
+                modified: self.modified,
+                offsets: self.offsets.clone(),
+                arena: Cell::new((std::ptr::null(), 0)),
+                schema: self.schema,
+                comments: self.comments,
+            }),
+            // The clone cannot keep borrowing from a buffer owned by this tree.
+            TreeData::OwnedBuffer(_) => self.to_owned_tree(),
+            TreeData::Borrowed(_) => Ok(Self {
+                inner: inner::ffi::clone_tree(self.inner.deref()),
+                _data: TreeData::Borrowed(PhantomData),
+                dirty: Cell::new(self.dirty.get()),
+                modified: self.modified,
+                offsets: self.offsets.clone(),
+                arena: Cell::new((std::ptr::null(), 0)),
+                schema: self.schema,
+                comments: self.comments,
+            }),
+        }
+    }
+
+    /// Clone the tree into one which owns all of its data, and so is not tied
+    /// to the lifetime of any source buffer. For a tree which
+    /// [is owned](#method.is_owned) this is the same as
+    /// [`clone`](#method.clone); for a tree parsed in place, every scalar
+    /// still in the source buffer is also copied into the new tree's arena.
+    pub fn to_owned_tree(&self) -> Result<Tree<'static>> {
+        let mut tree = Tree {
+            inner: inner::ffi::clone_tree(self.inner.deref()),
+            _data: TreeData::Owned,
+            dirty: Cell::new(false),
+            modified: false,
+            offsets: OnceCell::new(),
+            arena: Cell::new((std::ptr::null(), 0)),
+            schema: self.schema,
+            comments: self.comments,
+        };
+        if !matches!(self._data, TreeData::Owned) && !tree.is_empty() {
+            tree.copy_subtree_to_arena(tree.root_id()?)?;
+        }
+        tree.dirty.set(self.dirty.get());
+        tree.modified |= self.modified;
+        Ok(tree)
+    }
+
     /// Emit tree as YAML to an owned string.
+    ///
+    /// This and the other emit methods clear the [dirty](#method.is_dirty)
+    /// flag.
     #[inline(always)]
     pub fn emit(&self) -> Result<String> {
-        let mut buf = vec![0; self.inner.capacity() * 32 + self.inner.arena_capacity()];
+        let mut buf = vec![0; self.estimate_emit_len()];
         let written = inner::ffi::emit(
             self.inner.as_ref().unwrap(),
             inner::Substr {
@@ -217,13 +871,20 @@ impl<'a> Tree<'a> {
             },
             true,
         )?;
+        self.mark_clean();
         Ok(written.to_string())
     }
 
     /// Emit tree as JSON to an owned string.
+    ///
+    /// Fails with [`Error::JsonUnsupported`] if the tree uses anchors,
+    /// aliases, or tags on keys or scalars; see
+    /// [`emit_json_with`](#method.emit_json_with) for other ways to handle
+    /// tags.
     #[inline(always)]
     pub fn emit_json(&self) -> Result<String> {
-        let mut buf = vec![0; self.inner.capacity() * 32 + self.inner.arena_capacity()];
+        self.check_json_support()?;
+        let mut buf = vec![0; self.estimate_emit_len()];
         let written = inner::ffi::emit_json(
             self.inner.as_ref().unwrap(),
             inner::Substr {
@@ -232,6 +893,7 @@ impl<'a> Tree<'a> {
             },
             true,
         )?;
+        self.mark_clean();
         Ok(written.to_string())
     }
 
@@ -247,13 +909,86 @@ impl<'a> Tree<'a> {
             },
             true,
         )?;
+        self.mark_clean();
         Ok(written.len)
     }
 
+    /// Emit tree as YAML to a new byte vector, which is sized to fit rather
+    /// than allocated up front for the worst case as in
+    /// [`emit`](#method.emit).
+    pub fn emit_to_vec(&self) -> Result<Vec<u8>> {
+        // Scalars usually take up most of the output, and most of them are
+        // in the arena. An empty buffer would never grow by doubling.
+        let mut buf = vec![0; (self.arena_capacity() + self.len() * 8).max(64)];
+        loop {
+            let written = inner::ffi::emit(
+                self.inner.as_ref().unwrap(),
+                inner::Substr {
+                    ptr: buf.as_mut_ptr(),
+                    len: buf.len(),
+                },
+                false,
+            )?;
+            // Nothing to write comes back as a null slice too.
+            if !written.ptr.is_null() || written.len == 0 {
+                buf.truncate(written.len);
+                self.mark_clean();
+                return Ok(buf);
+            }
+            buf.resize(written.len.max(buf.len() * 2), 0);
+        }
+    }
+
+    /// Emit the given node and its descendants as YAML to an owned string.
+    /// A map entry is emitted with its key, as `key: value`, and a sequence
+    /// item as its value alone.
+    ///
+    /// Unlike the other emit methods, this leaves the
+    /// [dirty](#method.is_dirty) flag as it is, since the rest of the tree is
+    /// not written.
+    ///
+    /// Fails with [`Error::NodeNotFound`] if the node is not in the tree,
+    /// which is always the case for an [empty](#method.is_empty) tree.
+    pub fn emit_node(&self, node: usize) -> Result<String> {
+        if self.is_empty() || node >= self.len() {
+            return Err(Error::NodeNotFound);
+        }
+        let mut buf = vec![0; self.estimate_emit_len()];
+        loop {
+            let written = inner::ffi::emit_node(
+                self.inner.as_ref().unwrap(),
+                node,
+                inner::Substr {
+                    ptr: buf.as_mut_ptr(),
+                    len: buf.len(),
+                },
+                false,
+            )?;
+            // Nothing to write comes back as a null slice too.
+            if !written.ptr.is_null() || written.len == 0 {
+                return Ok(written.to_string());
+            }
+            buf.resize(written.len, 0);
+        }
+    }
+
+    /// Emit tree as YAML to the start of the given buffer. Returns the written
+    /// YAML along with the unused remainder of the buffer, so that several
+    /// trees can be emitted one after another into the same buffer.
+    ///
+    /// Fails with [`Error::Utf8`] if the written YAML is not valid UTF-8.
+    #[inline(always)]
+    pub fn emit_to_slice<'b>(&self, buf: &'b mut [u8]) -> Result<(&'b str, &'b mut [u8])> {
+        let written = self.emit_to_buffer(buf)?;
+        let (yaml, rest) = buf.split_at_mut(written);
+        Ok((std::str::from_utf8(yaml)?, rest))
+    }
+
     /// Emit tree as JSON to the given buffer. Returns the number of bytes
-    /// written.
+    /// written. Fails as with [`emit_json`](#method.emit_json).
     #[inline(always)]
     pub fn emit_json_to_buffer(&self, buf: &mut [u8]) -> Result<usize> {
+        self.check_json_support()?;
         let written = inner::ffi::emit_json(
             self.inner.as_ref().unwrap(),
             inner::Substr {
@@ -262,10 +997,10 @@ impl<'a> Tree<'a> {
             },
             true,
         )?;
+        self.mark_clean();
         Ok(written.len)
     }
 
-    #[cfg(not(windows))]
     /// Emit tree as YAML to the given writer. Returns the number of bytes
     /// written.
     #[inline(always)]
@@ -274,20 +1009,58 @@ impl<'a> Tree<'a> {
         writer: &mut W,
     ) -> Result<usize> {
         let written =
-            inner::ffi::emit_to_rwriter(&self.inner, Box::new(inner::RWriter { writer }), false)?;
+            inner::ffi::emit_to_rwriter(&self.inner, Box::new(inner::RWriter::new(writer)), false)?;
+        self.mark_clean();
+        Ok(written)
+    }
+
+    /// Emit tree as YAML to the given writer, which unlike with
+    /// [`emit_to_writer`](#method.emit_to_writer) need not implement
+    /// [`Seek`](std::io::Seek), e.g. a socket or a pipe. Returns the number
+    /// of bytes written, which are counted as they are written rather than
+    /// read from the writer's position.
+    #[inline(always)]
+    pub fn emit_to_writer_unseekable<W: std::io::Write>(&self, writer: &mut W) -> Result<usize> {
+        let written = inner::ffi::emit_to_rwriter(
+            &self.inner,
+            Box::new(inner::RWriter::unseekable(writer)),
+            false,
+        )?;
+        self.mark_clean();
         Ok(written)
     }
 
-    #[cfg(not(windows))]
     /// Emit tree as JSON to the given writer. Returns the number of bytes
-    /// written.
+    /// written. Fails as with [`emit_json`](#method.emit_json).
     #[inline(always)]
     pub fn emit_json_to_writer<W: std::io::Write + std::io::Seek>(
         &self,
         writer: &mut W,
     ) -> Result<usize> {
+        self.check_json_support()?;
         let written =
-            inner::ffi::emit_to_rwriter(&self.inner, Box::new(inner::RWriter { writer }), false)?;
+            inner::ffi::emit_to_rwriter(&self.inner, Box::new(inner::RWriter::new(writer)), true)?;
+        self.mark_clean();
+        Ok(written)
+    }
+
+    /// Emit tree as JSON to the given writer, which need not implement
+    /// [`Seek`](std::io::Seek), as with
+    /// [`emit_to_writer_unseekable`](#method.emit_to_writer_unseekable).
+    /// Returns the number of bytes written. Fails as with
+    /// [`emit_json`](#method.emit_json).
+    #[inline(always)]
+    pub fn emit_json_to_writer_unseekable<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize> {
+        self.check_json_support()?;
+        let written = inner::ffi::emit_to_rwriter(
+            &self.inner,
+            Box::new(inner::RWriter::unseekable(writer)),
+            true,
+        )?;
+        self.mark_clean();
         Ok(written)
     }
 
@@ -297,6 +1070,116 @@ impl<'a> Tree<'a> {
         Ok(self.inner.root_id()?)
     }
 
+    /// Iterate over the documents in the tree. If the root is a stream, as
+    /// when the source has `---` document markers, these are its children;
+    /// otherwise the root itself is the only document. A tree parsed from
+    /// empty source has no documents.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("---\nname: a\n---\nname: b\n")?;
+    /// let names = tree
+    ///     .docs()?
+    ///     .map(|doc| Ok(doc.get("name")?.val()?.to_owned()))
+    ///     .collect::<Result<Vec<_>, ryml::Error>>()?;
+    /// assert_eq!(names, ["a", "b"]);
+    /// assert_eq!(ryml::Tree::parse("name: c\n")?.docs()?.count(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn docs<'t>(&'t self) -> Result<impl Iterator<Item = NodeRef<'a, 't, 't, &'t Self>>> {
+        let (single, first) = match self.doc_root()? {
+            Some(root) if self.is_stream(root)? => (None, self.node_data(root)?.first_child),
+            Some(root) => (Some(NodeRef::new_exists(self, root)), NONE),
+            None => (None, NONE),
+        };
+        let root = self.root_id().unwrap_or(NONE);
+        Ok(single
+            .into_iter()
+            .chain(node::NodeIterator::new(self, root, first)))
+    }
+
+    /// Get the number of documents in the tree, as iterated by
+    /// [`docs`](#method.docs).
+    pub fn num_docs(&self) -> Result<usize> {
+        match self.doc_root()? {
+            Some(root) if self.is_stream(root)? => self.num_children(root),
+            Some(_) => Ok(1),
+            None => Ok(0),
+        }
+    }
+
+    /// Consume the tree, flattening it into a `(path, value)` pair for every
+    /// scalar, in document order. Paths are made of the keys of map entries,
+    /// joined by `.`, and the positions of sequence items in brackets, e.g.
+    /// `servers[0].port`. The root scalar of a single-scalar document has an
+    /// empty path, and empty maps and sequences are skipped. Aliases are
+    /// given as written (e.g. `*base`) unless the tree is
+    /// [resolved](#method.resolve) first.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("name: app\nservers: [{port: 80}, {port: 443}]\n")?;
+    /// let leaves: Vec<_> = tree.into_leaves()?.collect();
+    /// assert_eq!(
+    ///     leaves,
+    ///     [
+    ///         ("name".to_owned(), "app".to_owned()),
+    ///         ("servers[0].port".to_owned(), "80".to_owned()),
+    ///         ("servers[1].port".to_owned(), "443".to_owned()),
+    ///     ]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_leaves(self) -> Result<impl Iterator<Item = (String, String)>> {
+        let mut leaves = Vec::new();
+        if let Some(root) = self.doc_root()? {
+            self.collect_leaves(root, &mut String::new(), &mut leaves)?;
+        }
+        Ok(leaves.into_iter())
+    }
+
+    fn collect_leaves(
+        &self,
+        node: usize,
+        path: &mut String,
+        leaves: &mut Vec<(String, String)>,
+    ) -> Result<()> {
+        let data = self.node_data(node)?;
+        if !data.node_type.is_container() {
+            if data.node_type.has_val() {
+                leaves.push((path.clone(), data.value.scalar.to_owned()));
+            }
+            return Ok(());
+        }
+        let len = path.len();
+        let mut pos = 0;
+        let mut child = data.first_child;
+        while child != NONE {
+            let child_data = self.node_data(child)?;
+            if child_data.node_type.has_key() {
+                path::push_key(path, child_data.key.scalar);
+            } else {
+                path::push_index(path, pos);
+            }
+            self.collect_leaves(child, path, leaves)?;
+            path.truncate(len);
+            pos += 1;
+            child = child_data.next_sibling;
+        }
+        Ok(())
+    }
+
+    /// Get the root node, unless the tree has no documents.
+    fn doc_root(&self) -> Result<Option<usize>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let root = self.root_id()?;
+        Ok((self.node_type(root)? != NodeType::NoType).then_some(root))
+    }
+
     /// Get a [`NodeRef`] to the root node.
     #[inline(always)]
     pub fn root_ref<'t>(&'t self) -> Result<NodeRef<'a, 't, '_, &'t Self>> {
@@ -329,6 +1212,40 @@ impl<'a> Tree<'a> {
         }
     }
 
+    /// Get a [`NodeRef`] to the node with the given raw index, as returned by
+    /// [`NodeRef::index`]. Unlike [`get`](#method.get), this also checks that
+    /// the index refers to a node which is currently part of the tree.
+    ///
+    /// **Note**: Indices are only stable while the tree's structure is. A
+    /// [`reorder`](#method.reorder) renumbers every node, and a removed
+    /// node's index may be reused for the next one inserted, so a stored
+    /// index can silently refer to a different node after either.
+    #[inline(always)]
+    pub fn node_at_index<'t>(&'t self, index: usize) -> Result<NodeRef<'a, 't, 't, &'t Self>> {
+        self.check_live(index)?;
+        Ok(NodeRef::new_exists(self, index))
+    }
+
+    /// Get a mutable [`NodeRef`] to the node with the given raw index. See
+    /// [`node_at_index`](#method.node_at_index) for details.
+    #[inline(always)]
+    pub fn node_at_index_mut<'t>(
+        &'t mut self,
+        index: usize,
+    ) -> Result<NodeRef<'a, 't, 't, &'t mut Self>> {
+        self.check_live(index)?;
+        Ok(NodeRef::new_exists(self, index))
+    }
+
+    /// Check that the given index refers to the root or to a node with a
+    /// parent, as opposed to a free slot.
+    fn check_live(&self, index: usize) -> Result<()> {
+        if self.is_empty() || (index != self.root_id()? && self.node_data(index)?.parent == NONE) {
+            return Err(Error::NodeNotFound);
+        }
+        Ok(())
+    }
+
     /// Get the total number of nodes.
     #[inline(always)]
     pub fn len(&self) -> usize {
@@ -380,7 +1297,7 @@ impl<'a> Tree<'a> {
     /// Reserves capacity to hold at least `capacity` nodes.
     #[inline(always)]
     pub fn reserve(&mut self, node_capacity: usize) {
-        self.inner.pin_mut().reserve(node_capacity);
+        self.inner_mut().reserve(node_capacity);
     }
 
     /// Ensures the tree's internal string arena is at least the given
@@ -390,7 +1307,7 @@ impl<'a> Tree<'a> {
     /// arena, and thus change the contents of individual nodes.
     #[inline(always)]
     pub fn reserve_arena(&mut self, arena_capacity: usize) {
-        self.inner.pin_mut().reserve_arena(arena_capacity);
+        self.inner_mut().reserve_arena(arena_capacity);
     }
 
     /// Clear the tree and zero every node.
@@ -399,13 +1316,13 @@ impl<'a> Tree<'a> {
     /// See also [`clear_arena`](#method.clear_arena).
     #[inline(always)]
     pub fn clear(&mut self) {
-        self.inner.pin_mut().clear();
+        self.inner_mut().clear();
     }
 
     /// Clear the internal string arena.
     #[inline(always)]
     pub fn clear_arena(&mut self) {
-        self.inner.pin_mut().clear_arena();
+        self.inner_mut().clear_arena();
     }
 
     /// Resolve references (aliases <- anchors) in the tree.
@@ -426,9 +1343,12 @@ impl<'a> Tree<'a> {
     /// potentially expensive operation, with a best-case linear complexity
     /// (from the initial traversal). This potential cost is the reason for
     /// requiring an explicit call.
-    #[inline(always)]
+    ///
+    /// Merge keys (`<<`) are applied first, as with
+    /// [`resolve_merge_keys`](#method.resolve_merge_keys).
     pub fn resolve(&mut self) -> Result<()> {
-        Ok(self.inner.pin_mut().resolve()?)
+        self.resolve_merge_keys()?;
+        Ok(self.inner_mut().resolve()?)
     }
 
     /// Get the type of the given node, if it exists.
@@ -445,9 +1365,54 @@ impl<'a> Tree<'a> {
     }
 
     /// Get the text of the given node, if it exists and is a key.
+    ///
+    /// Fails with [`Error::ComplexKey`] if the key is a
+    /// [complex key](#method.is_complex_key); use
+    /// [`key_tree`](#method.key_tree) to read those.
     #[inline(always)]
     pub fn key(&self, node: usize) -> Result<&str> {
-        Ok(self.inner.key(node).map(|s| s.as_ref())?)
+        if self.is_complex_key(node)? {
+            return Err(Error::ComplexKey);
+        }
+        let key = self.inner.key(node)?.as_ref();
+        self.debug_check_arena(key);
+        Ok(key)
+    }
+
+    /// Check if the given node has a complex key, i.e. a sequence or map
+    /// given in flow style with the explicit `? key` form:
+    /// ```yaml
+    /// ? [a, b]
+    /// : value
+    /// ```
+    ///
+    /// **Note**: rapidyaml only keeps the source text of complex keys, so
+    /// they are detected as unquoted keys starting with `[` or `{`, which
+    /// a plain scalar cannot. Only flow sequences are reliably parsed as
+    /// complex keys, and they are emitted back as quoted strings, so the `?`
+    /// form does not survive a round trip.
+    pub fn is_complex_key(&self, node: usize) -> Result<bool> {
+        let data = self.node_data(node)?;
+        Ok(data.node_type.has_key()
+            && !data.node_type.is_key_quoted()
+            && !data.node_type.is_key_ref()
+            && data.key.scalar.starts_with(['[', '{']))
+    }
+
+    /// Get the key of the given node as a standalone tree. For a
+    /// [complex key](#method.is_complex_key), the root of the new tree is the
+    /// container making up the key; for a scalar key, it is a plain value.
+    pub fn key_tree(&self, node: usize) -> Result<Tree<'static>> {
+        let key = self.inner.key(node)?.as_ref();
+        if self.is_complex_key(node)? {
+            Tree::parse(key)
+        } else {
+            let mut tree = Tree::default();
+            tree.reserve(1);
+            tree.to_val(0, "")?;
+            tree.set_val_copied(0, key)?;
+            Ok(tree)
+        }
     }
 
     /// Get the text of the tag on the key of the given node, if it exists and
@@ -478,7 +1443,188 @@ impl<'a> Tree<'a> {
     /// Get the text of the given node, if it exists and is a value.
     #[inline(always)]
     pub fn val(&self, node: usize) -> Result<&str> {
-        Ok(self.inner.val(node).map(|s| s.as_ref())?)
+        let val = self.inner.val(node)?.as_ref();
+        self.debug_check_arena(val);
+        Ok(val)
+    }
+
+    /// Get the length in bytes of the key of the given node, if it exists.
+    /// This is read straight from the node data, and is 0 for a node without
+    /// a key.
+    #[inline(always)]
+    pub fn key_len(&self, node: usize) -> Result<usize> {
+        let data = self.node_data(node)?;
+        Ok(if data.node_type.has_key() {
+            data.key.scalar.len()
+        } else {
+            0
+        })
+    }
+
+    /// Get the length in bytes of the value of the given node, if it exists,
+    /// as with [`key_len`](#method.key_len). This is 0 for a container.
+    #[inline(always)]
+    pub fn val_len(&self, node: usize) -> Result<usize> {
+        let data = self.node_data(node)?;
+        Ok(if data.node_type.has_val() {
+            data.value.scalar.len()
+        } else {
+            0
+        })
+    }
+
+    /// Parse the value of the given node, if it exists and is a value, as
+    /// any type implementing [`FromStr`](std::str::FromStr). Fails with
+    /// [`Error::ScalarParse`] if the value does not parse.
+    pub fn val_as<T: std::str::FromStr>(&self, node: usize) -> Result<T> {
+        let value = self.val(node)?;
+        value
+            .parse()
+            .map_err(|_| self.scalar_parse_error(node, std::any::type_name::<T>(), None))
+    }
+
+    /// Parse the value of the given node, if it exists and is a value, as
+    /// a type implementing [`FromStr`](std::str::FromStr), typically an
+    /// enum. Unlike [`val_as`](#method.val_as), the
+    /// [`Error::ScalarParse`] it fails with keeps the parse error.
+    pub fn val_as_enum<T>(&self, node: usize) -> Result<T>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.val(node)?.parse().map_err(|e: T::Err| {
+            self.scalar_parse_error(node, std::any::type_name::<T>(), Some(e.to_string()))
+        })
+    }
+
+    /// Build the [`Error::ScalarParse`] for the value of the given node.
+    fn scalar_parse_error(
+        &self,
+        node: usize,
+        target: &'static str,
+        reason: Option<String>,
+    ) -> Error {
+        let value = match self.val(node) {
+            Ok(value) => value.to_owned(),
+            Err(e) => return e,
+        };
+        match self.node_path(node) {
+            Ok(path) => Error::ScalarParse {
+                value,
+                target,
+                path,
+                reason,
+            },
+            Err(e) => e,
+        }
+    }
+
+    /// Get the path to the node from the root, in the form read by
+    /// [`at`](#method.at).
+    fn node_path(&self, node: usize) -> Result<String> {
+        let mut ancestors = Vec::new();
+        let mut index = node;
+        loop {
+            let data = self.node_data(index)?;
+            if data.parent == NONE {
+                break;
+            }
+            ancestors.push(index);
+            index = data.parent;
+        }
+        let mut path = String::new();
+        for &index in ancestors.iter().rev() {
+            let data = self.node_data(index)?;
+            if data.node_type.has_key() {
+                path::push_key(&mut path, data.key.scalar);
+            } else {
+                let mut pos = 0;
+                let mut sibling = data.prev_sibling;
+                while sibling != NONE {
+                    pos += 1;
+                    sibling = self.node_data(sibling)?.prev_sibling;
+                }
+                path::push_index(&mut path, pos);
+            }
+        }
+        Ok(path)
+    }
+
+    /// Parse the value of the given node as an `i64`, following the tree's
+    /// [schema](#method.schema) (e.g. `010` is 8 in YAML 1.1 but 10 in
+    /// YAML 1.2). Fails with [`Error::InvalidInteger`] if the value is not
+    /// an integer, or [`Error::ScalarOutOfRange`] if it does not fit.
+    #[inline(always)]
+    pub fn val_i64(&self, node: usize) -> Result<i64> {
+        self.val_int(node, "i64")
+    }
+
+    /// Parse the value of the given node as a `u64`, as with
+    /// [`val_i64`](#method.val_i64).
+    #[inline(always)]
+    pub fn val_u64(&self, node: usize) -> Result<u64> {
+        self.val_int(node, "u64")
+    }
+
+    /// Parse the value of the given node as any integer type, following the
+    /// tree's schema.
+    fn val_int<T>(&self, node: usize, target: &'static str) -> Result<T>
+    where
+        T: TryFrom<i128> + TryFrom<u128>,
+    {
+        let value = self.val(node)?;
+        let out_of_range = || Error::ScalarOutOfRange {
+            value: value.to_owned(),
+            target,
+        };
+        match self.schema().int_parts(value) {
+            Some((true, Some(magnitude))) => 0i128
+                .checked_sub_unsigned(magnitude)
+                .and_then(|int| T::try_from(int).ok())
+                .ok_or_else(out_of_range),
+            Some((false, Some(magnitude))) => T::try_from(magnitude).map_err(|_| out_of_range()),
+            Some((_, None)) => Err(out_of_range()),
+            None => Err(Error::InvalidInteger {
+                value: value.to_owned(),
+                target,
+            }),
+        }
+    }
+
+    /// Parse the value of the given node as an `f64`, also accepting the YAML
+    /// spellings `.inf`, `-.inf` and `.nan` (in lower, title or upper case)
+    /// unless the tree uses the [JSON schema](YamlSchema::Json).
+    pub fn val_f64(&self, node: usize) -> Result<f64> {
+        let value = self.val(node)?;
+        let (negative, unsigned) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        match unsigned {
+            _ if !self.schema().has_special_floats() => self.val_as(node),
+            ".inf" | ".Inf" | ".INF" if negative => Ok(f64::NEG_INFINITY),
+            ".inf" | ".Inf" | ".INF" => Ok(f64::INFINITY),
+            ".nan" | ".NaN" | ".NAN" if value == unsigned => Ok(f64::NAN),
+            _ => self.val_as(node),
+        }
+    }
+
+    /// Parse the value of the given node as a boolean, following the tree's
+    /// [schema](#method.schema): YAML 1.1 also accepts `yes`, `no`, `on`,
+    /// `off`, `y` and `n`, which are strings in YAML 1.2. Fails with
+    /// [`Error::ScalarParse`] for anything else.
+    pub fn val_bool(&self, node: usize) -> Result<bool> {
+        self.schema()
+            .to_bool(self.val(node)?)
+            .ok_or_else(|| self.scalar_parse_error(node, "bool", None))
+    }
+
+    /// Get the raw bytes of the value of the given node, if it exists and is
+    /// a value. Unlike [`val`](#method.val), this makes no assumption that the
+    /// value is valid UTF-8, which it may not be if it was decoded from `\x`
+    /// escapes in a double quoted scalar.
+    pub fn val_bytes(&self, node: usize) -> Result<&[u8]> {
+        Ok(self.inner.val(node)?.as_bytes())
     }
 
     /// Get the text of the tag on the value of the given node, if it exists and
@@ -635,6 +1781,36 @@ impl<'a> Tree<'a> {
         Ok(self.inner.is_quoted(node)?)
     }
 
+    /// Get the style the value of the given node is written in when emitted,
+    /// if the node exists. Parsed scalars are always
+    /// [`Auto`](ScalarStyle::Auto), as the parser does not record styles.
+    #[inline(always)]
+    pub fn val_style(&self, node: usize) -> Result<ScalarStyle> {
+        Ok(ScalarStyle::of_val(self.node_data(node)?.node_type))
+    }
+
+    /// Set the style the value of the given node is written in when emitted,
+    /// replacing any style it had before. The emitter uses the style as
+    /// given, so e.g. a plain value which needs quoting will not parse back
+    /// the same.
+    ///
+    /// ```
+    /// # use ryml::{ScalarStyle, Tree};
+    /// let mut tree = Tree::parse("script: \"echo a\\necho b\\n\"")?;
+    /// let node = tree.find_child(0, "script")?;
+    /// tree.set_val_style(node, ScalarStyle::Literal)?;
+    /// assert_eq!(ScalarStyle::Literal, tree.val_style(node)?);
+    /// assert!(tree.emit()?.starts_with("script: |\n  echo a\n  echo b\n"));
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn set_val_style(&mut self, node: usize, style: ScalarStyle) -> Result<()> {
+        let node_type = self.node_data(node)?.node_type;
+        self.set_flags(
+            node,
+            (node_type & !NodeType::WipValStyle) | style.val_flag(),
+        )
+    }
+
     /// If the given node exists, returns true if it is a anchor.
     #[inline(always)]
     pub fn is_anchor(&self, node: usize) -> Result<bool> {
@@ -680,9 +1856,13 @@ impl<'a> Tree<'a> {
     }
 
     /// If the given node exists, returns true if it has children.
+    ///
+    /// This only reads the node's cached link to its first child, so it is
+    /// O(1) no matter how many children there are, unlike comparing
+    /// [`num_children`](#method.num_children) to zero.
     #[inline(always)]
     pub fn has_children(&self, node: usize) -> Result<bool> {
-        Ok(self.inner.has_children(node)?)
+        Ok(self.node_data(node)?.first_child != NONE)
     }
 
     /// If the given node exists, returns true if it has a sibling.
@@ -753,18 +1933,77 @@ impl<'a> Tree<'a> {
 
     /// If the given node exists and has a child at the given
     /// position, returns the index to the child node.
+    ///
+    /// Fails with [`Error::NotAContainer`] if the node is a scalar.
     #[inline(always)]
     pub fn child_at(&self, node: usize, pos: usize) -> Result<usize> {
+        let node_type = self.node_type(node)?;
+        if node_type.has_val() && !node_type.is_container() {
+            return Err(Error::NotAContainer);
+        }
         not_none!(self.inner.child(node, pos)?)
     }
 
     /// If the given node exists and has a child at the given
     /// key, returns the index to the child node.
+    ///
+    /// Fails with [`Error::NotAMap`] if the node is a scalar or a sequence.
     #[inline(always)]
     pub fn find_child(&self, node: usize, key: &str) -> Result<usize> {
+        let node_type = self.node_type(node)?;
+        if node_type.has_val() || node_type.is_seq() {
+            return Err(Error::NotAMap);
+        }
         not_none!(self.inner.find_child(node, &(key.into()))?)
     }
 
+    /// Like [`find_child`](#method.find_child), but returns `Ok(None)` if the
+    /// map has no child with the given key, keeping `Err` for real failures,
+    /// such as the node not existing or not being a map.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("a: 1")?;
+    /// assert!(tree.find_child_opt(0, "a")?.is_some());
+    /// assert_eq!(None, tree.find_child_opt(0, "b")?);
+    /// assert!(tree.find_child_opt(1, "b").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn find_child_opt(&self, node: usize, key: &str) -> Result<Option<usize>> {
+        let node_type = self.node_type(node)?;
+        if node_type.has_val() || node_type.is_seq() {
+            return Err(Error::NotAMap);
+        }
+        Ok(some_index(self.inner.find_child(node, &(key.into()))?))
+    }
+
+    /// Like [`child_at`](#method.child_at), but returns `Ok(None)` if the
+    /// container has no child at the given position.
+    #[inline(always)]
+    pub fn child_at_opt(&self, node: usize, pos: usize) -> Result<Option<usize>> {
+        let node_type = self.node_type(node)?;
+        if node_type.has_val() && !node_type.is_container() {
+            return Err(Error::NotAContainer);
+        }
+        Ok(some_index(self.inner.child(node, pos)?))
+    }
+
+    /// Like [`parent`](#method.parent), but returns `Ok(None)` for the root,
+    /// which has no parent.
+    #[inline(always)]
+    pub fn parent_opt(&self, node: usize) -> Result<Option<usize>> {
+        Ok(some_index(self.inner.parent(node)?))
+    }
+
+    /// Like [`next_sibling`](#method.next_sibling), but returns `Ok(None)`
+    /// for the last child of its parent.
+    #[inline(always)]
+    pub fn next_sibling_opt(&self, node: usize) -> Result<Option<usize>> {
+        Ok(some_index(self.inner.next_sibling(node)?))
+    }
+
     /// If the given node exists and has siblings, returns the
     /// number of siblings.
     #[inline(always)]
@@ -816,14 +2055,10 @@ impl<'a> Tree<'a> {
     /// Turn the given node into a key-value pair.
     #[inline(always)]
     pub fn to_keyval(&mut self, node: usize, key: &str, val: &str) -> Result<()> {
-        Ok(self
-            .inner
-            .pin_mut()
-            .to_keyval(node, key.into(), val.into(), 0)?)
+        self.to_keyval_with_flags(node, key, val, NodeType::NoType)
     }
 
     /// Turn the given node into a key-value pair with additional flags.
-    #[inline(always)]
     pub fn to_keyval_with_flags(
         &mut self,
         node: usize,
@@ -831,15 +2066,15 @@ impl<'a> Tree<'a> {
         val: &str,
         more_flags: NodeType,
     ) -> Result<()> {
-        Ok(self
-            .inner
-            .pin_mut()
-            .to_keyval(node, key.into(), val.into(), more_flags.0)?)
+        self.inner_mut()
+            .to_keyval(node, "".into(), "".into(), more_flags.0)?;
+        self.set_key(node, key)?;
+        self.set_val(node, val)
     }
 
     /// Turn the given node with the given key into a map.
     pub fn to_map_by_key(&mut self, node: usize, key: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().to_map_with_key(node, key.into(), 0)?)
+        self.to_map_with_key_and_flags(node, key, NodeType::NoType)
     }
 
     /// Turn the given node with the given key into a map with additional flags.
@@ -849,15 +2084,15 @@ impl<'a> Tree<'a> {
         key: &str,
         more_flags: NodeType,
     ) -> Result<()> {
+        let key = self.arena_copy(key)?;
         Ok(self
-            .inner
-            .pin_mut()
+            .inner_mut()
             .to_map_with_key(node, key.into(), more_flags.0)?)
     }
 
     /// Turn the given node with the given key into a sequence.
     pub fn to_seq_by_key(&mut self, node: usize, key: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().to_seq_with_key(node, key.into(), 0)?)
+        self.to_seq_with_key_and_flags(node, key, NodeType::NoType)
     }
 
     /// Turn the given node with the given key into a sequence with additional
@@ -868,16 +2103,16 @@ impl<'a> Tree<'a> {
         key: &str,
         more_flags: NodeType,
     ) -> Result<()> {
+        let key = self.arena_copy(key)?;
         Ok(self
-            .inner
-            .pin_mut()
+            .inner_mut()
             .to_seq_with_key(node, key.into(), more_flags.0)?)
     }
 
     /// Turn the given node into a value.
     #[inline(always)]
     pub fn to_val(&mut self, node: usize, val: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().to_val(node, val.into(), 0)?)
+        self.to_val_with_flags(node, val, NodeType::NoType)
     }
 
     /// Turn the given node into a value with additional flags.
@@ -888,171 +2123,214 @@ impl<'a> Tree<'a> {
         val: &str,
         more_flags: NodeType,
     ) -> Result<()> {
-        Ok(self
-            .inner
-            .pin_mut()
-            .to_val(node, val.into(), more_flags.0)?)
+        let val = self.arena_copy(val)?;
+        Ok(self.inner_mut().to_val(node, val.into(), more_flags.0)?)
     }
 
     /// Turn the given node into a stream.
     #[inline(always)]
     pub fn to_stream(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().to_stream(node, 0)?)
+        Ok(self.inner_mut().to_stream(node, 0)?)
     }
 
     /// Turn the given node into a stream with additional flags.
     #[inline(always)]
     pub fn to_stream_with_flags(&mut self, node: usize, more_flags: NodeType) -> Result<()> {
-        Ok(self.inner.pin_mut().to_stream(node, more_flags.0)?)
+        Ok(self.inner_mut().to_stream(node, more_flags.0)?)
     }
 
     /// Turn the given node into a map.
     #[inline(always)]
     pub fn to_map(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().to_map(node, 0)?)
+        Ok(self.inner_mut().to_map(node, 0)?)
     }
 
     /// Turn the given node into a map with additional flags.
     #[inline(always)]
     pub fn to_map_with_flags(&mut self, node: usize, more_flags: NodeType) -> Result<()> {
-        Ok(self.inner.pin_mut().to_map(node, more_flags.0)?)
+        Ok(self.inner_mut().to_map(node, more_flags.0)?)
     }
 
     /// Turn the given node into a sequence.
     #[inline(always)]
     pub fn to_seq(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().to_seq(node, 0)?)
+        Ok(self.inner_mut().to_seq(node, 0)?)
     }
 
     /// Turn the given node into a sequence with additional flags.
     #[inline(always)]
     pub fn to_seq_with_flags(&mut self, node: usize, more_flags: NodeType) -> Result<()> {
-        Ok(self.inner.pin_mut().to_seq(node, more_flags.0)?)
+        Ok(self.inner_mut().to_seq(node, more_flags.0)?)
     }
 
     /// Turn the given node into a doc.
     #[inline(always)]
     pub fn to_doc(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().to_doc(node, 0)?)
+        Ok(self.inner_mut().to_doc(node, 0)?)
     }
 
     /// Turn the given node into a doc with additional flags.
     #[inline(always)]
     pub fn to_doc_with_flags(&mut self, node: usize, more_flags: NodeType) -> Result<()> {
-        Ok(self.inner.pin_mut().to_doc(node, more_flags.0)?)
+        Ok(self.inner_mut().to_doc(node, more_flags.0)?)
     }
 
     /// Set the tag on the key of the given node.
     #[inline(always)]
     pub fn set_key_tag(&mut self, node: usize, tag: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_key_tag(node, tag.into())?)
+        let tag = self.arena_copy(tag)?;
+        Ok(self.inner_mut().set_key_tag(node, tag.into())?)
     }
 
     /// Set the anchor on the key of the given node.
     #[inline(always)]
     pub fn set_key_anchor(&mut self, node: usize, anchor: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_key_anchor(node, anchor.into())?)
+        let anchor = self.arena_copy(anchor)?;
+        Ok(self.inner_mut().set_key_anchor(node, anchor.into())?)
     }
 
     /// Set the anchor on the value of the given node.
     #[inline(always)]
     pub fn set_val_anchor(&mut self, node: usize, anchor: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_val_anchor(node, anchor.into())?)
+        let anchor = self.arena_copy(anchor)?;
+        Ok(self.inner_mut().set_val_anchor(node, anchor.into())?)
     }
 
     /// Set the ref on the key of the given node.
     #[inline(always)]
     pub fn set_key_ref(&mut self, node: usize, refr: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_key_ref(node, refr.into())?)
+        let refr = self.arena_copy(refr)?;
+        Ok(self.inner_mut().set_key_ref(node, refr.into())?)
     }
 
     /// Set the ref on the value of the given node.
     #[inline(always)]
     pub fn set_val_ref(&mut self, node: usize, refr: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_val_ref(node, refr.into())?)
+        let refr = self.arena_copy(refr)?;
+        Ok(self.inner_mut().set_val_ref(node, refr.into())?)
     }
 
     /// Set the tag on the value of the given node.
     pub fn set_val_tag(&mut self, node: usize, tag: &str) -> Result<()> {
-        Ok(self.inner.pin_mut().set_val_tag(node, tag.into())?)
+        let tag = self.arena_copy(tag)?;
+        Ok(self.inner_mut().set_val_tag(node, tag.into())?)
+    }
+
+    /// Set the key of the given node to a copy of `key` in the arena, so that
+    /// it need not outlive the tree.
+    #[inline(always)]
+    pub fn set_key_copied(&mut self, node: usize, key: &str) -> Result<()> {
+        self.set_key(node, key)
+    }
+
+    /// Set the value of the given node to a copy of `val` in the arena, so
+    /// that it need not outlive the tree.
+    #[inline(always)]
+    pub fn set_val_copied(&mut self, node: usize, val: &str) -> Result<()> {
+        self.set_val(node, val)
+    }
+
+    /// Copy a string into the tree's arena, growing it if needed, and return
+    /// the copy. Fails with [`Error::ArenaFull`] if the arena cannot grow to
+    /// fit it.
+    ///
+    /// The copy borrows the tree, as a later copy may move the arena. To set
+    /// a key or value from a temporary string, use
+    /// [`set_key_copied`](#method.set_key_copied) or
+    /// [`set_val_copied`](#method.set_val_copied), which copy and set in one
+    /// step.
+    ///
+    /// ```
+    /// # use ryml::Tree;
+    /// let mut tree = Tree::parse("a: 1")?;
+    /// let len = tree.arena_len();
+    /// assert_eq!("copied", tree.copy_to_arena(&String::from("copied"))?);
+    /// assert_eq!(len + 6, tree.arena_len());
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn copy_to_arena(&mut self, text: &str) -> Result<&str> {
+        let copied = self.arena_copy(text)?;
+        // SAFETY: the arena only moves when it grows, which needs the tree
+        // to be borrowed mutably again.
+        Ok(unsafe { &*(copied.deref() as *const str) })
     }
 
     /// Remove the anchor on the key of the given node.
     pub fn rem_key_anchor(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().rem_key_anchor(node)?)
+        Ok(self.inner_mut().rem_key_anchor(node)?)
     }
 
     /// Remove the anchor on the value of the given node.
     pub fn rem_val_anchor(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().rem_val_anchor(node)?)
+        Ok(self.inner_mut().rem_val_anchor(node)?)
     }
 
     /// Remove the reference on the key of the given node.
     pub fn rem_key_ref(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().rem_key_ref(node)?)
+        Ok(self.inner_mut().rem_key_ref(node)?)
     }
 
     /// Remove the reference on the value of the given node.
     pub fn rem_val_ref(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().rem_val_ref(node)?)
+        Ok(self.inner_mut().rem_val_ref(node)?)
     }
 
     /// Remove the reference on the anchor of the given node.
     pub fn rem_anchor_ref(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().rem_anchor_ref(node)?)
+        Ok(self.inner_mut().rem_anchor_ref(node)?)
     }
 
     /// Insert a new node as the child of the given parent at the given
     /// position, returning its index.
     #[inline(always)]
     pub fn insert_child(&mut self, parent: usize, after: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().insert_child(parent, after)?)
+        Ok(self.inner_mut().insert_child(parent, after)?)
     }
 
     /// Insert a new node as the first child of the given parent, returning
     /// its index.
     #[inline(always)]
     pub fn prepend_child(&mut self, parent: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().prepend_child(parent)?)
+        Ok(self.inner_mut().prepend_child(parent)?)
     }
 
     /// Insert a new node as the last child of the given parent, returning
     /// its index.
     #[inline(always)]
     pub fn append_child(&mut self, parent: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().append_child(parent)?)
+        Ok(self.inner_mut().append_child(parent)?)
     }
 
     /// Insert a new node as the sibling of the given node, returning its index.
     #[inline(always)]
     pub fn insert_sibling(&mut self, node: usize, after: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().insert_sibling(node, after)?)
+        Ok(self.inner_mut().insert_sibling(node, after)?)
     }
 
     /// Insert a new node as the first sibling of the given node, returning its
     /// index.
     #[inline(always)]
     pub fn prepend_sibling(&mut self, node: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().prepend_sibling(node)?)
+        Ok(self.inner_mut().prepend_sibling(node)?)
     }
 
     /// Insert a new node as the last sibling of the given node, returning its
     /// index.
     #[inline(always)]
     pub fn append_sibling(&mut self, node: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().append_sibling(node)?)
+        Ok(self.inner_mut().append_sibling(node)?)
     }
 
     /// Remove the given node from its parent, including any children.
     #[inline(always)]
     pub fn remove(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().remove(node)?)
+        Ok(self.inner_mut().remove(node)?)
     }
 
     /// Remove all children from a given node, leaving the node itself.
     #[inline(always)]
     pub fn remove_children(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().remove_children(node)?)
+        Ok(self.inner_mut().remove_children(node)?)
     }
 
     /// Reorder the tree in memory so that all the nodes are stored in a linear
@@ -1061,53 +2339,326 @@ impl<'a> Tree<'a> {
     /// array.
     #[inline(always)]
     pub fn reorder(&mut self) -> Result<()> {
-        Ok(self.inner.pin_mut().reorder()?)
+        Ok(self.inner_mut().reorder()?)
     }
 
     /// Change the type of a node, resetting its contents if necessary and
     /// returning whether the change was possible.
     #[inline(always)]
     pub fn change_type(&mut self, node: usize, new_type: NodeType) -> Result<bool> {
-        Ok(self.inner.pin_mut().change_type(node, new_type.0)?)
-    }
-
-    #[inline(always)]
-    fn set_flags(&mut self, node: usize, new_type: NodeType) -> Result<()> {
-        Ok(self.inner.pin_mut()._set_flags(node, new_type.0)?)
+        Ok(self.inner_mut().change_type(node, new_type.0)?)
     }
 
-    #[inline(always)]
-    fn set_key(&mut self, node: usize, key: &str) -> Result<()> {
-        let new_key = self.inner.pin_mut().copy_to_arena(key.into())?;
-        Ok(self.inner.pin_mut()._set_key(node, new_key.into(), 0)?)
+    /// Restore the type, key and value of the given node from a snapshot of
+    /// its [`NodeData`], e.g. as taken with [`NodeRef::data`] for undo
+    /// support. The snapshot may come from any tree; any of its scalars which
+    /// are not in this tree's arena are copied into it, so the snapshot need
+    /// not outlive the call.
+    ///
+    /// Only `node_type`, `key` and `value` are applied. The parent, child and
+    /// sibling indices describe the structure of the tree rather than the
+    /// node, so they are ignored, but the snapshot must fit the current
+    /// structure: fails with [`Error::StructureMismatch`] if the node has
+    /// children and the snapshot is not of a container, or if the snapshot
+    /// has a key but the node's parent is not a map, or vice versa.
+    pub fn set_node_data(&mut self, node: usize, data: &NodeData<'_>) -> Result<()> {
+        let current = self.node_data(node)?;
+        let in_map = current.parent != NONE && self.node_type(current.parent)?.is_map();
+        if (current.first_child != NONE && !data.node_type.is_container())
+            || data.node_type.has_key() != in_map
+        {
+            return Err(Error::StructureMismatch);
+        }
+        let target = inner::ffi::Tree::get_mut(self.inner_mut(), node)?;
+        // SAFETY: the node exists, and any borrowed scalars are copied into
+        // the arena before the snapshot's borrow ends.
+        unsafe {
+            let target = &mut *target.cast::<NodeData<'_>>();
+            target.node_type = data.node_type;
+            target.key = data.key;
+            target.value = data.value;
+        }
+        self.copy_node_to_arena(node)
+    }
+
+    #[inline(always)]
+    fn set_flags(&mut self, node: usize, new_type: NodeType) -> Result<()> {
+        Ok(self.inner_mut()._set_flags(node, new_type.0)?)
+    }
+
+    #[inline(always)]
+    fn set_key(&mut self, node: usize, key: &str) -> Result<()> {
+        let new_key = self.arena_copy(key)?;
+        Ok(self.inner_mut()._set_key(node, new_key.into(), 0)?)
     }
 
     #[inline(always)]
     fn set_val(&mut self, node: usize, val: &str) -> Result<()> {
-        let new_val = self.inner.pin_mut().copy_to_arena(val.into())?;
-        Ok(self.inner.pin_mut()._set_val(node, new_val.into(), 0)?)
+        let new_val = self.arena_copy(val)?;
+        Ok(self.inner_mut()._set_val(node, new_val.into(), 0)?)
     }
 
     #[inline(always)]
     fn clear_node(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut()._clear(node)?)
+        Ok(self.inner_mut()._clear(node)?)
     }
 
     #[inline(always)]
     fn clear_key(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut()._clear_key(node)?)
+        Ok(self.inner_mut()._clear_key(node)?)
     }
 
     #[inline(always)]
     fn clear_val(&mut self, node: usize) -> Result<()> {
-        Ok(self.inner.pin_mut()._clear_val(node)?)
+        Ok(self.inner_mut()._clear_val(node)?)
+    }
+
+    #[inline(always)]
+    fn node_data(&self, node: usize) -> Result<&NodeData<'_>> {
+        if node >= self.inner.capacity() {
+            return Err(Error::NodeNotFound);
+        }
+        unsafe { self.inner.get(node)?.as_ref() }.ok_or(Error::NodeNotFound)
+    }
+
+    /// Copy every scalar (keys, values, tags and anchors) in the given subtree
+    /// which is not already in the arena into the arena, so that the subtree
+    /// no longer borrows from another tree or buffer.
+    fn copy_subtree_to_arena(&mut self, node: usize) -> Result<()> {
+        let mut nodes = Vec::new();
+        self.walk_preorder(node, &mut |index| {
+            nodes.push(index);
+            Ok(true)
+        })?;
+        for index in nodes {
+            self.copy_node_to_arena(index)?;
+        }
+        Ok(())
+    }
+
+    /// Copy a string into the arena, growing it if needed. Fails with
+    /// [`Error::ArenaFull`] if the arena cannot grow to fit it.
+    fn arena_copy(&mut self, text: &str) -> Result<inner::Substr> {
+        let mut copied = inner::Substr {
+            ptr: std::ptr::null_mut(),
+            len: 0,
+        };
+        #[cfg(not(test))]
+        let limit = usize::MAX;
+        #[cfg(test)]
+        let limit = tests::ALLOCATION_LIMIT.with(Cell::get);
+        if inner::ffi::try_copy_to_arena(self.inner_mut(), text.into(), limit, &mut copied)? {
+            Ok(copied)
+        } else {
+            Err(Error::ArenaFull {
+                needed: text.len(),
+                available: self.inner.arena_slack()?,
+            })
+        }
+    }
+
+    /// Copy the scalars of a single node into the arena, as with
+    /// [`copy_subtree_to_arena`](#method.copy_subtree_to_arena).
+    fn copy_node_to_arena(&mut self, node: usize) -> Result<()> {
+        let data = inner::ffi::Tree::get_mut(self.inner_mut(), node)?.cast::<NodeData<'static>>();
+        for field in 0..6 {
+            // SAFETY: growing the arena relocates the arena contents but
+            // never the node data itself.
+            let scalar = unsafe {
+                let data = &mut *data;
+                let scalar = match field {
+                    0 => &mut data.key.tag,
+                    1 => &mut data.key.scalar,
+                    2 => &mut data.key.anchor,
+                    3 => &mut data.value.tag,
+                    4 => &mut data.value.scalar,
+                    _ => &mut data.value.anchor,
+                };
+                scalar as *mut &str
+            };
+            let text = unsafe { *scalar };
+            if text.is_empty() || self.inner.in_arena(text.into()) {
+                continue;
+            }
+            let copied = self.arena_copy(text)?;
+            unsafe { *scalar = &*(copied.deref() as *const str) };
+        }
+        Ok(())
+    }
+
+    /// Visit every node of the tree depth-first in document order, passing
+    /// the tree, the node and its depth (0 for the root) to the visitor.
+    /// Returning [`ControlFlow::Break`] stops the walk, which then returns
+    /// `Break` too.
+    ///
+    /// ```rust
+    /// # use std::ops::ControlFlow;
+    /// let tree = ryml::Tree::parse("a: {b: [1, 2]}\nc: 3\n")?;
+    /// let mut deepest = 0;
+    /// tree.walk(|_, _, depth| {
+    ///     deepest = deepest.max(depth);
+    ///     ControlFlow::Continue(())
+    /// })?;
+    /// assert_eq!(3, deepest);
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn walk<F>(&self, mut visitor: F) -> Result<ControlFlow<()>>
+    where
+        F: FnMut(&Self, usize, usize) -> ControlFlow<()>,
+    {
+        if self.is_empty() {
+            return Ok(ControlFlow::Continue(()));
+        }
+        self.walk_depth(self.root_id()?, 0, &mut visitor)
+    }
+
+    fn walk_depth(
+        &self,
+        node: usize,
+        depth: usize,
+        visitor: &mut dyn FnMut(&Self, usize, usize) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>> {
+        if visitor(self, node, depth).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+        let mut child = self.inner.first_child(node)?;
+        while child != NONE {
+            if self.walk_depth(child, depth + 1, visitor)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+            child = self.inner.next_sibling(child)?;
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Count the nodes of the tree whose type has every flag set in the
+    /// mask, e.g. `NodeType::Map | NodeType::ValAnch` for anchored maps. The
+    /// empty mask [`NoType`](NodeType::NoType) matches every node.
+    ///
+    /// **Note**: A stream counts as a sequence, since its type includes the
+    /// [`Seq`](NodeType::Seq) flag.
+    pub fn count_by_type(&self, mask: NodeType) -> usize {
+        self.count_nodes(|node_type| (node_type & mask) == mask)
+    }
+
+    /// Count the scalar nodes of the tree, i.e. those which are not maps or
+    /// sequences, including aliases and nodes with an empty value.
+    #[inline(always)]
+    pub fn num_scalars(&self) -> usize {
+        self.count_nodes(|node_type| !node_type.is_container() && node_type != NodeType::NoType)
+    }
+
+    /// Count the maps in the tree.
+    #[inline(always)]
+    pub fn num_maps(&self) -> usize {
+        self.count_nodes(|node_type| node_type.is_map())
+    }
+
+    /// Count the sequences in the tree, not including a stream of documents.
+    #[inline(always)]
+    pub fn num_seqs(&self) -> usize {
+        self.count_nodes(|node_type| node_type.is_seq() && !node_type.is_stream())
+    }
+
+    fn count_nodes(&self, mut pred: impl FnMut(NodeType) -> bool) -> usize {
+        let mut count = 0;
+        let _ = self.walk(|tree, node, _| {
+            if tree.node_type(node).is_ok_and(&mut pred) {
+                count += 1;
+            }
+            ControlFlow::Continue(())
+        });
+        count
+    }
+
+    /// Find the first node of the tree, depth-first in document order, which
+    /// matches the predicate.
+    ///
+    /// ```rust
+    /// let tree = ryml::Tree::parse("a: {b: [1, 2]}\nc: 2\n")?;
+    /// let two = tree.find(|tree, node| tree.val(node).ok() == Some("2")).unwrap();
+    /// assert_eq!(Some("b"), tree.parent(two).and_then(|p| tree.key(p)).ok());
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn find<F>(&self, mut pred: F) -> Option<usize>
+    where
+        F: FnMut(&Self, usize) -> bool,
+    {
+        let mut found = None;
+        self.walk(|tree, node, _| {
+            if pred(tree, node) {
+                found = Some(node);
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .ok()
+        .and(found)
+    }
+
+    /// Iterate over the descendants of the given node (not including the
+    /// node itself) depth-first in document order.
+    ///
+    /// ```rust
+    /// let tree = ryml::Tree::parse("a: {b: [1, 2]}\nc: 3\n")?;
+    /// let a = tree.find_child(tree.root_id()?, "a")?;
+    /// assert_eq!(3, tree.descendants(a)?.count());
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn descendants(&self, node: usize) -> Result<impl Iterator<Item = usize> + '_> {
+        let mut next = self.node_data(node)?.first_child;
+        Ok(std::iter::from_fn(move || {
+            let current = next;
+            if current == NONE {
+                return None;
+            }
+            next = self.next_in_subtree(current, node);
+            Some(current)
+        }))
+    }
+
+    /// The node after the given one in document order, without leaving the
+    /// subtree of `top`.
+    fn next_in_subtree(&self, mut current: usize, top: usize) -> usize {
+        let Ok(data) = self.node_data(current) else {
+            return NONE;
+        };
+        if data.first_child != NONE {
+            return data.first_child;
+        }
+        while current != top {
+            let Ok(data) = self.node_data(current) else {
+                return NONE;
+            };
+            if data.next_sibling != NONE {
+                return data.next_sibling;
+            }
+            current = data.parent;
+        }
+        NONE
+    }
+
+    /// Visit the given node and its descendants in document order, stopping
+    /// early if the visitor returns `false`.
+    fn walk_preorder(
+        &self,
+        node: usize,
+        visitor: &mut dyn FnMut(usize) -> Result<bool>,
+    ) -> Result<bool> {
+        for index in std::iter::once(node).chain(self.descendants(node)?) {
+            if !visitor(index)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
     /// Recursively duplicate the given node, returning the index to the
     /// duplicate.
     #[inline(always)]
     pub fn duplicate(&mut self, node: usize, new_parent: usize, after: usize) -> Result<usize> {
-        Ok(self.inner.pin_mut().duplicate(node, new_parent, after)?)
+        Ok(self.inner_mut().duplicate(node, new_parent, after)?)
     }
 
     /// Recursively duplicate the given node from a different tree, returning
@@ -1115,13 +2666,13 @@ impl<'a> Tree<'a> {
     #[inline(always)]
     pub fn duplicate_from_tree(
         &mut self,
-        tree: &Self,
+        tree: &Tree<'_>,
         node: usize,
         parent: usize,
         after: usize,
     ) -> Result<usize> {
         Ok(unsafe {
-            self.inner.pin_mut().duplicate_from_tree(
+            self.inner_mut().duplicate_from_tree(
                 tree.inner.deref() as *const inner::ffi::Tree,
                 node,
                 parent,
@@ -1139,10 +2690,7 @@ impl<'a> Tree<'a> {
         parent: usize,
         after: usize,
     ) -> Result<usize> {
-        Ok(self
-            .inner
-            .pin_mut()
-            .duplicate_children(node, parent, after)?)
+        Ok(self.inner_mut().duplicate_children(node, parent, after)?)
     }
 
     /// Recursively duplicate the children of the given node (but not the node
@@ -1151,13 +2699,13 @@ impl<'a> Tree<'a> {
     #[inline(always)]
     pub fn duplicate_children_from_tree(
         &mut self,
-        tree: &Self,
+        tree: &Tree<'_>,
         node: usize,
         parent: usize,
         after: usize,
     ) -> Result<usize> {
         Ok(unsafe {
-            self.inner.pin_mut().duplicate_children_from_tree(
+            self.inner_mut().duplicate_children_from_tree(
                 tree.inner.deref() as *const inner::ffi::Tree,
                 node,
                 parent,
@@ -1169,7 +2717,7 @@ impl<'a> Tree<'a> {
     /// Duplicate the contents of a given node to the given index.
     #[inline(always)]
     pub fn duplicate_contents(&mut self, node: usize, dest_index: usize) -> Result<()> {
-        Ok(self.inner.pin_mut().duplicate_contents(node, dest_index)?)
+        Ok(self.inner_mut().duplicate_contents(node, dest_index)?)
     }
 
     /// Duplicate the contents of a given node from another tree to the given
@@ -1177,12 +2725,12 @@ impl<'a> Tree<'a> {
     #[inline(always)]
     pub fn duplicate_contents_from_tree(
         &mut self,
-        tree: &Self,
+        tree: &Tree<'_>,
         node: usize,
         dest_index: usize,
     ) -> Result<()> {
         unsafe {
-            self.inner.pin_mut().duplicate_contents_from_tree(
+            self.inner_mut().duplicate_contents_from_tree(
                 tree.inner.deref() as *const inner::ffi::Tree,
                 node,
                 dest_index,
@@ -1204,15 +2752,216 @@ impl<'a> Tree<'a> {
         after: usize,
     ) -> Result<usize> {
         Ok(self
-            .inner
-            .pin_mut()
+            .inner_mut()
             .duplicate_children_no_rep(node, parent, after)?)
     }
 
+    /// Recursively duplicate the given node from a different tree, like
+    /// [`duplicate_from_tree`](#method.duplicate_from_tree), but first resolve
+    /// any aliases in the subtree which refer to anchors defined *outside* of
+    /// it, returning the index to the duplicate.
+    ///
+    /// The first alias to each external anchor is replaced by a copy of the
+    /// anchored node (keeping the anchor itself), so any later aliases to the
+    /// same anchor still point to a valid target within the copy. Aliases
+    /// within those copies are resolved in turn, and an alias to an anchored
+    /// key, or used as a key, is replaced by the anchored text. Aliases to
+    /// anchors defined inside the subtree are left untouched. The result is a
+    /// self-contained fragment that can be resolved or re-parsed on its own.
+    ///
+    /// Fails with [`Error::AnchorNotFound`] for an alias to an anchor which
+    /// is not defined, with [`Error::RecursiveAlias`] for an alias to a node
+    /// containing it, and with [`Error::ComplexKey`] for an alias to a
+    /// container used as a key.
+    ///
+    /// [`duplicate_from_tree`](#method.duplicate_from_tree) is the cheaper
+    /// alternative if dangling aliases are acceptable (e.g. because the copy
+    /// will be resolved against anchors that also exist in the destination).
+    ///
+    /// **Note**: The source tree is cloned in order to resolve the aliases,
+    /// so this is considerably more expensive than a plain duplicate.
+    pub fn duplicate_subtree_resolved(
+        &mut self,
+        src: &Self,
+        node: usize,
+        parent: usize,
+        after: usize,
+    ) -> Result<usize> {
+        let mut scratch = src.clone();
+        // Anchors defined before the subtree, in document order. A later
+        // definition of the same name shadows an earlier one.
+        let mut outer_anchors: HashMap<&str, (usize, bool)> = HashMap::new();
+        src.walk_preorder(src.root_id()?, &mut |index| {
+            if index == node {
+                return Ok(false);
+            }
+            let data = src.node_data(index)?;
+            if data.node_type.has_key_anchor() {
+                outer_anchors.insert(data.key.anchor, (index, true));
+            }
+            if data.node_type.has_val_anchor() {
+                outer_anchors.insert(data.value.anchor, (index, false));
+            }
+            Ok(true)
+        })?;
+        // Copies of anchored nodes may hold aliases to other outside
+        // anchors, so resolve one alias at a time until none are left.
+        while let Some((alias, is_key)) = scratch.first_outside_alias(node)? {
+            let data = scratch.node_data(alias)?;
+            let name = if is_key {
+                data.key.anchor
+            } else {
+                data.value.anchor
+            }
+            .to_owned();
+            let (target, is_key_anchor) = *outer_anchors
+                .get(name.as_str())
+                .ok_or_else(|| Error::AnchorNotFound(name.clone()))?;
+            if is_key_anchor || is_key {
+                let text = if is_key_anchor {
+                    src.key(target)?
+                } else if src.has_val(target)? {
+                    src.val(target)?
+                } else {
+                    return Err(Error::ComplexKey);
+                };
+                if is_key {
+                    scratch.rem_key_ref(alias)?;
+                    scratch.set_key(alias, text)?;
+                } else {
+                    scratch.rem_val_ref(alias)?;
+                    scratch.set_val(alias, text)?;
+                }
+            } else {
+                let mut ancestor = alias;
+                while ancestor != NONE {
+                    if ancestor == target {
+                        return Err(Error::RecursiveAlias(name));
+                    }
+                    ancestor = scratch.node_data(ancestor)?.parent;
+                }
+                // The copy keeps the anchor, so later aliases to it are now
+                // inside the subtree.
+                scratch.duplicate_contents(target, alias)?;
+            }
+        }
+        let index = self.duplicate_from_tree(&scratch, node, parent, after)?;
+        self.copy_subtree_to_arena(index)?;
+        Ok(index)
+    }
+
+    /// Find the first alias in the given subtree, in document order, to an
+    /// anchor which is not defined before it within the subtree, along with
+    /// whether it is a key.
+    fn first_outside_alias(&self, node: usize) -> Result<Option<(usize, bool)>> {
+        let mut inner_anchors = HashSet::new();
+        let mut found = None;
+        self.walk_preorder(node, &mut |index| {
+            let data = self.node_data(index)?;
+            let node_type = data.node_type;
+            if node_type.has_key_anchor() {
+                inner_anchors.insert(data.key.anchor);
+            }
+            if node_type.is_key_ref() && !inner_anchors.contains(data.key.anchor) {
+                found = Some((index, true));
+                return Ok(false);
+            }
+            if node_type.has_val_anchor() {
+                inner_anchors.insert(data.value.anchor);
+            }
+            if node_type.is_val_ref() && !inner_anchors.contains(data.value.anchor) {
+                found = Some((index, false));
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+        Ok(found)
+    }
+
+    /// Copy the given node and its descendants into a new standalone tree,
+    /// with the node as its root (without its key).
+    ///
+    /// Anchors and aliases within the subtree are kept, and each alias still
+    /// refers to the copy of its anchor. Aliases to anchors defined outside
+    /// of the subtree are replaced by copies of the anchored values, as with
+    /// [`duplicate_subtree_resolved`](#method.duplicate_subtree_resolved).
+    /// Anchors defined more than once in the copy are then renamed as with
+    /// [`dedup_anchors`](#method.dedup_anchors), so each name is unique and
+    /// the copy can be inserted into another tree without its aliases being
+    /// captured by the wrong definition.
+    ///
+    /// Every scalar is copied into the new tree's arena, so it does not
+    /// borrow from this tree or its source. To copy the node exactly as it
+    /// is, key included, use [`extract_subtree`](#method.extract_subtree).
+    pub fn clone_subtree(&self, node: usize) -> Result<Tree<'static>> {
+        let mut tree = Tree::default();
+        tree.reserve(self.len());
+        let root = tree.root_id()?;
+        // The copy borrows the scratch tree's strings until they are copied
+        // into its own arena.
+        let mut scratch = Tree::default();
+        if node == self.root_id()? {
+            // Every anchor is inside the root, so there is nothing to resolve.
+            tree.duplicate_contents_from_tree(self, node, root)?;
+        } else {
+            scratch.reserve(self.len());
+            let scratch_root = scratch.root_id()?;
+            let copy = scratch.duplicate_subtree_resolved(self, node, scratch_root, NONE)?;
+            tree.duplicate_contents_from_tree(&scratch, copy, root)?;
+        }
+        let root_type = tree.node_type(root)?;
+        tree.set_flags(root, root_type & !NodeType::KEY_FLAGS)?;
+        tree.copy_subtree_to_arena(root)?;
+        tree.schema = self.schema;
+        tree.comments = self.comments;
+        tree.dedup_anchors()?;
+        Ok(tree)
+    }
+
+    /// Copy the given node and its descendants, exactly as they are, into a
+    /// new standalone tree. The new root is a map holding the node as its
+    /// only entry if the node has a key, and otherwise a sequence holding it
+    /// as its only item, so `a` in `a: {b: 1}` is extracted as `a: {b: 1}`.
+    /// The root itself, having no key, is copied as the new root.
+    ///
+    /// Unlike [`clone_subtree`](#method.clone_subtree), tags, anchors and
+    /// aliases are copied untouched, so an alias to an anchor defined
+    /// outside of the subtree is left dangling. Every scalar is copied into
+    /// the new tree's arena, so it does not borrow from this tree or its
+    /// source.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("db: {host: &h local, replica: *h}\nport: 80\n")?;
+    /// let db = tree.extract_subtree(tree.at("db")?.index())?;
+    /// assert_eq!("db:\n  host: &h local\n  replica: *h\n", db.emit()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_subtree(&self, node: usize) -> Result<Tree<'static>> {
+        let mut tree = Tree::default();
+        tree.reserve(self.len() + 1);
+        let root = tree.root_id()?;
+        if node == self.root_id()? {
+            tree.duplicate_contents_from_tree(self, node, root)?;
+        } else {
+            if self.has_key(node)? {
+                tree.to_map(root)?;
+            } else {
+                tree.to_seq(root)?;
+            }
+            tree.duplicate_from_tree(self, node, root, NONE)?;
+        }
+        tree.copy_subtree_to_arena(root)?;
+        tree.schema = self.schema;
+        tree.comments = self.comments;
+        Ok(tree)
+    }
+
     /// Change the node's position in the parent.
     #[inline(always)]
     pub fn move_node(&mut self, node: usize, after: usize) -> Result<()> {
-        Ok(inner::ffi::move_node(self.inner.pin_mut(), node, after)?)
+        Ok(inner::ffi::move_node(self.inner_mut(), node, after)?)
     }
 
     /// Change the node's parent and position.
@@ -1224,7 +2973,7 @@ impl<'a> Tree<'a> {
         after: usize,
     ) -> Result<()> {
         Ok(inner::ffi::move_node_to_new_parent(
-            self.inner.pin_mut(),
+            self.inner_mut(),
             node,
             new_parent,
             after,
@@ -1242,8 +2991,8 @@ impl<'a> Tree<'a> {
         after: usize,
     ) -> Result<usize> {
         Ok(inner::ffi::move_node_from_tree(
-            self.inner.pin_mut(),
-            tree.inner.pin_mut(),
+            self.inner_mut(),
+            tree.inner_mut(),
             node,
             new_parent,
             after,
@@ -1251,12 +3000,41 @@ impl<'a> Tree<'a> {
     }
 }
 
+/// Estimate the number of nodes a YAML source will parse into, so the tree can
+/// be sized once up front instead of growing while parsing. Block entries
+/// mostly take a line each, and flow entries are separated by commas.
+fn estimate_node_capacity(text: &str) -> usize {
+    let separators = text
+        .bytes()
+        .filter(|byte| matches!(byte, b'\n' | b','))
+        .count();
+    separators + 16
+}
+
+/// Write a float so that it reads back as the same float: with a decimal
+/// point even if it is whole, and with YAML's spellings of NaN and infinity.
+pub(crate) fn float_text(value: f64) -> String {
+    if value.is_nan() {
+        ".nan".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { ".inf" } else { "-.inf" }.into()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     static SRC: &str = include_str!("../test/AIScheduleAnchor.aiprog.yml");
 
+    thread_local! {
+        /// The most the arena may allocate at once, lowered to make it fail
+        /// to grow.
+        pub(super) static ALLOCATION_LIMIT: Cell<usize> = const { Cell::new(usize::MAX) };
+    }
+
     #[test]
     fn parse() -> Result<()> {
         let tree = Tree::parse(SRC)?;
@@ -1291,6 +3069,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn empty_fast() -> Result<()> {
+        let seq = Tree::parse("[a, b]")?;
+        assert!(seq.has_children(0)?);
+        assert!(!seq.root_ref()?.is_empty_fast()?);
+        assert!(seq.root_ref()?.child_at(1)?.is_empty_fast()?);
+        Ok(())
+    }
+
+    #[test]
+    fn child_lookup_errors() -> Result<()> {
+        let tree = Tree::parse("key: value")?;
+        let value = tree.find_child(tree.root_id()?, "key")?;
+        assert!(matches!(tree.find_child(value, "fish"), Err(Error::NotAMap)));
+        assert!(matches!(tree.child_at(value, 0), Err(Error::NotAContainer)));
+        let seq = Tree::parse("[a, b]")?;
+        assert!(matches!(seq.find_child(0, "a"), Err(Error::NotAMap)));
+        assert!(!seq.root_ref()?.has_child("a")?);
+        Ok(())
+    }
+
     #[test]
     fn construct_tree() -> Result<()> {
         let mut tree = Tree::default();
@@ -1304,6 +3103,2555 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn clone_ownership() -> Result<()> {
+        let tree = Tree::parse("a: b\n")?;
+        assert!(tree.clone().is_owned());
+        let owned: Tree<'static> = {
+            let mut text = String::from("a: [b, c]\n");
+            let borrowed = Tree::parse_in_place(&mut text)?;
+            assert!(!borrowed.clone().is_owned());
+            borrowed.to_owned_tree()?
+        };
+        assert!(owned.is_owned());
+        assert_eq!("a:\n  - b\n  - c\n", owned.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_owned_buffer() -> Result<()> {
+        let tree = Tree::parse_owned_buf(b"a: [b, c]\n".to_vec())?;
+        assert!(tree.is_owned());
+        assert_eq!(0, tree.arena_len());
+        let clone = tree.clone();
+        drop(tree);
+        assert_eq!("a:\n  - b\n  - c\n", clone.emit()?);
+        assert!(matches!(
+            Tree::parse_owned_buf(vec![b'a', b':', 0xff]),
+            Err(Error::Utf8(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_resolved() -> Result<()> {
+        let src = Tree::parse("base: &b {x: 1}\nsub:\n  a: *b\n  c: *b\n  d: &d 2\n  e: *d\n")?;
+        let sub = src.find_child(src.root_id()?, "sub")?;
+        let mut dest = Tree::default();
+        dest.reserve(16);
+        dest.to_map(0)?;
+        dest.duplicate_subtree_resolved(&src, sub, 0, NONE)?;
+        assert_eq!(
+            "sub:\n  a: &b\n    x: 1\n  c: *b\n  d: &d 2\n  e: *d\n",
+            dest.emit()?
+        );
+        dest.resolve()?;
+        let mut plain = Tree::default();
+        plain.reserve(16);
+        plain.to_map(0)?;
+        plain.duplicate_from_tree(&src, sub, 0, NONE)?;
+        plain.resolve().expect_err("anchor b is not in the copy");
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_resolved_nested() -> Result<()> {
+        let src = Tree::parse("base: &b {p: 1}\nm: &m {q: *b}\nk: &k key\nsub: {v: *m, *k : x}\n")?;
+        let sub = src.find_child(src.root_id()?, "sub")?;
+        let mut copy = src.clone_subtree(sub)?;
+        assert_eq!("v: &m\n  q: &b\n    p: 1\nkey: x\n", copy.emit()?);
+        copy.resolve()?;
+        assert_eq!("v:\n  q:\n    p: 1\nkey: x\n", copy.emit()?);
+
+        let src = Tree::parse("a: &x {sub: {v: *x}}\n")?;
+        let sub = src.at("a.sub")?.index();
+        assert!(matches!(
+            src.clone_subtree(sub),
+            Err(Error::RecursiveAlias(name)) if name == "x"
+        ));
+        let src = Tree::parse("sub: {v: *y}\n")?;
+        let sub = src.at("sub")?.index();
+        assert!(matches!(
+            src.clone_subtree(sub),
+            Err(Error::AnchorNotFound(name)) if name == "y"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn emit_escaped() -> Result<()> {
+        let tree = Tree::parse("discount: 50%\nemail: me@example.com\nplain: text\n")?;
+        let options = EmitOptions {
+            escape: vec!['%', '@'],
+            ..Default::default()
+        };
+        assert_eq!(
+            "discount: \"50\\x25\"\nemail: \"me\\x40example.com\"\nplain: text\n",
+            tree.emit_with(&options)?
+        );
+        assert_eq!(tree.emit()?, tree.emit_with(&EmitOptions::default())?);
+        let reparsed = Tree::parse(tree.emit_with(&options)?)?;
+        assert_eq!("50%", reparsed.val(1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_grouped() -> Result<()> {
+        let tree = Tree::parse("a: 1\nb: {x: 1, y: [1], z: 2}\nc: 2\nd: [1]\ne: 3\n")?;
+        let options = EmitOptions {
+            group_scalars_first: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "a: 1\nc: 2\ne: 3\nb:\n  x: 1\n  z: 2\n  y:\n    - 1\nd:\n  - 1\n",
+            tree.emit_with(&options)?
+        );
+        assert_eq!("b", tree.key(tree.child_at(0, 1)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn validate() {
+        assert!(Tree::is_valid_yaml(SRC));
+        assert!(Tree::is_valid_yaml("a: [b, c]"));
+        assert!(!Tree::is_valid_yaml("a: [b, c"));
+        Tree::validate_yaml("a: 'b").expect_err("unterminated quoted scalar");
+    }
+
+    #[test]
+    fn parse_warnings() -> Result<()> {
+        let src = "a: 1\nb: !!set {x: null}\na: 2\n\tc: 3\n";
+        let (tree, warnings) = Tree::parse_with_warnings(src)?;
+        assert_eq!(4, tree.num_children(tree.root_id()?)?);
+        assert_eq!(
+            warnings,
+            [
+                Warning::DeprecatedTag {
+                    tag: "!!set".into(),
+                    location: Location {
+                        offset: 5,
+                        line: 1,
+                        col: 0
+                    }
+                },
+                Warning::DuplicateKey {
+                    key: "a".into(),
+                    location: Location {
+                        offset: 24,
+                        line: 2,
+                        col: 0
+                    }
+                },
+                Warning::TabIndentation {
+                    location: Location {
+                        offset: 29,
+                        line: 3,
+                        col: 0
+                    }
+                },
+            ]
+        );
+        assert!(Tree::parse_with_warnings(SRC)?.1.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_warnings_allowed_tabs() -> Result<()> {
+        for src in [
+            "a: |\n  \tx\n",
+            "a: >-\n\n  x\n  \ty\nb: 1\n",
+            "- |2 # literal\n   \tx\n",
+            "a: [1,\n\t2]\n",
+            "a: {b: [1,\n\t2],\n\tc: 3}\n",
+            "a: 'x\n\ty'\n",
+            "a: \"x\n\ty\"\n",
+            "a: 1\n\t# comment\n",
+        ] {
+            assert_eq!(
+                Vec::<Warning>::new(),
+                Tree::parse_with_warnings(src)?.1,
+                "{src:?}"
+            );
+        }
+        // The tab after the block scalar and flow collection is still found.
+        let (_, warnings) = Tree::parse_with_warnings("a: |\n  x\nb: [1,\n\t2]\n\tc: 3\n")?;
+        assert_eq!(
+            vec![20],
+            warnings
+                .iter()
+                .map(|warning| warning.location().offset)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn emit_json_tags() -> Result<()> {
+        let tree = Tree::parse("a: !str d\nb: !!seq [1, 'x']\nc: 0x1f\n")?;
+        tree.emit_json().expect_err("JSON doesn't support tags");
+        let mut options = EmitOptions::default();
+        assert!(matches!(
+            tree.emit_json_with(&options),
+            Err(Error::JsonUnsupported("tags"))
+        ));
+        options.json_tags = JsonTagEncoding::Drop;
+        assert_eq!(
+            r#"{"a": "d","b": [1,"x"],"c": 31}"#,
+            tree.emit_json_with(&options)?
+        );
+        options.json_tags = JsonTagEncoding::WrapObject;
+        assert_eq!(
+            r#"{"a": {"$tag": "!str","value": "d"},"b": {"$tag": "!!seq","value": [1,"x"]},"c": 31}"#,
+            tree.emit_json_with(&options)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn emit_json_with_yaml_options() -> Result<()> {
+        let tree = Tree::parse("a: [1, 2]\n")?;
+        let unsupported = [
+            (
+                EmitOptions {
+                    indent: 4,
+                    ..Default::default()
+                },
+                "indent",
+            ),
+            (
+                EmitOptions {
+                    line_ending: LineEnding::CrLf,
+                    ..Default::default()
+                },
+                "line_ending",
+            ),
+            (
+                EmitOptions {
+                    max_flow_width: Some(80),
+                    ..Default::default()
+                },
+                "max_flow_width",
+            ),
+            (
+                EmitOptions {
+                    doc_start: true,
+                    ..Default::default()
+                },
+                "doc_start",
+            ),
+            (
+                EmitOptions {
+                    comments: true,
+                    ..Default::default()
+                },
+                "comments",
+            ),
+        ];
+        for (options, option) in unsupported {
+            match tree.emit_json_with(&options) {
+                Err(Error::UnsupportedEmitOption(name)) => assert_eq!(option, name),
+                other => panic!("unexpected result {:?} for {}", other, option),
+            }
+        }
+        assert_eq!("null", Tree::default().emit_json_with(&Default::default())?);
+        assert_eq!(
+            "null",
+            Tree::parse("")?.emit_json_with(&Default::default())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn emit_json_with_resolved_scalars() -> Result<()> {
+        let options = EmitOptions {
+            json_tags: JsonTagEncoding::Drop,
+            ..Default::default()
+        };
+        let tree = Tree::parse(
+            "a: [~, null, '~', True, 1e3, .5, -7]
+b:
+c: !!int '12'
+",
+        )?;
+        let json = tree.emit_json_with(&options)?;
+        assert_eq!(
+            r#"{"a": [null,null,"~",true,1e3,0.5,-7],"b": null,"c": 12}"#,
+            json
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(
+            tree.to_json_value()?,
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        );
+        for source in [
+            "a: &x 1
+b: *x
+",
+            "a: *x
+",
+            "a: .inf
+",
+        ] {
+            assert!(matches!(
+                Tree::parse(source)?.emit_json_with(&options),
+                Err(Error::JsonUnsupported(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn val_bytes() -> Result<()> {
+        let tree = Tree::parse("plain: /tmp/a\nraw: \"/tmp/\\xff\"\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!(b"/tmp/a", root.get("plain")?.val_bytes()?);
+        assert_eq!(b"/tmp/\xff", root.get("raw")?.val_bytes()?);
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let raw = root.get("raw")?;
+            assert_eq!(b"/tmp/\xff", raw.as_path()?.as_os_str().as_bytes());
+            assert_eq!(
+                std::path::Path::new("/tmp/a"),
+                root.get("plain")?.as_path()?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn overlay() -> Result<()> {
+        let base = "name: app\nserver:\n  host: localhost\n  ports: [80]\nlog: info\n";
+        let fragment =
+            Tree::parse("server:\n  ports: [443]\n  tls: true\nlog: [stdout]\nextra: 1\n")?;
+        let mut tree = Tree::parse(base)?;
+        tree.root_ref_mut()?
+            .overlay(&fragment.root_ref()?, SeqMerge::Replace)?;
+        assert_eq!(
+            "name: app\nserver:\n  host: localhost\n  ports:\n    - 443\n  tls: true\nlog:\n  - stdout\nextra: 1\n",
+            tree.emit()?
+        );
+        let mut tree = Tree::parse(base)?;
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut("server")?
+            .overlay(&fragment.root_ref()?.get("server")?, SeqMerge::Concat)?;
+        root.get_mut("client")?
+            .overlay(&fragment.root_ref()?.get("server")?, SeqMerge::Concat)?;
+        drop(fragment);
+        assert_eq!(
+            "name: app\nserver:\n  host: localhost\n  ports:\n    - 80\n    - 443\n  tls: true\nlog: info\nclient:\n  ports:\n    - 443\n  tls: true\n",
+            tree.emit()?
+        );
+        let other = tree.clone();
+        assert!(matches!(
+            tree.root_ref_mut()?
+                .get_mut("log")?
+                .overlay(&other.root_ref()?, SeqMerge::Replace),
+            Err(Error::NotAMap)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn get_or_insert() -> Result<()> {
+        let mut tree = Tree::parse("a: {b: 1}\nc: [x]\nd:\ne: text\n")?;
+        let mut root = tree.root_ref_mut()?;
+        root.get_or_insert_map("a")?
+            .get_or_insert_seq("f")?
+            .get_mut(0)?
+            .set_val("y")?;
+        root.get_or_insert_seq("c")?.get_mut(1)?.set_val("z")?;
+        root.get_or_insert_map("d")?.get_mut("g")?.set_val("2")?;
+        assert!(matches!(root.get_or_insert_seq("a"), Err(Error::NotASeq)));
+        assert!(matches!(root.get_or_insert_map("c"), Err(Error::NotAMap)));
+        assert!(matches!(root.get_or_insert_map("e"), Err(Error::NotAMap)));
+        assert!(matches!(
+            root.get_mut("c")?.get_or_insert_map("h"),
+            Err(Error::NotAMap)
+        ));
+        // A seeded parent is created on the way.
+        root.get_mut("new")?
+            .get_or_insert_map("inner")?
+            .get_mut("k")?
+            .set_val("v")?;
+        let mut seq = root.get_mut("c")?;
+        seq.get_mut(2)?.get_or_insert_seq("l")?;
+        assert_eq!(
+            "a:\n  b: 1\n  f:\n    - y\nc:\n  - x\n  - z\n  - l: []\nd:\n  g: 2\ne: text\nnew:\n  inner:\n    k: v\n",
+            tree.emit()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn val_style() -> Result<()> {
+        let mut tree = Tree::parse("a: 'x y'\nb: [1]\n")?;
+        let mut a = tree.root_ref_mut()?.get_mut("a")?;
+        assert_eq!(ScalarStyle::Auto, a.val_style()?);
+        for style in [
+            ScalarStyle::Plain,
+            ScalarStyle::DoubleQuoted,
+            ScalarStyle::Folded,
+            ScalarStyle::SingleQuoted,
+            ScalarStyle::Literal,
+        ] {
+            a.set_val_style(style)?;
+            assert_eq!(style, a.val_style()?);
+            let node_type = a.node_type()?;
+            assert_eq!(style.val_flag(), node_type & NodeType::WipValStyle);
+            assert!(node_type.is_keyval());
+        }
+        a.set_val_style(ScalarStyle::DoubleQuoted)?;
+        let mut c = tree.root_ref_mut()?.get_mut("c")?;
+        c.set_val("z")?;
+        c.set_val_style(ScalarStyle::SingleQuoted)?;
+        assert_eq!("a: \"x y\"\nb:\n  - 1\nc: 'z'\n", tree.emit()?);
+        tree.root_ref_mut()?
+            .get_mut("a")?
+            .set_val_style(ScalarStyle::Auto)?;
+        assert_eq!("a: 'x y'\nb:\n  - 1\nc: 'z'\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_from() -> Result<()> {
+        let base = "a:\n  b:\n    c: 1\n    d: 2\n  list: [x]\ne: &e {f: 3}\n";
+        let other = Tree::parse("a:\n  b:\n    d: 20\n    g: 4\n  list: [y]\ne: scalar\n")?;
+        let mut tree = Tree::parse(base)?;
+        tree.merge_from(0, &other, 0, SeqMerge::Replace)?;
+        drop(other);
+        assert_eq!(
+            "a:\n  b:\n    c: 1\n    d: 20\n    g: 4\n  list:\n    - y\ne: scalar\n",
+            tree.emit()?
+        );
+        // Scalars, sequences and maps can replace each other anywhere,
+        // keeping the destination's key.
+        let other = Tree::parse("- {h: 5}\n- [z]\n")?;
+        let mut tree = Tree::parse(base)?;
+        let e = tree.find_child(0, "e")?;
+        tree.merge_from(e, &other, 0, SeqMerge::Concat)?;
+        let list = tree.at("a.list")?.index();
+        tree.merge_from(list, &other, other.child_at(0, 1)?, SeqMerge::Concat)?;
+        let b = tree.at("a.b")?.index();
+        tree.merge_from(b, &other, other.child_at(0, 0)?, SeqMerge::Concat)?;
+        assert_eq!(
+            "a:\n  b:\n    c: 1\n    d: 2\n    h: 5\n  list:\n    - x\n    - z\ne:\n  - h: 5\n  - - z\n",
+            tree.emit()?
+        );
+        let scalar = Tree::parse("text")?;
+        tree.merge_from(0, &scalar, 0, SeqMerge::Replace)?;
+        assert_eq!("text\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_to_slice() -> Result<()> {
+        let first = Tree::parse("a: 1")?;
+        let second = Tree::parse("[b, c]")?;
+        let mut buf = [0; 64];
+        let (a, rest) = first.emit_to_slice(&mut buf)?;
+        assert_eq!("a: 1\n", a);
+        let (b, rest) = second.emit_to_slice(rest)?;
+        assert_eq!("- b\n- c\n", b);
+        assert_eq!(64 - a.len() - b.len(), rest.len());
+        assert!(first.emit_to_slice(&mut [0; 2]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn val_eq_normalized() -> Result<()> {
+        let ours =
+            Tree::parse("[app, true, 0x1F, 1.5e3, ~, '1', 1, .inf, .nan, \"a b\", *x, {a: 1}]")?;
+        let theirs =
+            Tree::parse("[\"app\", Yes, 31, 1500.0, null, 1, 1.0, +.inf, .NaN, a b, *x, {a: 1}]")?;
+        let equal: Vec<bool> = (0..12)
+            .map(|pos| {
+                ours.val_eq_normalized(ours.child_at(0, pos)?, &theirs, theirs.child_at(0, pos)?)
+            })
+            .collect::<Result<_>>()?;
+        let (ours, theirs) = (ours.root_ref()?, theirs.root_ref()?);
+        assert!(ours
+            .first_child()?
+            .val_eq_normalized(&theirs.first_child()?)?);
+        assert_eq!(
+            vec![true, true, true, true, true, false, false, true, true, true, true, false],
+            equal
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_presized() -> Result<()> {
+        let items: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let tree = Tree::parse(format!("[{}]", items.join(", ")))?;
+        assert_eq!(101, tree.len());
+        assert_eq!(estimate_node_capacity(&items.join(",")), tree.capacity());
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_anchors() -> Result<()> {
+        let src = "a: &base 1\nb: *base\nc: &base 2\nd: *base\ne: &base_2 3\nf: &base 4\ng: *base\nh: *base_2\n";
+        let mut tree = Tree::parse(src)?;
+        let dups = tree.find_duplicate_anchors()?;
+        assert_eq!(1, dups.len());
+        assert_eq!("base", dups[0].0);
+        let nodes: Vec<&str> = dups[0]
+            .1
+            .iter()
+            .map(|&node| tree.key(node))
+            .collect::<Result<_>>()?;
+        assert_eq!(vec!["a", "c", "f"], nodes);
+        let mut resolved = tree.clone();
+        resolved.resolve()?;
+        assert_eq!(2, tree.dedup_anchors()?);
+        assert!(tree.find_duplicate_anchors()?.is_empty());
+        assert_eq!(
+            "a: &base 1\nb: *base\nc: &base_3 2\nd: *base_3\ne: &base_2 3\nf: &base_4 4\ng: *base_4\nh: *base_2\n",
+            tree.emit()?
+        );
+        tree.resolve()?;
+        assert_eq!(resolved.emit()?, tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn set_node_data() -> Result<()> {
+        let mut tree = Tree::parse("a: &x 1\nb: [c]\n")?;
+        let before = tree.clone();
+        let a = tree.find_child(0, "a")?;
+        let b = tree.find_child(0, "b")?;
+        tree.get_mut(a)?.set_key("z")?;
+        tree.get_mut(a)?.set_val("changed")?;
+        tree.rem_val_anchor(a)?;
+        let other = Tree::parse("[!!str 2]")?;
+        let foreign = *other.get(1)?.data().unwrap();
+        assert!(matches!(
+            tree.set_node_data(b, &foreign),
+            Err(Error::StructureMismatch)
+        ));
+        tree.set_node_data(a, before.get(a)?.data().unwrap())?;
+        drop(before);
+        tree.set_node_data(tree.child_at(b, 0)?, &foreign)?;
+        drop(other);
+        assert_eq!("a: &x 1\nb:\n  - !!str 2\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn iter_mutation() -> Result<()> {
+        let mut tree = Tree::parse("[a, b, c, d, e, f]")?;
+        let mut seen = Vec::new();
+        let mut root = tree.root_ref_mut()?;
+        let iter = root.iter_mut()?;
+        assert_eq!(6, iter.len());
+        for mut node in iter {
+            let val = node.val()?.to_owned();
+            if val == "b" {
+                // Remove the node itself and the one after it.
+                let tree = node.tree_mut();
+                let next = tree.next_sibling(node.index())?;
+                tree.remove(next)?;
+                tree.remove(node.index())?;
+            }
+            seen.push(val);
+        }
+        assert_eq!(vec!["a", "b", "d", "e", "f"], seen);
+        assert_eq!("- a\n- d\n- e\n- f\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn iter_rev() -> Result<()> {
+        let mut tree = Tree::parse("[a, b, c, d, e]")?;
+        let root = tree.root_ref()?;
+        let forward: Vec<_> = root.iter()?.map(|n| n.val().unwrap().to_owned()).collect();
+        let reverse: Vec<_> = root
+            .iter()?
+            .rev()
+            .map(|n| n.val().unwrap().to_owned())
+            .collect();
+        for (a, b) in forward.iter().zip(reverse.iter().rev()) {
+            assert_eq!(a, b);
+        }
+        assert_eq!(vec!["e", "d", "c", "b", "a"], reverse);
+        let mut iter = root.iter()?;
+        assert_eq!("a", iter.next().unwrap().val()?);
+        assert_eq!("e", iter.next_back().unwrap().val()?);
+        assert_eq!("d", iter.next_back().unwrap().val()?);
+        assert_eq!(2, iter.len());
+        assert_eq!((2, Some(2)), iter.size_hint());
+        assert_eq!("b", iter.next().unwrap().val()?);
+        assert_eq!("c", iter.next_back().unwrap().val()?);
+        assert_eq!(0, iter.len());
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+        let mut root = tree.root_ref_mut()?;
+        let mut iter = root.iter_mut()?;
+        assert_eq!((5, Some(5)), iter.size_hint());
+        iter.next_back().unwrap().set_val("z")?;
+        assert_eq!(4, iter.len());
+        assert_eq!("a", iter.next().unwrap().val()?);
+        assert_eq!("z", root.child_at(4)?.val()?);
+        assert_eq!(0, Tree::parse("[]")?.root_ref()?.iter()?.rev().count());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_keys() -> Result<()> {
+        let mut tree = Tree::parse(
+            "a: &a {x: 1, y: 1}\nb: &b {y: 2, z: 2}\nc: {<<: *a}\nd: &d\n  y: 0\n  <<: [*a, *b]\ne: [{<<: *d, w: 4}]\n",
+        )?;
+        tree.resolve_merge_keys()?;
+        assert_eq!(
+            tree.emit()?,
+            "a: &a\n  x: 1\n  y: 1\nb: &b\n  y: 2\n  z: 2\nc:\n  x: 1\n  y: 1\nd: &d\n  y: 0\n  x: 1\n  z: 2\ne:\n  - y: 0\n    x: 1\n    z: 2\n    w: 4\n"
+        );
+        let mut tree = Tree::parse("a: &a [1]\nb: {<<: *a}\n")?;
+        assert!(matches!(tree.resolve(), Err(Error::NotAMap)));
+        let mut tree = Tree::parse("b: {<<: *a}\n")?;
+        assert!(matches!(
+            tree.resolve(),
+            Err(Error::AnchorNotFound(name)) if name == "a"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn retain_comments() -> Result<()> {
+        let src = "# settings\nserver: # required\n  port: 80 # default\n  hosts:\n    - a\n    # b is quoted\n    - 'b # not'\n  text: |\n    # not a comment\n  flow: [1, 2] # flow\n# end\n";
+        let options = ParseOptions {
+            retain_comments: true,
+            ..Default::default()
+        };
+        let tree = Tree::parse_with(src, &options)?;
+        let root = tree.root_ref()?;
+        let first = root.get(0)?;
+        assert_eq!(
+            ("#", COMMENT_TAG, "settings"),
+            (first.key()?, first.val_tag()?, first.val()?)
+        );
+        let server = root.get("server")?;
+        assert_eq!(TRAILING_COMMENT_TAG, server.get(0)?.val_tag()?);
+        assert_eq!("default", server.get(2)?.val()?);
+        assert_eq!("b is quoted", server.get("hosts")?.get(1)?.val()?);
+        assert_eq!(3, server.get("flow")?.num_children()?);
+        assert_eq!("end", root.last_child()?.val()?);
+        let yaml = tree.emit_with(&EmitOptions {
+            comments: true,
+            ..Default::default()
+        })?;
+        assert_eq!(
+            yaml,
+            "# settings\nserver: # required\n  port: 80 # default\n  hosts:\n    - a\n    # b is quoted\n    - 'b # not'\n  text: |\n    # not a comment\n\n  flow:\n    - 1\n    - 2 # flow\n# end\n"
+        );
+        assert!(tree.emit()?.contains("'#': !trailing-comment required\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn iter_map() -> Result<()> {
+        let tree = Tree::parse("{a: 1, b: [2], c: {d: 3}}")?;
+        let root = tree.root_ref()?;
+        let keys: Vec<_> = root.iter_map()?.map(|(key, _)| key).collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+        let (key, node) = root.iter_map()?.last().unwrap();
+        assert_eq!("c", key);
+        assert_eq!("3", node.get("d")?.val()?);
+        assert!(matches!(root.get("b")?.iter_map(), Err(Error::NotAMap)));
+        assert!(matches!(root.get("a")?.iter_map(), Err(Error::NotAMap)));
+        let tree = Tree::parse("? [a, b]\n: c\nd: e\n")?;
+        assert!(matches!(
+            tree.root_ref()?.iter_map(),
+            Err(Error::ComplexKey)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn move_within_parent() -> Result<()> {
+        let mut tree = Tree::parse("[a, b, c, d]")?;
+        let order = |tree: &Tree| -> Result<String> {
+            Ok(tree
+                .root_ref()?
+                .iter()?
+                .map(|n| n.val().unwrap().to_owned())
+                .collect::<Vec<_>>()
+                .join(""))
+        };
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut(2)?.move_up()?;
+        root.get_mut(0)?.move_up()?;
+        assert_eq!("acbd", order(&tree)?);
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut(0)?.move_down()?;
+        root.get_mut(3)?.move_down()?;
+        assert_eq!("cabd", order(&tree)?);
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut(2)?.move_to_front()?;
+        root.get_mut(0)?.move_to_front()?;
+        assert_eq!("bcad", order(&tree)?);
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut(0)?.move_to_back()?;
+        root.get_mut(3)?.move_to_back()?;
+        root.move_up()?;
+        assert_eq!("cadb", order(&tree)?);
+        Ok(())
+    }
+
+    #[test]
+    fn at_path() -> Result<()> {
+        let tree = Tree::parse(SRC)?;
+        let node = tree.at("param_root.objects.DemoAIActionIdx")?;
+        assert_eq!("DemoAIActionIdx", node.key()?);
+        assert_eq!(
+            "1",
+            tree.at("param_root.objects.DemoAIActionIdx.Demo_Join")?
+                .val()?
+        );
+        assert_eq!(tree.root_id()?, tree.at("")?.index());
+        for missing in [
+            "param_root.missing",
+            "param_root.objects[0]",
+            "version.x",
+            "param_root.lists.AI.objects['a.b']",
+        ] {
+            assert!(
+                matches!(tree.at(missing), Err(Error::NodeNotFound)),
+                "{}",
+                missing
+            );
+        }
+        let tree = Tree::parse("a: [x, {weird.key: [1, 2]}]")?;
+        assert_eq!("2", tree.at("a[1]['weird.key'][1]")?.val()?);
+        assert!(matches!(tree.at("a[2]"), Err(Error::NodeNotFound)));
+        assert!(matches!(tree.at("a..b"), Err(Error::InvalidPath { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn deep_eq() -> Result<()> {
+        let src = "a: &x !t [1, {b: c}]\nd: *x\n";
+        let tree = Tree::parse(src)?;
+        let same = Tree::parse("a: &x !t\n  - '1'\n  - b: c\nd: *x\n")?;
+        assert!(tree.deep_eq(&same));
+        assert!(tree == same);
+        assert!(tree.deep_eq(&tree.clone()));
+        for different in [
+            "a: &x !t [1.0, {b: c}]\nd: *x\n",
+            "a: &x !u [1, {b: c}]\nd: *x\n",
+            "a: &y !t [1, {b: c}]\nd: *y\n",
+            "a: &x !t [1, {b: c}]\nd: x\n",
+            "a: &x !t [1, {b: c}, e]\nd: *x\n",
+            "a: &x !t [{b: c}, 1]\nd: *x\n",
+            "d: *x\na: &x !t [1, {b: c}]\n",
+            "a: &x !t [1, [b, c]]\nd: *x\n",
+        ] {
+            assert!(!tree.deep_eq(&Tree::parse(different)?), "{}", different);
+        }
+        assert!(Tree::default().deep_eq(&Tree::default()));
+        assert!(!tree.deep_eq(&Tree::default()));
+        Ok(())
+    }
+
+    #[test]
+    // The interior mutability of a tree is its dirty flag, arena cache and
+    // offset index, none of which is hashed.
+    #[allow(clippy::mutable_key_type)]
+    fn hash_content() -> Result<()> {
+        use std::collections::HashMap;
+        use std::hash::{BuildHasher, RandomState};
+
+        let state = RandomState::new();
+        let tree = Tree::parse("a: &x !t [1, {b: c}]\nd: *x\n")?;
+        let same = Tree::parse("a: &x !t\n  - '1'\n  - b: c\nd: *x\n")?;
+        assert_eq!(state.hash_one(&tree), state.hash_one(&same));
+        assert_eq!(state.hash_one(&tree), state.hash_one(tree.clone()));
+        for different in ["a: [1, {b: c}]\n", "a: [[1], b, c]\n", "a: [1, [b, c]]\n"] {
+            assert_ne!(
+                state.hash_one(&tree),
+                state.hash_one(Tree::parse(different)?)
+            );
+        }
+
+        let mut cache = HashMap::new();
+        cache.insert(tree, 1);
+        assert_eq!(Some(&1), cache.get(&same));
+        cache.insert(Tree::default(), 2);
+        assert_eq!(Some(&2), cache.get(&Tree::default()));
+        assert_eq!(2, cache.len());
+        Ok(())
+    }
+
+    #[test]
+    fn scalar_lens() -> Result<()> {
+        let tree = Tree::parse("naïve: [a, bc]\nempty:\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!((0, 0), (root.key_len()?, root.val_len()?));
+        let seq = root.get(0)?;
+        assert_eq!((6, 0), (seq.key_len()?, seq.val_len()?));
+        assert_eq!((0, 2), (seq.get(1)?.key_len()?, seq.get(1)?.val_len()?));
+        assert_eq!(0, root.get("empty")?.val_len()?);
+        assert!(matches!(tree.val_len(99), Err(Error::NodeNotFound)));
+        Ok(())
+    }
+
+    #[test]
+    fn set_copied() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: 2\n")?;
+        let a = tree.find_child(0, "a")?;
+        {
+            let key = String::from("key");
+            let val = format!("{}{}", "val", 1);
+            tree.set_key_copied(a, &key)?;
+            tree.set_val_copied(a, &val)?;
+        }
+        // Overwrite whatever the dropped strings used to hold.
+        let _noise = vec![String::from("xxxxx"); 16];
+        assert_eq!("key: val1\nb: 2\n", tree.emit()?);
+        let b = tree.find_child(0, "b")?;
+        assert_eq!("b", tree.key_tree(b)?.emit()?.trim_end());
+        Ok(())
+    }
+
+    #[test]
+    fn try_clone() -> Result<()> {
+        let big = "x".repeat(4096);
+        let tree = Tree::parse_owned_buf(format!("a: {big}\n").into_bytes())?;
+        ALLOCATION_LIMIT.with(|limit| limit.set(1024));
+        let clone = tree.try_clone();
+        ALLOCATION_LIMIT.with(|limit| limit.set(usize::MAX));
+        assert!(matches!(clone, Err(Error::ArenaFull { needed: 4096, .. })));
+        let clone = tree.try_clone()?;
+        drop(tree);
+        assert_eq!(big, clone.root_ref()?.get("a")?.val()?);
+        Ok(())
+    }
+
+    #[test]
+    fn arena_full() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\n")?;
+        let a = tree.find_child(0, "a")?;
+        let big = "x".repeat(4096);
+        ALLOCATION_LIMIT.with(|limit| limit.set(1024));
+        let copied = tree.copy_to_arena(&big).map(|_| ());
+        let set = tree.set_val_copied(a, &big);
+        ALLOCATION_LIMIT.with(|limit| limit.set(usize::MAX));
+        assert!(matches!(copied, Err(Error::ArenaFull { needed: 4096, .. })));
+        assert!(matches!(set, Err(Error::ArenaFull { needed: 4096, .. })));
+        assert_eq!("a: 1\n", tree.emit()?);
+        tree.set_val_copied(a, &big)?;
+        assert_eq!(big, tree.val(a)?);
+        Ok(())
+    }
+
+    #[test]
+    fn visit_events() -> Result<()> {
+        #[derive(Default)]
+        struct Recorder(Vec<String>);
+
+        impl EventHandler for Recorder {
+            fn on_doc_start(&mut self) {
+                self.0.push("doc".into());
+            }
+            fn on_doc_end(&mut self) {
+                self.0.push("/doc".into());
+            }
+            fn on_map_start(&mut self, tag: Option<&str>, anchor: Option<&str>) {
+                self.0.push(format!("map {tag:?} {anchor:?}"));
+            }
+            fn on_map_end(&mut self) {
+                self.0.push("/map".into());
+            }
+            fn on_seq_start(&mut self, tag: Option<&str>, anchor: Option<&str>) {
+                self.0.push(format!("seq {tag:?} {anchor:?}"));
+            }
+            fn on_seq_end(&mut self) {
+                self.0.push("/seq".into());
+            }
+            fn on_scalar(&mut self, value: &str, tag: Option<&str>, anchor: Option<&str>) {
+                self.0.push(format!("{value} {tag:?} {anchor:?}"));
+            }
+            fn on_alias(&mut self, anchor: &str) {
+                self.0.push(format!("*{anchor}"));
+            }
+        }
+
+        let mut recorder = Recorder::default();
+        Tree::parse("a: &x !!str 1\nb: !s [*x, c]\nd:\n")?.visit_events(&mut recorder)?;
+        assert_eq!(
+            vec![
+                "doc",
+                "map None None",
+                "a None None",
+                "1 Some(\"!!str\") Some(\"x\")",
+                "b None None",
+                "seq Some(\"!s\") None",
+                "*x",
+                "c None None",
+                "/seq",
+                "d None None",
+                " None None",
+                "/map",
+                "/doc",
+            ],
+            recorder.0
+        );
+
+        let mut recorder = Recorder::default();
+        super::parse_events("--- 1\n--- [2]\n", &mut recorder)?;
+        assert_eq!(
+            vec![
+                "doc",
+                "1 None None",
+                "/doc",
+                "doc",
+                "seq None None",
+                "2 None None",
+                "/seq",
+                "/doc"
+            ],
+            recorder.0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_schema() -> Result<()> {
+        let schema = Schema {
+            kind: Some(SchemaKind::Map),
+            required: vec!["name".into(), "port".into()],
+            properties: [
+                (
+                    "level".to_owned(),
+                    Schema {
+                        allowed: vec!["debug".into(), "info".into()],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "servers".to_owned(),
+                    Schema {
+                        kind: Some(SchemaKind::Seq),
+                        items: Some(Box::new(Schema {
+                            required: vec!["host".into()],
+                            ..Default::default()
+                        })),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "tags".to_owned(),
+                    Schema {
+                        kind: Some(SchemaKind::Seq),
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        let tree = Tree::parse("name: a\nport: 1\nlevel: info\nservers: [{host: x}]\n")?;
+        assert_eq!(Ok(()), tree.validate(&schema));
+
+        let tree = Tree::parse(
+            "name: a\nlevel: trace\nservers: [{host: x}, {port: 2}, y]\ntags: {a: 1}\n",
+        )?;
+        let errors = tree.validate(&schema).unwrap_err();
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "at the root: missing required key \"port\"",
+                "at level: \"trace\" is not one of the allowed values: debug, info",
+                "at servers[1]: missing required key \"host\"",
+                "at servers[2]: expected a map, found a scalar",
+                "at tags: expected a sequence, found a map",
+            ],
+            errors
+        );
+
+        let errors = Tree::parse("[1]")?.validate(&schema).unwrap_err();
+        assert_eq!(1, errors.len());
+        assert_eq!("", errors[0].path);
+        assert!(Tree::default().validate(&Schema::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_in_place_bytes() -> Result<()> {
+        let mut buf = "a: [héllo, 2]\n".as_bytes().to_vec();
+        let tree = Tree::parse_in_place_bytes(&mut buf)?;
+        assert!(!tree.is_owned());
+        assert_eq!("héllo", tree.at("a[0]")?.val()?);
+        drop(tree);
+
+        let tree = Tree::parse_bytes(&buf)?;
+        drop(buf);
+        assert!(tree.is_owned());
+        assert_eq!("héllo", tree.at("a[0]")?.val()?);
+
+        let mut bad = b"a: 1\nb: \xc3(\n".to_vec();
+        match Tree::parse_in_place_bytes(&mut bad) {
+            Err(Error::Utf8(e)) => assert_eq!(8, e.valid_up_to()),
+            other => panic!("unexpected result {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_into() -> Result<()> {
+        let mut tree = Tree::default();
+        tree.parse_into("a: [1, 2, 3]\nb: {c: d}\n")?;
+        assert_eq!("d", tree.at("b.c")?.val()?);
+        let (capacity, arena_capacity) = (tree.capacity(), tree.arena_capacity());
+
+        tree.parse_into("x: y")?;
+        assert!(!tree.is_dirty());
+        assert_eq!(2, tree.len());
+        assert_eq!("y", tree.at("x")?.val()?);
+        assert_eq!(capacity, tree.capacity());
+        assert_eq!(arena_capacity, tree.arena_capacity());
+        assert_eq!("x: y\n", tree.emit()?);
+
+        assert!(matches!(tree.parse_into("a: 'b"), Err(Error::Parse { .. })));
+        assert_eq!(1, tree.len());
+        assert_eq!(NodeType::NoType, tree.node_type(0)?);
+
+        let mut buf = String::from("- a");
+        let mut tree = Tree::parse_in_place(buf.as_mut_str())?;
+        tree.parse_into("[b, c]")?;
+        assert!(tree.is_owned());
+        assert_eq!("c", tree.at("[1]")?.val()?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_events() -> Result<()> {
+        let tree = Tree::parse(
+            "a: &x !!str 1\nb: *x\n&k !t c: \"q\\tr\\\\\"\nd: [!<tag:x> v, ~, |\n  lit\n]\n",
+        )?;
+        assert_eq!(
+            "+STR\n+DOC\n+MAP\n\
+             =VAL :a\n=VAL &x <tag:yaml.org,2002:str> :1\n\
+             =VAL :b\n=ALI *x\n\
+             =VAL &k <!t> :c\n=VAL :q\\tr\\\\\n\
+             =VAL :d\n+SEQ\n=VAL <tag:x> :v\n=VAL :~\n=VAL :lit\\n\n-SEQ\n\
+             -MAP\n-DOC\n-STR\n",
+            tree.emit_events()?
+        );
+        let tree = Tree::parse("--- !foo\n- a\n---\nb: {}\n")?;
+        assert_eq!(
+            "+STR\n+DOC ---\n+SEQ <!foo>\n=VAL :a\n-SEQ\n-DOC\n\
+             +DOC ---\n+MAP\n=VAL :b\n+MAP\n-MAP\n-MAP\n-DOC\n-STR\n",
+            tree.emit_events()?
+        );
+        assert_eq!("+STR\n-STR\n", Tree::parse("")?.emit_events()?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_to_writer_cursor() -> Result<()> {
+        let tree = Tree::parse(
+            "a: &x 1\nb: *x\nc: !t [x, 'y z', \"w\\n\"]\nd: |\n  text\n---\n- ~\n- {e: f}\n",
+        )?;
+        let yaml = tree.emit()?;
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        let written = tree.emit_to_writer(&mut cursor)?;
+        assert_eq!(yaml.len(), written);
+        assert_eq!(yaml.as_bytes(), cursor.get_ref());
+        Ok(())
+    }
+
+    #[test]
+    fn emit_to_writer_unseekable() -> Result<()> {
+        let tree = Tree::parse("a: 1\nb: [x, 'y z']\nc: |\n  text\n")?;
+        let yaml = tree.emit()?;
+        let mut out = b"# header\n".to_vec();
+        let written = tree.emit_to_writer_unseekable(&mut out)?;
+        assert_eq!(yaml.len(), written);
+        assert_eq!(format!("# header\n{}", yaml).as_bytes(), out);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_json_outputs() -> Result<()> {
+        let tree = Tree::parse("a: 1\nb: [x, 'y z']\n")?;
+        let json = tree.emit_json()?;
+        assert_eq!(r#"{"a": 1,"b": ["x","y z"]}"#, json);
+        let mut buf = [0; 64];
+        let len = tree.emit_json_to_buffer(&mut buf)?;
+        assert_eq!(json.as_bytes(), &buf[..len]);
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        tree.emit_json_to_writer(&mut cursor)?;
+        assert_eq!(json.as_bytes(), cursor.get_ref());
+        let mut out = Vec::new();
+        assert_eq!(json.len(), tree.emit_json_to_writer_unseekable(&mut out)?);
+        assert_eq!(json.as_bytes(), out);
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        tree.emit_to_writer(&mut cursor)?;
+        assert_eq!(tree.emit()?.as_bytes(), cursor.get_ref());
+        for src in ["a: !!str 1", "!k a: 1", "a: &x 1\nb: *x"] {
+            assert!(matches!(
+                Tree::parse(src)?.emit_json(),
+                Err(Error::JsonUnsupported(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn int_accessors() -> Result<()> {
+        let tree = Tree::parse("port: 8080\nlevel: 7\nneg: -3\nname: web\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!(8080, root.get("port")?.as_u16()?);
+        assert_eq!(8080, root.get("port")?.as_usize()?);
+        assert_eq!(7, root.get("level")?.as_u8()?);
+        assert_eq!(-3, root.get("neg")?.as_i128()?);
+        assert!(matches!(
+            root.get("port")?.as_u8(),
+            Err(Error::ScalarOutOfRange { target: "u8", .. })
+        ));
+        assert!(matches!(
+            root.get("neg")?.as_u32(),
+            Err(Error::ScalarOutOfRange { target: "u32", .. })
+        ));
+        assert!(matches!(
+            root.get("name")?.as_i64(),
+            Err(Error::InvalidInteger { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn int_accessors_follow_schema() -> Result<()> {
+        let mut tree = Tree::parse(
+            "[0x10, 010, -170141183460469231731687303715884105728, 340282366920938463463374607431768211455, 340282366920938463463374607431768211456]",
+        )?;
+        let root = tree.root_ref()?;
+        assert_eq!(16, root.get(0)?.as_i64()?);
+        assert_eq!(tree.val_i64(1)?, root.get(0)?.as_i64()?);
+        assert_eq!(8, root.get(1)?.as_u8()?);
+        assert_eq!(i128::MIN, root.get(2)?.as_i128()?);
+        assert_eq!(u128::MAX, root.get(3)?.as_u128()?);
+        assert!(matches!(
+            root.get(3)?.as_i128(),
+            Err(Error::ScalarOutOfRange { target: "i128", .. })
+        ));
+        assert!(matches!(
+            root.get(4)?.as_u128(),
+            Err(Error::ScalarOutOfRange { .. })
+        ));
+        assert!(matches!(
+            tree.val_i64(3),
+            Err(Error::ScalarOutOfRange { target: "i64", .. })
+        ));
+        tree.set_schema(YamlSchema::Core);
+        let root = tree.root_ref()?;
+        assert_eq!(10, root.get(1)?.as_i32()?);
+        assert_eq!(10, root.get(1)?.val_i64()?);
+        Ok(())
+    }
+
+    #[test]
+    fn dirty_flag() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: [x]\n")?;
+        assert!(!tree.is_dirty());
+        tree.root_ref()?.get("a")?.val()?;
+        assert!(!tree.is_dirty());
+        tree.root_ref_mut()?.get_mut("a")?.set_val("2")?;
+        assert!(tree.is_dirty());
+        assert!(tree.clone().is_dirty());
+        tree.emit()?;
+        assert!(!tree.is_dirty());
+        tree.root_ref_mut()?.get_mut("b")?.remove_child_at(0)?;
+        assert!(tree.is_dirty());
+        tree.mark_clean();
+        assert!(!tree.is_dirty());
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_seq_len() -> Result<()> {
+        let mut tree = Tree::parse("a: [x]\nb:\nc: 1\nd: {}\n")?;
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut("a")?.ensure_seq_len(3, "y")?;
+        root.get_mut("a")?.ensure_seq_len(1, "z")?;
+        root.get_mut("b")?.ensure_seq_len(1, "")?;
+        root.get_mut("e")?.ensure_seq_len(2, "~")?;
+        assert!(matches!(
+            root.get_mut("c")?.ensure_seq_len(1, ""),
+            Err(Error::NotASeq)
+        ));
+        assert!(matches!(
+            root.get_mut("d")?.ensure_seq_len(1, ""),
+            Err(Error::NotASeq)
+        ));
+        assert_eq!(
+            "a:\n  - x\n  - y\n  - y\nb:\n  - \nc: 1\nd: {}\ne:\n  - ~\n  - ~\n",
+            tree.emit()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn emit_formatter() -> Result<()> {
+        let tree = Tree::parse("a: [1, 'two', 3]\nb: &x c\nd: *x\n")?;
+        let yaml = tree.emit_with_formatter(|node, out| {
+            let val = node.val().ok()?;
+            val.parse::<u8>()
+                .ok()
+                .map(|n| write!(out, "0x{:02x}", n).map_err(|_| Error::NodeNotFound))
+        })?;
+        assert_eq!("a:\n  - 0x01\n  - 'two'\n  - 0x03\nb: &x c\nd: *x\n", yaml);
+        assert!(matches!(
+            tree.emit_with_formatter(|_, _| Some(Err(Error::NotAMap))),
+            Err(Error::NotAMap)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn error_clone() {
+        fn assert_send<T: Send>(_: &T) {}
+        let err = Tree::parse("a: 'b").unwrap_err();
+        let errors = vec![err.clone(), err];
+        assert_send(&errors);
+        assert!(matches!(&errors[0], Error::Parse { msg, .. } if !msg.is_empty()));
+        assert_eq!(errors[0].to_string(), errors[1].to_string());
+    }
+
+    #[test]
+    fn leak() -> Result<()> {
+        let tree = Tree::parse("name: app\nport: 80\n")?.leak();
+        let name: &'static str = tree.val(tree.find_child(0, "name")?)?;
+        let port = tree.root_ref()?.get("port")?.index();
+        let port: &'static str = tree.val(port)?;
+        assert_eq!(("app", "80"), (name, port));
+        Ok(())
+    }
+
+    #[test]
+    fn as_array() -> Result<()> {
+        let tree = Tree::parse("rgba: [1, 0.5, 0.25, 1]\npos: [1, 2]\nbad: [1, x, 3]\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!(
+            [1.0, 0.5, 0.25, 1.0],
+            root.get("rgba")?.as_array::<f32, 4>()?
+        );
+        assert!(matches!(
+            root.get("pos")?.as_array::<i32, 3>(),
+            Err(Error::WrongLength {
+                expected: 3,
+                actual: 2
+            })
+        ));
+        assert!(matches!(
+            root.get("bad")?.as_array::<i32, 3>(),
+            Err(Error::InvalidItem { index: 1, .. })
+        ));
+        assert!(matches!(root.as_array::<i32, 3>(), Err(Error::NotASeq)));
+        Ok(())
+    }
+
+    #[test]
+    fn canonicalize_anchors() -> Result<()> {
+        let mut first = Tree::parse("a: &base 1\nb: &x [*base]\nc: &base 2\nd: *base\ne: *x\n")?;
+        let mut second = Tree::parse("a: &k 1\nb: &a2 [*k]\nc: &k 2\nd: *k\ne: *a2\n")?;
+        first.canonicalize_anchors()?;
+        second.canonicalize_anchors()?;
+        assert_eq!(
+            "a: &a1 1\nb: &a2\n  - *a1\nc: &a3 2\nd: *a3\ne: *a2\n",
+            first.emit()?
+        );
+        assert_eq!(first.emit()?, second.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn setters_copy() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: [x]\n")?;
+        for _ in 0..64 {
+            let key = String::from("key");
+            let val = "val".repeat(16);
+            tree.to_keyval(1, &key, &val)?;
+            tree.set_val_anchor(1, &format!("anchor{}", val.len()))?;
+            tree.set_val_tag(3, &String::from("!t"))?;
+        }
+        assert_eq!(
+            "key: &anchor48 ".to_owned() + &"val".repeat(16),
+            tree.emit()?.lines().next().unwrap()
+        );
+        assert_eq!("!t", tree.val_tag(3)?);
+        Ok(())
+    }
+
+    #[test]
+    fn arena_generation() -> Result<()> {
+        let mut tree = Tree::parse("a: 1")?;
+        tree.reserve_arena(256);
+        let generation = tree.arena_generation();
+        tree.root_ref_mut()?.get_mut("a")?.set_val("2")?;
+        assert_eq!(generation, tree.arena_generation());
+        tree.reserve_arena(tree.arena_capacity() * 2);
+        assert_eq!(generation + 1, tree.arena_generation());
+        assert_eq!("2", tree.val(1)?);
+        tree.reserve_arena(tree.arena_capacity() * 2);
+        assert_eq!("2", tree.val(1)?);
+        if cfg!(debug_assertions) {
+            assert_eq!(generation + 2, tree.arena.get().1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn at_mut() -> Result<()> {
+        let mut tree = Tree::parse("{}")?;
+        let mut root = tree.root_ref_mut()?;
+        root.at_mut("a[0]")?.set_val("x")?;
+        root.at_mut("b.c")?.set_val("y")?;
+        root.at_mut("a[1].d")?.set_val("z")?;
+        root.at_mut_typed(&[("e", ContainerType::Map), ("0", ContainerType::Map)])?
+            .set_val("w")?;
+        assert!(matches!(root.at_mut("b.c.d"), Err(Error::NotAMap)));
+        assert!(matches!(
+            root.at_mut_typed(&[("a", ContainerType::Map), ("k", ContainerType::Map)]),
+            Err(Error::NotAMap)
+        ));
+        assert!(matches!(root.at_mut("a[5]"), Err(Error::NodeNotFound)));
+        assert!(matches!(root.at_mut("a.x"), Err(Error::NotAMap)));
+        assert!(matches!(root.at_mut("b[0]"), Err(Error::NotASeq)));
+        assert!(matches!(root.at_mut("b."), Err(Error::InvalidPath { .. })));
+        assert_eq!("w", root.at_mut("e['0']")?.val()?);
+        assert_eq!(
+            tree.emit()?,
+            "a:\n  - x\n  - d: z\nb:\n  c: y\ne:\n  0: w\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn tree_writer() -> Result<()> {
+        let mut writer = TreeWriter::new();
+        std::io::copy(&mut std::io::Cursor::new(SRC), &mut writer).unwrap();
+        assert_eq!(SRC.len(), writer.len());
+        let tree = writer.finish()?;
+        assert_eq!(Tree::parse(SRC)?.emit()?, tree.emit()?);
+        let mut writer = TreeWriter::new();
+        std::io::Write::write_all(&mut writer, b"a: \xff").unwrap();
+        assert!(matches!(writer.finish(), Err(Error::Utf8(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn remove_self() -> Result<()> {
+        let mut tree = Tree::parse("[1, 2, 3, 4]")?;
+        let mut root = tree.root_ref_mut()?;
+        for node in root.iter_mut()? {
+            if node.as_u8()? % 2 == 0 {
+                node.remove_self()?;
+            }
+        }
+        assert!(matches!(root.remove_self(), Err(Error::RootRemoval)));
+        assert_eq!(tree.emit()?, "- 1\n- 3\n");
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() -> Result<()> {
+        use serde::Serialize;
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize)]
+        struct Port(u16);
+
+        #[derive(Serialize)]
+        enum Mode {
+            Fast,
+            Retry { times: u8 },
+        }
+
+        #[derive(Serialize)]
+        struct Config {
+            name: &'static str,
+            version: &'static str,
+            ports: Vec<Port>,
+            proxy: Option<&'static str>,
+            modes: (Mode, Mode),
+            env: BTreeMap<&'static str, f64>,
+        }
+
+        let config = Config {
+            name: "server",
+            version: "1.0",
+            ports: vec![Port(80), Port(443)],
+            proxy: None,
+            modes: (Mode::Fast, Mode::Retry { times: 3 }),
+            env: [("ratio", 0.5), ("limit", f64::INFINITY)].into(),
+        };
+        assert_eq!(
+            ser::to_string(&config)?,
+            "name: server\nversion: '1.0'\nports:\n  - 80\n  - 443\nproxy: ~\nmodes:\n  - \
+             Fast\n  - Retry:\n      times: 3\nenv:\n  limit: .inf\n  ratio: 0.5\n"
+        );
+        let skip = ser::Serializer { skip_none: true };
+        assert!(!skip.to_string(&config)?.contains("proxy"));
+        assert_eq!(ser::to_string(&[Some(1), None])?, "- 1\n- ~\n");
+        assert!(ser::to_tree(&BTreeMap::from([((1, 2), 3)])).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_indicators() -> Result<()> {
+        use std::collections::BTreeMap;
+
+        for indicator in "|>@`%-?:,[]{}#&*!'\"".chars() {
+            for text in [indicator.to_string(), format!("{}x", indicator)] {
+                let yaml = ser::to_string(&BTreeMap::from([("a", text.as_str())]))?;
+                let back: BTreeMap<String, String> = de::from_str(&yaml)?;
+                assert_eq!(Some(&text), back.get("a"), "{}", yaml);
+                let yaml = ser::to_string(&BTreeMap::from([(text.as_str(), "a")]))?;
+                let back: BTreeMap<String, String> = de::from_str(&yaml)?;
+                assert_eq!(Some("a"), back.get(&text).map(String::as_str), "{}", yaml);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() -> Result<()> {
+        use serde::Deserialize;
+        use std::collections::BTreeMap;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Port(u16);
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Retry { times: u8 },
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(untagged)]
+        enum Any {
+            Int(i64),
+            Str(String),
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Config<'a> {
+            name: &'a str,
+            version: String,
+            ports: Vec<Port>,
+            proxy: Option<String>,
+            modes: (Mode, Mode),
+            env: BTreeMap<String, f64>,
+            any: Vec<Any>,
+        }
+
+        let tree = Tree::parse(
+            "name: server\nversion: 1.0\nports: [80, 443]\nproxy: ~\nmodes:\n  - Fast\n  \
+             - Retry: {times: 3}\nenv: {limit: .inf, ratio: 0.5}\nany: [1, '1', a]",
+        )?;
+        let config: Config = de::from_tree(&tree)?;
+        assert_eq!(
+            config,
+            Config {
+                name: "server",
+                version: "1.0".into(),
+                ports: vec![Port(80), Port(443)],
+                proxy: None,
+                modes: (Mode::Fast, Mode::Retry { times: 3 }),
+                env: [("limit".into(), f64::INFINITY), ("ratio".into(), 0.5)].into(),
+                any: vec![Any::Int(1), Any::Str("1".into()), Any::Str("a".into())],
+            }
+        );
+        assert_eq!(de::from_str::<Vec<u8>>("[1, &a 2, *a]")?, vec![1, 2, 2]);
+        let missing = de::from_str::<Mode>("Retry: {}").unwrap_err();
+        assert_eq!(missing.to_string(), "missing field `times`");
+        assert!(de::from_str::<u8>("300").is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_empty() -> Result<()> {
+        assert_eq!(None, de::from_str::<Option<i32>>("")?);
+        assert_eq!(None, de::from_str::<Option<i32>>("# nothing\n")?);
+        de::from_str::<()>("")?;
+        assert!(de::from_str::<i32>("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn first_difference() -> Result<()> {
+        let tree = Tree::parse("a: {b: [1, 'x']}\nc: &anchor 2")?;
+        let same = Tree::parse("a:\n  b:\n    - 0x1\n    - x\nc: &anchor 2\n")?;
+        assert!(tree.content_eq(&same)?);
+        let other = Tree::parse("a: {b: [1, 'y']}\nc: &anchor 2")?;
+        let difference = tree.first_difference(&other)?.unwrap();
+        assert_eq!(difference.path, "a.b[1]");
+        assert_eq!(difference.to_string(), r#"at a.b[1]: "x" != "y""#);
+        let other = Tree::parse("a: {b: [1, 'x']}\nd: 2")?;
+        assert_eq!(
+            tree.first_difference(&other)?.unwrap().to_string(),
+            r#"at c: "c": &anchor "2" != "d": "2""#
+        );
+        let other = Tree::parse("a: {b: [1]}\nc: &anchor 2")?;
+        assert_eq!(
+            tree.first_difference(&other)?.unwrap().to_string(),
+            r#"at a.b[1]: "x" != nothing"#
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn assert_roundtrips() {
+        testing::assert_roundtrips(SRC);
+        testing::assert_roundtrips_exact("a: 1\nb:\n  - x\n");
+        let exact = std::panic::catch_unwind(|| testing::assert_roundtrips_exact("a:   1\n"));
+        assert!(exact.is_err());
+    }
+
+    #[test]
+    fn emit_line_endings() -> Result<()> {
+        let tree = Tree::parse("a: \"x%\\ny\"\nb: |\n  line 1\n  line 2\nc: [1, 2]\n")?;
+        let mut options = EmitOptions {
+            line_ending: LineEnding::CrLf,
+            ..Default::default()
+        };
+        let yaml = tree.emit_with(&options)?;
+        assert_eq!(yaml, tree.emit()?.replace('\n', "\r\n"));
+        assert!(!yaml.replace("\r\n", "").contains('\n'));
+        options.escape = vec!['%'];
+        let yaml = tree.emit_with(&options)?;
+        assert!(yaml.starts_with("a: \"x\\x25\\ny\"\r\nb: |\r\n  line 1\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn emit_layout() -> Result<()> {
+        let tree =
+            Tree::parse("a:\n  b: 1\n  s:\n    - x: 1\n      y: 2\n    - lit: |\n        text\n")?;
+        let mut options = EmitOptions {
+            indent: 4,
+            ..Default::default()
+        };
+        let yaml = tree.emit_with(&options)?;
+        assert_eq!(
+            "a:\n    b: 1\n    s:\n        -   x: 1\n            y: 2\n        -   lit: |\n                text\n\n",
+            yaml
+        );
+        assert!(Tree::parse(&yaml)?.content_eq(&tree)?);
+        options = EmitOptions {
+            doc_start: true,
+            ..Default::default()
+        };
+        assert_eq!(format!("---\n{}", tree.emit()?), tree.emit_with(&options)?);
+        let mut tree = Tree::parse("a: [alpha, beta]\nb: [alpha, beta, gamma]\n")?;
+        tree.root_ref_mut()?.set_flow_style(true)?;
+        options = EmitOptions {
+            max_flow_width: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            "a: [alpha,beta]\nb:\n  - alpha\n  - beta\n  - gamma\n",
+            tree.emit_with(&options)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn emit_layout_empty() -> Result<()> {
+        let options = EmitOptions {
+            indent: 4,
+            doc_start: true,
+            ..Default::default()
+        };
+        assert_eq!("", Tree::default().emit_with(&options)?);
+        let stream = Tree::parse("--- a\n--- b\n")?;
+        assert_eq!(stream.emit()?, stream.emit_with(&options)?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_indent_round_trip() -> Result<()> {
+        // A literal starting with a space needs an indentation indicator.
+        let tree = Tree::parse("a: |2\n   x\n  y\nb: [c, {d: e}]\n")?;
+        assert_eq!(" x\ny\n", tree.root_ref()?.get("a")?.val()?);
+        for indent in [0, 3, 4, 9, 10, 12, 100] {
+            let yaml = tree.emit_with(&EmitOptions {
+                indent,
+                ..Default::default()
+            })?;
+            let expected = " ".repeat(indent.clamp(2, 9) + 1) + "x\n";
+            assert!(yaml.contains(&expected), "{indent}: {yaml:?}");
+            assert!(Tree::parse(&yaml)?.content_eq(&tree)?, "{indent}: {yaml:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tag_eq() -> Result<()> {
+        let mut tree = Tree::parse("!!str a: !list [1]\nb: !!str 2\nc: !<tag:x.org,2024:y> 3\n")?;
+        let root = tree.root_ref()?;
+        assert!(root.get("a")?.has_val_tag_eq("!list", false));
+        assert!(!root.get("a")?.has_val_tag_eq("!lis", false));
+        assert!(!root
+            .get("a")?
+            .has_key_tag_eq("tag:yaml.org,2002:str", false));
+        assert!(root.get("a")?.has_key_tag_eq("tag:yaml.org,2002:str", true));
+        assert!(root.get("b")?.has_val_tag_eq("!!str", true));
+        assert!(root.get("c")?.has_val_tag_eq("tag:x.org,2024:y", true));
+        assert!(!root.get("a")?.get(0)?.has_val_tag_eq("!list", true));
+        let mut root = tree.root_ref_mut()?;
+        assert!(!root.get_mut("d")?.has_val_tag_eq("!list", false));
+        Ok(())
+    }
+
+    #[test]
+    fn val_as() -> Result<()> {
+        let tree = Tree::parse("[42, -7, 2.5, -.inf, .NaN, YES, Off, maybe, 1.2.3.4]")?;
+        assert_eq!(42, tree.val_i64(1)?);
+        assert_eq!(42, tree.val_u64(1)?);
+        assert!(matches!(
+            tree.val_u64(2),
+            Err(Error::ScalarOutOfRange { target: "u64", .. })
+        ));
+        assert_eq!(2.5, tree.val_f64(3)?);
+        assert_eq!(f64::NEG_INFINITY, tree.val_f64(4)?);
+        assert!(tree.val_f64(5)?.is_nan());
+        assert!(tree.val_bool(6)?);
+        assert!(!tree.val_bool(7)?);
+        assert!(matches!(
+            tree.val_bool(8),
+            Err(Error::ScalarParse { target: "bool", .. })
+        ));
+        let addr: std::net::Ipv4Addr = tree.root_ref()?.get(8)?.val_as()?;
+        assert_eq!(addr, std::net::Ipv4Addr::new(1, 2, 3, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_schema() -> Result<()> {
+        let src = "[no, On, 010, 0o10, 0b11, 1_000, TRUE, .inf]";
+        let mut tree = Tree::parse(src)?;
+        assert_eq!(YamlSchema::V1_1, tree.schema());
+        assert!(!tree.val_bool(1)?);
+        assert!(tree.val_bool(2)?);
+        assert_eq!(8, tree.val_i64(3)?);
+        assert!(tree.val_i64(4).is_err());
+        assert_eq!(3, tree.val_i64(5)?);
+        assert_eq!(1000, tree.val_u64(6)?);
+        tree.set_schema(YamlSchema::Core);
+        assert!(matches!(tree.val_bool(1), Err(Error::ScalarParse { .. })));
+        assert!(tree.val_bool(7)?);
+        assert_eq!(10, tree.val_i64(3)?);
+        assert_eq!(8, tree.val_i64(4)?);
+        assert!(tree.val_i64(6).is_err());
+        tree.set_schema(YamlSchema::Json);
+        assert!(tree.val_bool(7).is_err());
+        assert!(tree.val_i64(3).is_err());
+        assert!(tree.val_f64(8).is_err());
+        let tree = Tree::parse("%YAML 1.2\n---\na: no\n")?;
+        assert_eq!(YamlSchema::V1_2, tree.schema());
+        let options = ParseOptions {
+            schema: Some(YamlSchema::V1_1),
+            ..Default::default()
+        };
+        assert_eq!(
+            YamlSchema::V1_1,
+            Tree::parse_with("%YAML 1.2\n---\na: no\n", &options)?.schema()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let src = "a: 1\nb: [1, 2\n";
+        let error = Tree::parse(src).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Parse {
+                location: Location {
+                    line: 2,
+                    col: 0,
+                    offset: 14,
+                },
+                ..
+            }
+        ));
+        assert_eq!("closing ] not found at line 3, column 1", error.to_string());
+        if let Error::Parse { location, .. } = error {
+            assert_eq!(Location::from_offset(src, location.offset), location);
+        }
+        assert!(matches!(
+            Tree::parse_in_place(src.to_owned()),
+            Err(Error::Parse {
+                location: Location { line: 2, .. },
+                ..
+            })
+        ));
+        assert!(matches!(Tree::validate_yaml(src), Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn count_descendants() -> Result<()> {
+        let tree = Tree::parse("a: {b: [1, 2], c: 3}\nd: [4]\ne: 5\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!(8, root.count_descendants(|_| true)?);
+        let is_seq = |node: &NodeRef<'_, '_, '_, &Tree<'_>>| node.is_seq().unwrap_or(false);
+        assert_eq!(2, root.count_descendants(is_seq)?);
+        assert_eq!(1, root.get("a")?.count_descendants(is_seq)?);
+        assert_eq!(0, root.get("d")?.count_descendants(is_seq)?);
+        assert!(root.any_descendant(|node| node.val().is_ok_and(|val| val == "4"))?);
+        assert!(!root
+            .get("a")?
+            .any_descendant(|node| node.val().is_ok_and(|val| val == "4"))?);
+        assert!(root
+            .get("a")?
+            .get("b")?
+            .all_descendants(|node| node.has_val().unwrap_or(false))?);
+        assert!(!root.all_descendants(|node| node.has_val().unwrap_or(false))?);
+        assert!(root.get("e")?.all_descendants(|_| false)?);
+        Ok(())
+    }
+
+    #[test]
+    fn docs() -> Result<()> {
+        let tree = Tree::parse("--- 1\n--- [2]\n---\nthree: 3\n")?;
+        assert_eq!(3, tree.num_docs()?);
+        let docs: Vec<_> = tree.docs()?.collect();
+        assert_eq!("1", docs[0].val()?);
+        assert_eq!("2", docs[1].get(0)?.val()?);
+        assert_eq!("3", docs[2].get("three")?.val()?);
+        for (src, count) in [("a: 1\n", 1), ("---\na: 1\n", 1), ("x", 1), ("", 0)] {
+            let tree = Tree::parse(src)?;
+            assert_eq!(count, tree.num_docs()?);
+            assert_eq!(count, tree.docs()?.count());
+        }
+        let tree = Tree::parse("a: 1\n")?;
+        assert_eq!("1", tree.docs()?.next().unwrap().get("a")?.val()?);
+        assert_eq!(0, Tree::default().docs()?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn repair() -> Result<()> {
+        let mut tree = Tree::parse("[a, b, c, d]")?;
+        let expected = tree.emit()?;
+        assert!(tree.repair()?.is_clean());
+        assert!(!tree.is_dirty());
+        unsafe {
+            tree.get_mut(3)?.data_unchecked_mut().prev_sibling = NONE;
+            tree.get_mut(2)?.data_unchecked_mut().parent = 7;
+            tree.get_mut(4)?.data_unchecked_mut().next_sibling = 2;
+        }
+        let report = tree.repair()?;
+        assert_eq!(report.parents, [2]);
+        assert_eq!(report.siblings, [3, 4]);
+        assert_eq!(report.broken_chains, [4]);
+        assert!(report.children.is_empty());
+        assert_eq!(expected, tree.emit()?);
+        unsafe {
+            let root = tree.get_mut(0)?.data_unchecked_mut();
+            root.first_child = NONE;
+            tree.get_mut(1)?.data_unchecked_mut().next_sibling = 3;
+        }
+        let report = tree.repair()?;
+        assert_eq!(report.children, [0]);
+        assert_eq!(report.siblings, [1]);
+        assert_eq!(expected, tree.emit()?);
+        assert!(tree.repair()?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn describe_node_data() -> Result<()> {
+        let long = "x".repeat(40);
+        let tree = Tree::parse(format!("--- [a, {{b: {}}}]\n--- c\n", long))?;
+        let describe = |index| tree.get(index).map(|node| node.data().unwrap().describe());
+        assert_eq!("STREAM", describe(0)?);
+        assert_eq!("SEQ", describe(1)?);
+        assert_eq!(r#"VAL val="a""#, describe(2)?);
+        assert_eq!("MAP", describe(3)?);
+        assert_eq!(
+            format!(r#"KEYVAL key="b" val="{}…""#, &long[..32]),
+            describe(4)?
+        );
+        assert_eq!(r#"VAL val="c""#, describe(5)?);
+        Ok(())
+    }
+
+    #[test]
+    fn into_leaves() -> Result<()> {
+        let tree = Tree::parse("a: {b: [1, [2, {c: 3}]], d: {}}\ne: &x 4\nf: *x\n")?;
+        let leaves: Vec<_> = tree.into_leaves()?.collect();
+        let expected = [
+            ("a.b[0]", "1"),
+            ("a.b[1][0]", "2"),
+            ("a.b[1][1].c", "3"),
+            ("e", "4"),
+            ("f", "*x"),
+        ];
+        assert_eq!(leaves.len(), expected.len());
+        for ((path, val), (expected_path, expected_val)) in leaves.iter().zip(expected) {
+            assert_eq!((path.as_str(), val.as_str()), (expected_path, expected_val));
+        }
+        let leaves: Vec<_> = Tree::parse("--- x\n--- [y]\n")?.into_leaves()?.collect();
+        assert_eq!(leaves[1], ("[1][0]".to_owned(), "y".to_owned()));
+        let src = "x.y: {'[0]': [a], \"it's\": b}\n";
+        let tree = Tree::parse(src)?;
+        let leaves: Vec<_> = Tree::parse(src)?.into_leaves()?.collect();
+        assert_eq!(r"['x.y']['[0]'][0]", leaves[0].0);
+        assert_eq!(r"['x.y'].it's", leaves[1].0);
+        for (path, val) in leaves {
+            assert_eq!(val, tree.at(&path)?.val()?, "{}", path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn as_enum() -> Result<()> {
+        #[derive(Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Safe,
+        }
+        impl std::str::FromStr for Mode {
+            type Err = &'static str;
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                match s {
+                    "fast" => Ok(Mode::Fast),
+                    "safe" => Ok(Mode::Safe),
+                    _ => Err("expected fast or safe"),
+                }
+            }
+        }
+        let tree = Tree::parse("modes: [fast, safe, slow]\n")?;
+        let modes = tree.root_ref()?.get("modes")?;
+        assert_eq!(Mode::Fast, modes.get(0)?.as_enum()?);
+        assert_eq!(Mode::Safe, modes.get(1)?.as_enum()?);
+        match modes.get(2)?.as_enum::<Mode>() {
+            Err(Error::ScalarParse {
+                value,
+                path,
+                reason,
+                ..
+            }) => {
+                assert_eq!(("slow", "modes[2]"), (value.as_str(), path.as_str()));
+                assert_eq!(Some("expected fast or safe"), reason.as_deref());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(modes.as_enum::<Mode>(), Err(Error::Other { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn node_ref_eq() -> Result<()> {
+        let tree = Tree::parse("a: [1, 2]\nb: [1, 2]\n")?;
+        let other = tree.clone();
+        let root = tree.root_ref()?;
+        assert!(root.get("a")? == root.get("a")?);
+        assert!(root.get("a")? != root.get("b")?);
+        assert!(root.get("a")? != other.root_ref()?.get("a")?);
+        assert!(root.get("a")?.deep_eq(&other.root_ref()?.get("a")?));
+        assert!(!root.get("a")?.deep_eq(&root.get("b")?));
+        Ok(())
+    }
+
+    #[test]
+    fn subtree_of_root() -> Result<()> {
+        let tree = Tree::parse("a: &x [1]\nb: *x\n")?;
+        let root = tree.root_id()?;
+        assert_eq!(tree.emit()?, tree.extract_subtree(root)?.emit()?);
+        assert_eq!(tree.emit()?, tree.clone_subtree(root)?.emit()?);
+        let stream = Tree::parse("--- a\n--- b\n")?;
+        let root = stream.root_id()?;
+        assert_eq!(stream.emit()?, stream.extract_subtree(root)?.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn extract_subtree() -> Result<()> {
+        let mut buf =
+            String::from("out: &o 1\nsub: {a: !t &x [1], b: *x, c: *o}\nseq: [x, {y: z}]\n");
+        let tree = Tree::parse_in_place(buf.as_mut_str())?;
+        let sub = tree.at("sub")?.extract_subtree()?;
+        let item = tree.at("seq[1]")?.extract_subtree()?;
+        buf.replace_range(.., &"z".repeat(buf.len()));
+        assert_eq!("sub:\n  a: !t &x\n    - 1\n  b: *x\n  c: *o\n", sub.emit()?);
+        assert_eq!("- y: z\n", item.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn clone_subtree() -> Result<()> {
+        let tree = Tree::parse(
+            "out: &o {p: 1}\nsub:\n  a: &x 1\n  b: *x\n  c: *o\n  d: &x 2\n  e: *x\n  f: &o 3\n  g: *o\n",
+        )?;
+        let mut sub = tree.root_ref()?.get("sub")?.clone_subtree()?;
+        assert_eq!(
+            sub.emit()?,
+            "a: &x 1\nb: *x\nc: &o\n  p: 1\nd: &x_2 2\ne: *x_2\nf: &o_2 3\ng: *o_2\n"
+        );
+        sub.resolve()?;
+        assert_eq!(
+            sub.emit()?,
+            "a: 1\nb: 1\nc:\n  p: 1\nd: 2\ne: 2\nf: 3\ng: 3\n"
+        );
+        drop(tree);
+        assert_eq!("1", sub.root_ref()?.get("a")?.val()?);
+
+        let mut buf = String::from("a: {b: [1, two], c: !t x}\nd: 5\n");
+        let tree = Tree::parse_in_place(buf.as_mut_str())?;
+        let (sub, leaf) = (
+            tree.at("a")?.clone_subtree()?,
+            tree.at("d")?.clone_subtree()?,
+        );
+        buf.replace_range(.., &"z".repeat(buf.len()));
+        assert_eq!("b:\n  - 1\n  - two\nc: !t x\n", sub.emit()?);
+        assert_eq!("5\n", leaf.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_node() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: [x, {y: 2}]\n")?;
+        let root = tree.root_ref()?;
+        assert_eq!("a: 1\n", root.get("a")?.emit()?);
+        assert_eq!("b:\n  - x\n  - y: 2\n", root.get("b")?.emit()?);
+        assert_eq!("x\n", root.get("b")?.get(0)?.emit()?);
+        assert_eq!("y: 2\n", root.get("b")?.get(1)?.emit()?);
+        assert!(tree.emit_node(100).is_err());
+        tree.root_ref_mut()?.get_mut("a")?.set_val("2")?;
+        tree.emit_node(1)?;
+        assert!(tree.is_dirty());
+        Ok(())
+    }
+
+    #[test]
+    fn emit_node_empty() -> Result<()> {
+        assert!(matches!(
+            Tree::default().emit_node(0),
+            Err(Error::NodeNotFound)
+        ));
+        assert_eq!("", Tree::parse("")?.emit_node(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn emit_to_vec() -> Result<()> {
+        let tree = Tree::parse("a: [1, 2, 3]\nb: {c: d}\n")?;
+        assert_eq!(tree.emit()?.into_bytes(), tree.emit_to_vec()?);
+        // Scalars parsed in place are not in the arena, so the first attempt
+        // is too small.
+        let mut text = "- a longer value than the estimate allows for\n".repeat(4);
+        let tree = Tree::parse_in_place(&mut text)?;
+        assert_eq!(0, tree.arena_capacity());
+        assert_eq!(tree.emit()?.into_bytes(), tree.emit_to_vec()?);
+        Ok(())
+    }
+
+    #[test]
+    fn display() -> Result<()> {
+        let mut tree = Tree::parse("a: [1, 2]\nb: héllo ✓\n")?;
+        assert_eq!(tree.emit()?, tree.to_string());
+        tree.root_ref_mut()?.get_mut("b")?.set_val("✓ 3")?;
+        assert_eq!("a:\n  - 1\n  - 2\nb: ✓ 3\n", format!("{}", tree));
+        assert!(tree.is_dirty());
+        Ok(())
+    }
+
+    #[test]
+    fn from_str() -> Result<()> {
+        let tree: Tree = String::from("a: 1").parse()?;
+        assert_eq!("1", tree.root_ref()?.get("a")?.val()?);
+        assert!(matches!("a: [".parse::<Tree>(), Err(Error::Parse { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn child() -> Result<()> {
+        let tree = Tree::parse("param_root: {objects: [a, b]}")?;
+        let root = tree.root_ref()?;
+        assert_eq!(
+            "b",
+            root.child("param_root").child("objects").child(1).val()?
+        );
+        let missing = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            root.child("objects").index()
+        }));
+        assert!(missing.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn count_children_by_type() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: &x {c: 2}\nd: [3]\ne: *x\nf:\n")?;
+        let counts = tree.root_ref()?.count_children_by_type()?;
+        assert_eq!(
+            ChildCounts {
+                scalars: 3,
+                maps: 1,
+                seqs: 1
+            },
+            counts
+        );
+        let mut root = tree.root_ref_mut()?;
+        assert_eq!(
+            ChildCounts::default(),
+            root.get_mut("g")?.count_children_by_type()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn escape_non_ascii() -> Result<()> {
+        let tree = Tree::parse("café: naïve\nplain: text\nemoji: \"a😀\"\n")?;
+        let options = EmitOptions {
+            escape_non_ascii: true,
+            ..Default::default()
+        };
+        let yaml = tree.emit_with(&options)?;
+        assert_eq!(
+            "\"caf\\u00e9\": \"na\\u00efve\"\nplain: text\nemoji: \"a\\U0001f600\"\n",
+            yaml
+        );
+        assert!(yaml.is_ascii());
+        assert!(Tree::parse(&yaml)?.content_eq(&tree)?);
+        assert_eq!(
+            "{\"caf\\u00e9\": \"na\\u00efve\",\"plain\": \"text\",\"emoji\": \"a\\ud83d\\ude00\"}",
+            tree.emit_json_with(&options)?
+        );
+        assert_eq!("café: naïve\nplain: text\nemoji: 'a😀'\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_control_chars() -> Result<()> {
+        let src = "a: fine\nb: \"bell\\x07\"\n";
+        let options = ParseOptions {
+            reject_control_chars: true,
+            ..Default::default()
+        };
+        assert!(Tree::parse(src).is_ok());
+        match Tree::parse_with(src, &options) {
+            Err(Error::ControlCharacter { offset, character }) => {
+                assert_eq!(character, '\x07');
+                assert_eq!(offset, 16);
+            }
+            other => panic!("expected a control character error, got {:?}", other),
+        }
+        Tree::parse_with("a: |\n  tab\there\n", &options)?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_defaults() -> Result<()> {
+        let mut tree = Tree::parse("name:\nlist: [a]\n")?;
+        let mut root = tree.root_ref_mut()?;
+        assert!(!root.get_mut("name")?.set_val_if_missing("default")?);
+        assert!(!root.get_mut("list")?.set_val_if_absent("default")?);
+        assert!(root.get_mut("new")?.set_val_if_missing("value")?);
+        assert_eq!("name: \nlist:\n  - a\nnew: value\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn key_diff() -> Result<()> {
+        let old = Tree::parse("a: 1\nb: {c: 2}\nlist: [{x: 1}, {x: 2}]\n")?;
+        let new = Tree::parse("a: 5\nb: {d: 2}\nlist: [{x: 1, y: 0}]\ne: 3\n")?;
+        let (removed, added) = old.key_diff(&new)?;
+        assert_eq!(removed, ["b.c", "list[1]", "list[1].x"]);
+        assert_eq!(added, ["b.d", "list[0].y", "e"]);
+        let (removed, added) = old.key_diff_with(&new, SeqIndices::Ignore)?;
+        assert_eq!(removed, ["b.c"]);
+        assert_eq!(added, ["b.d", "list[].y", "e"]);
+        Ok(())
+    }
+
+    #[test]
+    fn flow_style() -> Result<()> {
+        let mut tree = Tree::parse("a: {b: [0, 1], c: 2}\n")?;
+        tree.root_ref_mut()?.get_mut("a")?.set_flow_style(false)?;
+        assert_eq!("a: {b: [0,1],c: 2}\n", tree.emit()?);
+        tree.root_ref_mut()?.set_block_style(true)?;
+        assert_eq!("a:\n  b:\n    - 0\n    - 1\n  c: 2\n", tree.emit()?);
+        tree.root_ref_mut()?.get_mut("a")?.get_mut("b")?.set_flow_style(true)?;
+        assert_eq!("a:\n  b: [0,1]\n  c: 2\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn siblings() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: 2\nc: 3\n")?;
+        let b = tree.root_ref()?.get("b")?;
+        let keys: Vec<String> = b
+            .siblings()?
+            .map(|node| node.key().unwrap().to_owned())
+            .collect();
+        assert_eq!(keys, ["a", "c"]);
+        assert_eq!(0, tree.root_ref()?.siblings()?.count());
+        let mut root = tree.root_ref_mut()?;
+        let mut c = root.get_mut("c")?;
+        for mut sibling in c.siblings_mut()? {
+            sibling.set_val("0")?;
+        }
+        assert_eq!("a: 0\nb: 0\nc: 3\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn set_val_typed() -> Result<()> {
+        let mut tree = Tree::parse("a: x")?;
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut("a")?.set_val_int(-42)?;
+        root.get_mut("b")?.set_val_float(2.0)?;
+        root.get_mut("c")?.set_val_float(f64::NEG_INFINITY)?;
+        root.get_mut("d")?.set_val_bool(true)?;
+        root.get_mut("e")?
+            .set_val_typed(format_args!("{}-{}", 1, 2))?;
+        root.get_mut("f")?.set_val_typed('z')?;
+        assert_eq!(
+            "a: -42\nb: 2.0\nc: -.inf\nd: true\ne: 1-2\nf: z\n",
+            tree.emit()?
+        );
+        let root = tree.root_ref()?;
+        assert_eq!(-42, root.get("a")?.val_i64()?);
+        assert_eq!(2.0, root.get("b")?.val_f64()?);
+        assert!(root.get("d")?.val_bool()?);
+        Ok(())
+    }
+
+    #[test]
+    fn leading_comment() -> Result<()> {
+        let options = ParseOptions {
+            retain_comments: true,
+            ..Default::default()
+        };
+        let mut tree = Tree::parse_with("# a\na: 1 # one\n# b\n# more\nb: 2\nc: 3\n", &options)?;
+        let root = tree.root_ref()?;
+        assert_eq!(Some("a".into()), root.get("a")?.leading_comment()?);
+        assert_eq!(Some("b\nmore".into()), root.get("b")?.leading_comment()?);
+        assert_eq!(None, root.get("c")?.leading_comment()?);
+        assert_eq!(None, root.leading_comment()?);
+
+        let mut root = tree.root_ref_mut()?;
+        root.get_mut("b")?.set_leading_comment("")?;
+        root.get_mut("c")?.set_leading_comment("c\n\nlast")?;
+        assert!(matches!(
+            root.get_mut("d")?.set_leading_comment("new"),
+            Err(Error::NodeNotFound)
+        ));
+        assert!(matches!(
+            root.set_leading_comment("x"),
+            Err(Error::NodeNotFound)
+        ));
+        let emit = EmitOptions {
+            comments: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "# a\na: 1 # one\nb: 2\n# c\n#\n# last\nc: 3\n",
+            tree.emit_with(&emit)?
+        );
+
+        let mut tree = Tree::parse("a: 1\n")?;
+        assert!(matches!(
+            tree.root_ref_mut()?.get_mut("a")?.set_leading_comment("x"),
+            Err(Error::CommentsNotRetained)
+        ));
+        assert_eq!("a: 1\n", tree.emit()?);
+        Ok(())
+    }
+
+    #[test]
+    fn resolved_tag() -> Result<()> {
+        let tree = Tree::parse(
+            "a: 123\nb: '123'\nc: !!str 123\nd: !!int '7'\ne: ~\nf:\ng: true\nh: -.inf\n\
+             i: !custom 1.5\nj: ! 1\nk: &x [1]\nl: *x\nm: !<tag:yaml.org,2002:bool> x\n\
+             n: yes\no: 0o17\n",
+        )?;
+        let root = tree.root_ref()?;
+        let types = [
+            ("a", YamlType::Int),
+            ("b", YamlType::Str),
+            ("c", YamlType::Str),
+            ("d", YamlType::Int),
+            ("e", YamlType::Null),
+            ("f", YamlType::Null),
+            ("g", YamlType::Bool),
+            ("h", YamlType::Float),
+            ("i", YamlType::Float),
+            ("j", YamlType::Str),
+            ("k", YamlType::Seq),
+            ("l", YamlType::Seq),
+            ("m", YamlType::Bool),
+            ("n", YamlType::Str),
+            ("o", YamlType::Int),
+        ];
+        for (key, expected) in types {
+            assert_eq!(expected, root.get(key)?.resolved_tag()?, "{}", key);
+        }
+        assert_eq!(YamlType::Map, root.resolved_tag()?);
+
+        let mut tree = tree;
+        tree.set_schema(YamlSchema::V1_1);
+        assert_eq!(YamlType::Bool, tree.root_ref()?.get("n")?.resolved_tag()?);
+        assert_eq!(YamlType::Str, tree.root_ref()?.get("o")?.resolved_tag()?);
+        let tree = Tree::parse("%YAML 1.1\n---\nn: yes\n")?;
+        let doc = tree.root_ref()?.get(0)?;
+        assert_eq!(YamlType::Bool, doc.get("n")?.resolved_tag()?);
+        Ok(())
+    }
+
+    #[test]
+    fn walk_find_descendants() -> Result<()> {
+        let tree = Tree::parse("a: {b: [1, 2]}\nc: 3\nd: [4]\n")?;
+        let mut visited = Vec::new();
+        let flow = tree.walk(|tree, node, depth| {
+            let text = if tree.is_container(node).unwrap_or(false) {
+                tree.key(node).unwrap_or("root")
+            } else {
+                tree.val(node).unwrap_or("?")
+            };
+            visited.push(format!("{depth}:{text}"));
+            if text == "3" {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+        assert_eq!(ControlFlow::Break(()), flow);
+        assert_eq!(visited, ["0:root", "1:a", "2:b", "3:1", "3:2", "1:3"]);
+        assert_eq!(
+            ControlFlow::Continue(()),
+            Tree::default().walk(|_, _, _| ControlFlow::Break(()))?
+        );
+
+        let four = tree.find(|tree, node| tree.val(node).ok() == Some("4"));
+        assert_eq!(Some(tree.child_at(tree.find_child(0, "d")?, 0)?), four);
+        assert_eq!(
+            None,
+            tree.find(|tree, node| tree.val(node).ok() == Some("5"))
+        );
+        assert_eq!(Some(0), tree.find(|_, _| true));
+
+        let a = tree.find_child(0, "a")?;
+        let vals: Vec<_> = tree
+            .descendants(a)?
+            .filter_map(|node| tree.val(node).ok())
+            .collect();
+        assert_eq!(vals, ["1", "2"]);
+        assert_eq!(7, tree.descendants(0)?.count());
+        let c = tree.find_child(0, "c")?;
+        assert_eq!(0, tree.descendants(c)?.count());
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_value_aliases() -> Result<()> {
+        let tree = Tree::parse("a: &x [1, *x]\n")?;
+        assert!(matches!(
+            tree.to_json_value(),
+            Err(Error::RecursiveAlias(name)) if name == "x"
+        ));
+        let tree = Tree::parse("a: &x {b: [*x]}\n")?;
+        assert!(matches!(
+            tree.to_json_value(),
+            Err(Error::RecursiveAlias(_))
+        ));
+        // Repeated aliases to the same node are fine.
+        let tree = Tree::parse("a: &x [1]\nb: [*x, *x, {c: *x}]\n")?;
+        assert_eq!(
+            serde_json::json!({"a": [1], "b": [[1], [1], {"c": [1]}]}),
+            tree.to_json_value()?
+        );
+
+        let mut laughs =
+            String::from("l0: &l0 [lol, lol, lol, lol, lol, lol, lol, lol, lol, lol]\n");
+        for level in 1..7 {
+            let prev = format!("*l{}", level - 1);
+            let items = [prev.as_str(); 10].join(", ");
+            laughs.push_str(&format!("l{level}: &l{level} [{items}]\n"));
+        }
+        assert!(matches!(
+            Tree::parse(laughs)?.to_json_value(),
+            Err(Error::AliasExpansionLimit(_))
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_value() -> Result<()> {
+        use serde_json::json;
+
+        let tree = Tree::parse(
+            "a: &x [1, -2, 1.5, '3', !!str 4, !!int '5', !!int abc]\nb: *x\nc: {~: null, true: yes}\n\
+             d: 18446744073709551615\ne: 'x y'\nf:\n",
+        )?;
+        let value = json!({
+            "a": [1, -2, 1.5, "3", "4", 5, "abc"],
+            "b": [1, -2, 1.5, "3", "4", 5, "abc"],
+            "c": {"~": null, "true": "yes"},
+            "d": 18446744073709551615u64,
+            "e": "x y",
+            "f": null,
+        });
+        assert_eq!(value, tree.to_json_value()?);
+        assert_eq!(json!(null), Tree::default().to_json_value()?);
+        assert_eq!(
+            json!([1, {"a": 2}]),
+            Tree::parse("--- 1\n--- {a: 2}\n")?.to_json_value()?
+        );
+        assert!(matches!(
+            Tree::parse("a: .inf")?.to_json_value(),
+            Err(Error::JsonUnsupported(_))
+        ));
+        assert!(matches!(
+            Tree::parse("? [a]\n: b\n")?.to_json_value(),
+            Err(Error::ComplexKey)
+        ));
+
+        let value = json!({"s": ["true", "12", "x"], "n": [null, false, 1, -1.5], "o": {}});
+        let tree = Tree::from_json_value(&value)?;
+        assert_eq!(value, tree.to_json_value()?);
+        assert_eq!(value, Tree::parse(tree.emit()?)?.to_json_value()?);
+        Ok(())
+    }
+
+    #[test]
+    fn count_by_type() -> Result<()> {
+        let tree = Tree::parse("a: &x {b: [1, 2], c: {}}\nd: *x\ne:\nf: !t [3]\n")?;
+        assert_eq!(tree.len(), tree.count_by_type(NodeType::NoType));
+        assert_eq!(3, tree.num_maps());
+        assert_eq!(2, tree.num_seqs());
+        assert_eq!(5, tree.num_scalars());
+        assert_eq!(1, tree.count_by_type(NodeType::Map | NodeType::ValAnch));
+        assert_eq!(1, tree.count_by_type(NodeType::Seq | NodeType::ValTag));
+        assert_eq!(1, tree.count_by_type(NodeType::Key | NodeType::ValRef));
+        assert_eq!(0, tree.count_by_type(NodeType::Map | NodeType::Seq));
+        assert_eq!(0, Tree::default().num_scalars());
+
+        let stream = Tree::parse("--- [1]\n--- 2\n")?;
+        assert_eq!(1, stream.num_seqs());
+        assert_eq!(2, stream.count_by_type(NodeType::Seq));
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_opt() -> Result<()> {
+        let tree = Tree::parse("a: [1, 2]\nb: c\n")?;
+        let a = tree.find_child(0, "a")?;
+        assert_eq!(Some(a), tree.find_child_opt(0, "a")?);
+        assert_eq!(None, tree.find_child_opt(0, "x")?);
+        assert!(matches!(tree.find_child_opt(a, "x"), Err(Error::NotAMap)));
+        assert_eq!(Some(tree.child_at(a, 1)?), tree.child_at_opt(a, 1)?);
+        assert_eq!(None, tree.child_at_opt(a, 2)?);
+        let b = tree.find_child(0, "b")?;
+        assert!(matches!(tree.child_at_opt(b, 0), Err(Error::NotAContainer)));
+        assert_eq!(Some(0), tree.parent_opt(a)?);
+        assert_eq!(None, tree.parent_opt(0)?);
+        assert_eq!(Some(b), tree.next_sibling_opt(a)?);
+        assert_eq!(None, tree.next_sibling_opt(b)?);
+        assert!(tree.parent_opt(100).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn node_at_offset() -> Result<()> {
+        let src = "a: 1\nb:\n  c: [x, yy]\n  d: {e: f}\n";
+        let tree = Tree::parse(src)?;
+        let at = |needle: &str, delta: usize| -> Result<String> {
+            let node = tree
+                .node_at_offset(src.find(needle).unwrap() + delta)
+                .unwrap();
+            Ok(if tree.is_container(node)? && !tree.has_key(node)? {
+                "root".into()
+            } else if tree.has_val(node)? {
+                tree.val(node)?.to_owned()
+            } else {
+                tree.key(node)?.to_owned()
+            })
+        };
+        assert_eq!("1", at("a", 0)?);
+        assert_eq!("1", at("1", 0)?);
+        assert_eq!("b", at("b", 0)?);
+        assert_eq!("c", at("c", 0)?);
+        assert_eq!("yy", at("yy", 1)?);
+        assert_eq!("x", at("x", 1)?);
+        assert_eq!("f", at("e", 0)?);
+        assert_eq!(None, tree.node_at_offset(src.len()));
+
+        let tree = Tree::parse_owned_buf(src.as_bytes().to_vec())?;
+        let node = tree.node_at_offset(src.find("yy").unwrap()).unwrap();
+        assert_eq!("yy", tree.val(node)?);
+
+        let mut buf = src.to_owned();
+        assert_eq!(
+            None,
+            Tree::parse_in_place(buf.as_mut_str())?.node_at_offset(0)
+        );
+        let mut tree = Tree::parse(src)?;
+        tree.root_ref_mut()?.get_mut("a")?.set_val("2")?;
+        tree.emit()?;
+        assert!(!tree.is_dirty());
+        assert_eq!(None, tree.node_at_offset(0));
+
+        let options = ParseOptions {
+            retain_comments: true,
+            ..Default::default()
+        };
+        let src = "# head\na: 1\n# mid\nb: [x, yy]\n";
+        let tree = Tree::parse_with(src, &options)?;
+        let node = tree.node_at_offset(src.find("yy").unwrap()).unwrap();
+        assert_eq!("yy", tree.val(node)?);
+        let node = tree.node_at_offset(src.find("b").unwrap()).unwrap();
+        assert_eq!("b", tree.key(node)?);
+        Ok(())
+    }
+
+    #[test]
+    fn tree_builder() -> Result<()> {
+        let tree = TreeBuilder::seq(|s| {
+            s.push("a")
+                .map(|m| {
+                    m.entry("k").value("v").entry("empty").seq(|_| {});
+                })
+                .seq(|s| {
+                    s.push("1").push("2");
+                });
+        })?;
+        assert_eq!("- a\n- k: v\n  empty: []\n- - 1\n  - 2\n", tree.emit()?);
+        assert_eq!("v", tree.at("[1].k")?.val()?);
+        assert!(tree.at("[1].empty")?.is_seq()?);
+        assert_eq!(tree, Tree::parse(tree.emit()?)?);
+
+        let tree = TreeBuilder::map(|_| {})?;
+        assert!(tree.root_ref()?.is_map()?);
+        assert_eq!(0, tree.root_ref()?.num_children()?);
+        Ok(())
+    }
+
+    #[test]
+    fn sort_keys() -> Result<()> {
+        let mut tree = Tree::parse("c: 1\na: x\nb:\n  - {z: 1, y: 2}\n  - 3\na: y\n")?;
+        tree.sort_keys()?;
+        assert_eq!(
+            "a: x\na: y\nb:\n  - y: 2\n    z: 1\n  - 3\nc: 1\n",
+            tree.emit()?
+        );
+
+        let options = ParseOptions {
+            retain_comments: true,
+            ..Default::default()
+        };
+        let src = "# c\nc: 1 # one\nb: 2\n# a\n# more\na: 3\n# end\n";
+        let tree = Tree::parse_with(src, &options)?;
+        let yaml = tree.emit_with(&EmitOptions {
+            sort_keys: true,
+            comments: true,
+            ..Default::default()
+        })?;
+        assert_eq!("# a\n# more\na: 3\nb: 2\n# c\nc: 1 # one\n# end\n", yaml);
+        assert_eq!(
+            src,
+            tree.emit_with(&EmitOptions {
+                comments: true,
+                ..Default::default()
+            })?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_refs() -> Result<()> {
+        let tree = Tree::parse("a: &a 1\n&k b: *a\nc: [*k, {<<: *a}]\n")?;
+        assert_eq!(Ok(()), tree.check_refs());
+        assert_eq!(Ok(()), Tree::default().check_refs());
+
+        let mut tree = Tree::parse("a: 1\nb: 2\nc: [3, 4]\nd: &x 5\n")?;
+        tree.set_val_ref(1, "x")?;
+        tree.set_val_ref(2, "y")?;
+        tree.set_val_ref(4, "y")?;
+        tree.set_val_ref(5, "x")?;
+        assert_eq!(Err(vec!["x".to_owned(), "y".to_owned()]), tree.check_refs());
+        assert!(Tree::parse(tree.emit()?)?.resolve().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn emit_to_vec_empty() -> Result<()> {
+        assert_eq!(
+            Tree::default().emit()?.into_bytes(),
+            Tree::default().emit_to_vec()?
+        );
+        let mut tree = Tree::parse("a: 1\n")?;
+        tree.clear();
+        assert_eq!(tree.emit()?.into_bytes(), tree.emit_to_vec()?);
+        Ok(())
+    }
+
+    #[test]
+    fn siblings_after() -> Result<()> {
+        let tree = Tree::parse("a: 1\nb: 2\nc: 3\n")?;
+        let root = tree.root_ref()?;
+        let keys = |node: NodeRef<'_, '_, '_, &Tree>| -> Result<Vec<String>> {
+            node.siblings_after()?
+                .map(|node| Ok(node.key()?.to_owned()))
+                .collect()
+        };
+        assert_eq!(keys(root.get("a")?)?, ["b", "c"]);
+        assert_eq!(keys(root.get("b")?)?, ["c"]);
+        assert!(keys(root.get("c")?)?.is_empty());
+        assert_eq!(0, root.siblings_after()?.count());
+        assert_eq!(2, root.get("a")?.siblings_after()?.rev().count());
+        assert_eq!(0, root.get("c")?.siblings_after()?.rev().count());
+        Ok(())
+    }
+
+    #[test]
+    fn complex_key() -> Result<()> {
+        let tree = Tree::parse("? [a, b]\n: value\nc: d\n'[e]': f\n")?;
+        let root = tree.root_id()?;
+        let complex = tree.first_child(root)?;
+        assert!(tree.is_complex_key(complex)?);
+        assert!(matches!(tree.key(complex), Err(Error::ComplexKey)));
+        assert_eq!("value", tree.val(complex)?);
+        let key = tree.root_ref()?.child_at(0)?.key_tree()?;
+        assert!(key.is_seq(key.root_id()?)?);
+        assert_eq!("b", key.val(key.child_at(key.root_id()?, 1)?)?);
+        let quoted = tree.last_child(root)?;
+        assert!(!tree.is_complex_key(quoted)?);
+        assert_eq!("[e]", tree.key(quoted)?);
+        let scalar = tree.key_tree(quoted)?;
+        assert_eq!("[e]", scalar.val(scalar.root_id()?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn index_round_trip() -> Result<()> {
+        let mut tree = Tree::parse("a: 1\nb: [2, 3]\n")?;
+        let index = tree.root_ref()?.get("b")?.get(1)?.index();
+        assert_eq!("3", tree.node_at_index(index)?.val()?);
+        tree.node_at_index_mut(index)?.set_val("4")?;
+        assert_eq!("4", tree.val(index)?);
+        let a = tree.find_child(tree.root_id()?, "a")?;
+        tree.remove(a)?;
+        assert!(tree.node_at_index(a).is_err());
+        assert!(tree.node_at_index(NONE).is_err());
+        Ok(())
+    }
+
     #[test]
     fn node_ref() {
         let mut tree = Tree::parse(SRC).unwrap();
@@ -0,0 +1,106 @@
+use crate::{Error, NodeType, Result, Tree, NONE};
+
+/// How a sequence in the source of an [overlay](crate::Tree::overlay) or
+/// [merge](crate::Tree::merge_from) combines with a sequence in the same
+/// place in the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeqMerge {
+    /// The source sequence replaces the destination sequence.
+    #[default]
+    Replace,
+    /// The source items are appended to the destination sequence.
+    Concat,
+}
+
+impl<'a> Tree<'a> {
+    /// Overlay the entries of a map node from another tree onto a map node of
+    /// this tree. Entries only in the source are appended, and entries present
+    /// in both are overridden by the source: nested maps are overlaid
+    /// recursively, sequences are combined according to `seqs`, and anything
+    /// else is replaced outright.
+    ///
+    /// Fails with [`Error::NotAMap`] if either node is not a map.
+    pub fn overlay(
+        &mut self,
+        node: usize,
+        src: &Tree<'_>,
+        src_node: usize,
+        seqs: SeqMerge,
+    ) -> Result<()> {
+        if !self.node_type(node)?.is_map() || !src.node_type(src_node)?.is_map() {
+            return Err(Error::NotAMap);
+        }
+        let mut child = src.node_data(src_node)?.first_child;
+        while child != NONE {
+            let data = src.node_data(child)?;
+            let existing = self.inner.find_child(node, &data.key.scalar.into())?;
+            if existing == NONE {
+                let after = self.node_data(node)?.last_child;
+                let index = self.duplicate_from_tree(src, child, node, after)?;
+                self.copy_subtree_to_arena(index)?;
+            } else {
+                let dest_type = self.node_type(existing)?;
+                if dest_type.is_map() && data.node_type.is_map() {
+                    self.overlay(existing, src, child, seqs)?;
+                } else if seqs == SeqMerge::Concat && dest_type.is_seq() && data.node_type.is_seq()
+                {
+                    let after = self.node_data(existing)?.last_child;
+                    self.duplicate_children_from_tree(src, child, existing, after)?;
+                    self.copy_subtree_to_arena(existing)?;
+                } else {
+                    let index = self.duplicate_from_tree(src, child, node, existing)?;
+                    self.remove(existing)?;
+                    self.copy_subtree_to_arena(index)?;
+                }
+            }
+            child = data.next_sibling;
+        }
+        Ok(())
+    }
+
+    /// Merge a node from another tree onto a node of this tree, as with
+    /// [`overlay`](#method.overlay) but for any kind of node. If both are
+    /// maps, the source map is overlaid onto the destination. If both are
+    /// sequences and `seqs` is [`SeqMerge::Concat`], the source items are
+    /// appended. Otherwise the destination is replaced by a copy of the
+    /// source, so e.g. a scalar can replace a map. The destination keeps
+    /// its own key, if it has one.
+    ///
+    /// ```rust
+    /// # use ryml::{SeqMerge, Tree};
+    /// let mut base = Tree::parse("db: {host: localhost, port: 5432}\nnames: [a]\n")?;
+    /// let overrides = Tree::parse("db: {port: 6543}\nnames: [b]\n")?;
+    /// base.merge_from(0, &overrides, 0, SeqMerge::Concat)?;
+    /// assert_eq!(
+    ///     "db:\n  host: localhost\n  port: 6543\nnames:\n  - a\n  - b\n",
+    ///     base.emit()?
+    /// );
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn merge_from(
+        &mut self,
+        dest: usize,
+        other: &Tree<'_>,
+        src: usize,
+        seqs: SeqMerge,
+    ) -> Result<()> {
+        let dest_type = self.node_type(dest)?;
+        let src_type = other.node_type(src)?;
+        if dest_type.is_map() && src_type.is_map() {
+            return self.overlay(dest, other, src, seqs);
+        }
+        if seqs == SeqMerge::Concat && dest_type.is_seq() && src_type.is_seq() {
+            let after = self.node_data(dest)?.last_child;
+            self.duplicate_children_from_tree(other, src, dest, after)?;
+            return self.copy_subtree_to_arena(dest);
+        }
+        self.remove_children(dest)?;
+        self.duplicate_contents_from_tree(other, src, dest)?;
+        // The whole type is copied from the source, but the key and the
+        // document the destination belongs to are its own.
+        let kept = NodeType::KEY_FLAGS | NodeType::Doc;
+        let merged_type = self.node_type(dest)?;
+        self.set_flags(dest, (merged_type & !kept) | (dest_type & kept))?;
+        self.copy_subtree_to_arena(dest)
+    }
+}
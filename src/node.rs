@@ -1,5 +1,8 @@
 use super::*;
 use crate::inner::NodeData;
+use crate::path::Segment;
+use crate::schema::expand_tag;
+use std::borrow::Cow;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SeedInner<'k> {
@@ -25,6 +28,78 @@ impl<'k> From<&'k str> for Seed<'k> {
     }
 }
 
+/// The kind of container a path segment is looked up in, for
+/// [`NodeRef::at_mut_typed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerType {
+    /// The segment is a key in a map.
+    Map,
+    /// The segment is an index into a sequence.
+    Seq,
+}
+
+/// The style a scalar value is written in when emitted, from
+/// [`NodeRef::val_style`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalarStyle {
+    /// No style is set, so the emitter picks one which fits the value. This
+    /// is the case for every parsed scalar, since the parser does not record
+    /// how scalars were written.
+    #[default]
+    Auto,
+    /// Written as is, e.g. `value`.
+    Plain,
+    /// Written in single quotes, e.g. `'value'`.
+    SingleQuoted,
+    /// Written in double quotes, e.g. `"value"`.
+    DoubleQuoted,
+    /// Written as a literal block scalar, keeping its line breaks (`|`).
+    Literal,
+    /// Written as a folded block scalar (`>`).
+    Folded,
+}
+
+impl ScalarStyle {
+    /// Every style with its value style flag.
+    const VAL_FLAGS: [(ScalarStyle, NodeType); 5] = [
+        (ScalarStyle::Plain, NodeType::WipValPlain),
+        (ScalarStyle::SingleQuoted, NodeType::WipValSquo),
+        (ScalarStyle::DoubleQuoted, NodeType::WipValDquo),
+        (ScalarStyle::Literal, NodeType::WipValLiteral),
+        (ScalarStyle::Folded, NodeType::WipValFolded),
+    ];
+
+    /// Get the style recorded in the value style flags of a node type. If
+    /// more than one flag is set, the first in declaration order wins.
+    pub(crate) fn of_val(node_type: NodeType) -> Self {
+        Self::VAL_FLAGS
+            .into_iter()
+            .find(|&(_, flag)| (node_type & flag) != NodeType::NoType)
+            .map_or(ScalarStyle::Auto, |(style, _)| style)
+    }
+
+    /// Get the value style flag for the style, or no flag for
+    /// [`Auto`](ScalarStyle::Auto).
+    pub(crate) fn val_flag(self) -> NodeType {
+        Self::VAL_FLAGS
+            .into_iter()
+            .find(|&(style, _)| style == self)
+            .map_or(NodeType::NoType, |(_, flag)| flag)
+    }
+}
+
+/// How many children of each kind a node has, from
+/// [`NodeRef::count_children_by_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChildCounts {
+    /// Children which are not containers, including aliases.
+    pub scalars: usize,
+    /// Children which are maps.
+    pub maps: usize,
+    /// Children which are sequences.
+    pub seqs: usize,
+}
+
 macro_rules! tree_ref_mut {
     ($tree:expr) => {{
         let tree_ref = $tree as *mut Tree<'_>;
@@ -32,58 +107,228 @@ macro_rules! tree_ref_mut {
     }};
 }
 
-/// An iterator over the children of a [`NodeRef`].
+/// An iterator over the children of a [`NodeRef`], from either end.
+///
+/// The iterator follows the sibling links of the tree as it goes rather than
+/// counting positions, so it copes with the children changing while it is in
+/// use: if the child last returned is removed or moved, iteration continues
+/// from the child which followed it (or, if that is gone too, from whatever
+/// now follows the child before it), and children which no longer belong to
+/// the parent are never returned. Iterating from both ends stops once the
+/// two meet.
 pub struct NodeIterator<'a, 't, 'k, T: 't + AsRef<Tree<'a>>> {
     tree: T,
     node_index: usize,
-    index: usize,
-    len: usize,
+    front: Cursor,
+    back: Cursor,
     _hack: PhantomData<(&'a (), &'k (), &'t ())>,
 }
 
+/// The position of one end of a [`NodeIterator`].
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    /// The child last returned from this end, or [`NONE`] before the first.
+    last: usize,
+    /// The children around the last one when it was returned.
+    prev: usize,
+    next: usize,
+}
+
+impl<'a, 't, 'k, T: 't + AsRef<Tree<'a>>> NodeIterator<'a, 't, 'k, T> {
+    pub(crate) fn new(tree: T, node_index: usize, first: usize) -> Self {
+        let last = if first == NONE {
+            NONE
+        } else {
+            tree.as_ref()
+                .node_data(node_index)
+                .map_or(NONE, |parent| parent.last_child)
+        };
+        Self {
+            tree,
+            node_index,
+            front: Cursor {
+                last: NONE,
+                prev: NONE,
+                next: first,
+            },
+            back: Cursor {
+                last: NONE,
+                prev: last,
+                next: NONE,
+            },
+            _hack: PhantomData,
+        }
+    }
+
+    /// Iterate over the children of `node_index` which follow `child`, as if
+    /// `child` had just been returned from the front.
+    fn after(tree: T, node_index: usize, child: usize) -> Self {
+        let mut iter = Self::new(tree, node_index, child);
+        if let Ok(data) = iter.tree.as_ref().node_data(child) {
+            iter.front = Cursor {
+                last: child,
+                prev: data.prev_sibling,
+                next: data.next_sibling,
+            };
+        }
+        iter
+    }
+
+    /// Get the data of the given node if it is still a child of the parent.
+    fn child_data(&self, node: usize) -> Option<&NodeData<'_>> {
+        if node == NONE {
+            return None;
+        }
+        self.tree
+            .as_ref()
+            .node_data(node)
+            .ok()
+            .filter(|data| data.parent == self.node_index)
+    }
+
+    /// Find the child to return next from the front or back, according to the
+    /// current structure.
+    fn peek(&self, back: bool) -> usize {
+        let (cursor, ahead, behind) = if back {
+            (self.back, self.back.prev, self.back.next)
+        } else {
+            (self.front, self.front.next, self.front.prev)
+        };
+        let step = |data: &NodeData<'_>| {
+            if back {
+                data.prev_sibling
+            } else {
+                data.next_sibling
+            }
+        };
+        if let Some(last) = self.child_data(cursor.last) {
+            step(last)
+        } else if self.child_data(ahead).is_some() {
+            ahead
+        } else if let Some(behind) = self.child_data(behind) {
+            step(behind)
+        } else if cursor.last != NONE && behind == NONE {
+            // The last child returned was the first (or last) one.
+            self.tree
+                .as_ref()
+                .node_data(self.node_index)
+                .map_or(NONE, |parent| {
+                    if back {
+                        parent.last_child
+                    } else {
+                        parent.first_child
+                    }
+                })
+        } else {
+            NONE
+        }
+    }
+
+    fn advance(&mut self, back: bool) -> Option<usize> {
+        let current = self.peek(back);
+        let other = if back {
+            self.front.last
+        } else {
+            self.back.last
+        };
+        if current == other {
+            return None;
+        }
+        let data = self.child_data(current)?;
+        let cursor = Cursor {
+            last: current,
+            prev: data.prev_sibling,
+            next: data.next_sibling,
+        };
+        if back {
+            self.back = cursor;
+        } else {
+            self.front = cursor;
+        }
+        Some(current)
+    }
+
+    fn remaining(&self) -> usize {
+        let mut count = 0;
+        let mut node = self.peek(false);
+        while node != self.back.last {
+            let Some(data) = self.child_data(node) else {
+                break;
+            };
+            count += 1;
+            node = data.next_sibling;
+        }
+        count
+    }
+}
+
 impl<'a, 't, 'k> Iterator for NodeIterator<'a, 't, 'k, &'t Tree<'a>> {
     type Item = NodeRef<'a, 't, 'k, &'t Tree<'a>>;
 
+    #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.len {
-            None
-        } else {
-            let index = self.tree.child_at(self.node_index, self.index).ok()?;
-            let node = NodeRef::new_exists(self.tree, index);
-            self.index += 1;
-            Some(node)
-        }
+        let index = self.advance(false)?;
+        Some(NodeRef::new_exists(self.tree, index))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, 't, 'k> DoubleEndedIterator for NodeIterator<'a, 't, 'k, &'t Tree<'a>> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = self.advance(true)?;
+        Some(NodeRef::new_exists(self.tree, index))
     }
 }
 
 impl<'a, 't, 'k> Iterator for NodeIterator<'a, 't, 'k, &'t mut Tree<'a>> {
     type Item = NodeRef<'a, 't, 'k, &'t mut Tree<'a>>;
 
+    #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.len {
-            None
-        } else {
-            let index = self.tree.child_at(self.node_index, self.index).ok()?;
-            let node = NodeRef::new_exists(tree_ref_mut!(self.tree), index);
-            self.index += 1;
-            Some(node)
-        }
+        let index = self.advance(false)?;
+        Some(NodeRef::new_exists(tree_ref_mut!(self.tree), index))
     }
-}
 
-impl<'a, 't, 'k> ExactSizeIterator for NodeIterator<'a, 't, 'k, &'t Tree<'a>> {
-    fn len(&self) -> usize {
-        self.len
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
     }
 }
 
-impl<'a, 't, 'k> ExactSizeIterator for NodeIterator<'a, 't, 'k, &'t mut Tree<'a>> {
-    fn len(&self) -> usize {
-        self.len
+impl<'a, 't, 'k> DoubleEndedIterator for NodeIterator<'a, 't, 'k, &'t mut Tree<'a>> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = self.advance(true)?;
+        Some(NodeRef::new_exists(tree_ref_mut!(self.tree), index))
     }
 }
 
+impl<'a, 't, 'k> ExactSizeIterator for NodeIterator<'a, 't, 'k, &'t Tree<'a>> {}
+
+impl<'a, 't, 'k> ExactSizeIterator for NodeIterator<'a, 't, 'k, &'t mut Tree<'a>> {}
+
 /// A reference to a node in the tree.
+///
+/// # Allocation
+///
+/// A `NodeRef` is just a reference to the tree, a node index and a
+/// borrowed lookup [seed](#method.is_seed), so creating one never
+/// allocates. Neither do the navigation methods (such as
+/// [`get`](#method.get), [`child`](#method.child),
+/// [`parent`](#method.parent), the sibling and child accessors and the
+/// [`iter`](#method.iter) and [`siblings`](#method.siblings) iterators) or
+/// the accessors which borrow from the tree, like [`key`](#method.key),
+/// [`val`](#method.val) and [`data`](#method.data). This holds for their
+/// errors too, except for [`Error::Other`], which carries a message from
+/// rapidyaml. Methods which return owned data, like [`emit`](#method.emit),
+/// allocate as needed.
 #[derive(Debug, Clone)]
 pub struct NodeRef<'a, 't, 'k, T>
 where
@@ -96,12 +341,31 @@ where
     _hack: PhantomData<(&'t (), &'a ())>,
 }
 
+/// Node references are equal if they refer to the same node of the same
+/// tree. Use [`NodeRef::deep_eq`] to compare their content.
 impl<'a, 't, T: AsRef<Tree<'a>> + 't> PartialEq for NodeRef<'a, 't, '_, T> {
     fn eq(&self, other: &Self) -> bool {
-        self.tree.as_ref() == other.tree.as_ref() && self.index == other.index
+        std::ptr::eq(self.tree.as_ref(), other.tree.as_ref()) && self.index == other.index
     }
 }
 
+macro_rules! int_accessors {
+    ($($name:ident: $int:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Get the node value as an `", stringify!($int), "`, if it exists.")]
+            ///
+            /// The value is read following the tree's
+            /// [schema](Tree::schema), as with [`Tree::val_i64`]. Fails with
+            /// [`Error::InvalidInteger`] if the value is not an integer, or
+            /// [`Error::ScalarOutOfRange`] if it does not fit the type.
+            #[inline(always)]
+            pub fn $name(&self) -> Result<$int> {
+                self.val_as_int(stringify!($int))
+            }
+        )*
+    };
+}
+
 macro_rules! tree_ref {
     ($tree:expr) => {{
         let tree_ref = $tree.as_ref() as *const Tree<'_>;
@@ -114,6 +378,7 @@ where
     T: AsRef<Tree<'a>> + 't,
     'a: 't,
 {
+    #[inline(always)]
     pub(crate) fn new_exists<'na>(tree: T, index: usize) -> NodeRef<'a, 't, 'na, T> {
         NodeRef {
             tree,
@@ -123,6 +388,17 @@ where
         }
     }
 
+    /// Get the raw index of the node in the tree, which can be turned back
+    /// into a [`NodeRef`] with [`Tree::node_at_index`]. For a node which does
+    /// not exist yet, this is the index of its parent.
+    ///
+    /// **Note**: Indices are invalidated by [`Tree::reorder`] and may be
+    /// reused after a node is removed.
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     /// Get the tree the node belongs to.
     #[inline(always)]
     pub fn tree<'r>(&'r self) -> &'t Tree<'a> {
@@ -189,6 +465,48 @@ where
         self.tree.as_ref().key(self.index)
     }
 
+    /// Check if the node has a complex (non-scalar) key. See
+    /// [`Tree::is_complex_key`] for the limitations.
+    #[inline(always)]
+    pub fn is_complex_key(&self) -> Result<bool> {
+        self.tree.as_ref().is_complex_key(self.index)
+    }
+
+    /// Get the node key as a standalone tree, which is the only way to read a
+    /// [complex key](Tree::is_complex_key).
+    #[inline(always)]
+    pub fn key_tree(&self) -> Result<Tree<'static>> {
+        self.tree.as_ref().key_tree(self.index)
+    }
+
+    /// Check if the node and its descendants have the same content as
+    /// another node, possibly from another tree, as compared by
+    /// [`Tree::deep_eq`]. Keys of the nodes themselves are compared too.
+    pub fn deep_eq<'b, U: AsRef<Tree<'b>>>(&self, other: &NodeRef<'b, '_, '_, U>) -> bool {
+        tree_ref!(self.tree).node_deep_eq(self.index, other.tree.as_ref(), other.index)
+    }
+
+    /// Emit the node and its descendants as YAML to an owned string. See
+    /// [`Tree::emit_node`].
+    #[inline(always)]
+    pub fn emit(&self) -> Result<String> {
+        self.tree.as_ref().emit_node(self.index)
+    }
+
+    /// Copy the node and its descendants into a new standalone tree, keeping
+    /// the anchors and aliases within it. See [`Tree::clone_subtree`].
+    #[inline(always)]
+    pub fn clone_subtree(&self) -> Result<Tree<'static>> {
+        self.tree.as_ref().clone_subtree(self.index)
+    }
+
+    /// Copy the node and its descendants, exactly as they are, into a new
+    /// standalone tree. See [`Tree::extract_subtree`].
+    #[inline(always)]
+    pub fn extract_subtree(&self) -> Result<Tree<'static>> {
+        self.tree.as_ref().extract_subtree(self.index)
+    }
+
     /// Get the tag on the node key, if it exists.
     #[inline(always)]
     pub fn key_tag(&self) -> Result<&str> {
@@ -219,6 +537,204 @@ where
         self.tree.as_ref().val(self.index)
     }
 
+    /// Get the length in bytes of the node key, without reading the key
+    /// itself. See [`Tree::key_len`].
+    #[inline(always)]
+    pub fn key_len(&self) -> Result<usize> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.as_ref().key_len(self.index)
+    }
+
+    /// Get the length in bytes of the node value, without reading the value
+    /// itself. See [`Tree::val_len`].
+    #[inline(always)]
+    pub fn val_len(&self) -> Result<usize> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.as_ref().val_len(self.index)
+    }
+
+    /// Parse the node value, if it exists, as any type implementing
+    /// [`FromStr`](std::str::FromStr). Fails with [`Error::ScalarParse`] if
+    /// the value does not parse.
+    #[inline(always)]
+    pub fn val_as<I: std::str::FromStr>(&self) -> Result<I> {
+        self.tree.as_ref().val_as(self.index)
+    }
+
+    /// Parse the node value, if it exists, into an enum (or any other type
+    /// implementing [`FromStr`](std::str::FromStr)), e.g. one deriving
+    /// `EnumString` with `strum`. Fails with [`Error::ScalarParse`], holding
+    /// the value, the path to the node and the parse error, if the value does
+    /// not parse.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// #[derive(Debug, PartialEq)]
+    /// enum Level {
+    ///     Debug,
+    ///     Info,
+    /// }
+    ///
+    /// impl std::str::FromStr for Level {
+    ///     type Err = String;
+    ///
+    ///     fn from_str(s: &str) -> Result<Self, Self::Err> {
+    ///         match s {
+    ///             "debug" => Ok(Level::Debug),
+    ///             "info" => Ok(Level::Info),
+    ///             _ => Err(format!("unknown level {}", s)),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let tree = ryml::Tree::parse("log: {level: debug, file: trace}")?;
+    /// let log = tree.root_ref()?.get("log")?;
+    /// assert_eq!(log.get("level")?.as_enum::<Level>()?, Level::Debug);
+    /// match log.get("file")?.as_enum::<Level>() {
+    ///     Err(ryml::Error::ScalarParse { path, reason, .. }) => {
+    ///         assert_eq!(path, "log.file");
+    ///         assert_eq!(reason.as_deref(), Some("unknown level trace"));
+    ///     }
+    ///     other => panic!("unexpected {:?}", other),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn as_enum<E>(&self) -> Result<E>
+    where
+        E: std::str::FromStr,
+        E::Err: std::fmt::Display,
+    {
+        self.tree.as_ref().val_as_enum(self.index)
+    }
+
+    /// Get the comment written on the lines before the node, as with
+    /// [`Tree::leading_comment`].
+    #[inline(always)]
+    pub fn leading_comment(&self) -> Result<Option<String>> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.as_ref().leading_comment(self.index)
+    }
+
+    /// Resolve the type of the node, as with [`Tree::resolved_tag`].
+    #[inline(always)]
+    pub fn resolved_tag(&self) -> Result<YamlType> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.as_ref().resolved_tag(self.index)
+    }
+
+    /// Parse the node value, if it exists, as an `i64`. The same as
+    /// [`as_i64`](#method.as_i64).
+    #[inline(always)]
+    pub fn val_i64(&self) -> Result<i64> {
+        self.as_i64()
+    }
+
+    /// Parse the node value, if it exists, as a `u64`. The same as
+    /// [`as_u64`](#method.as_u64).
+    #[inline(always)]
+    pub fn val_u64(&self) -> Result<u64> {
+        self.as_u64()
+    }
+
+    /// Parse the node value, if it exists, as an `f64`, as with
+    /// [`Tree::val_f64`].
+    #[inline(always)]
+    pub fn val_f64(&self) -> Result<f64> {
+        self.tree.as_ref().val_f64(self.index)
+    }
+
+    /// Parse the node value, if it exists, as a YAML 1.1 boolean, as with
+    /// [`Tree::val_bool`].
+    #[inline(always)]
+    pub fn val_bool(&self) -> Result<bool> {
+        self.tree.as_ref().val_bool(self.index)
+    }
+
+    /// Get the raw bytes of the node value, if it exists, without assuming
+    /// they are valid UTF-8.
+    #[inline(always)]
+    pub fn val_bytes(&self) -> Result<&[u8]> {
+        self.tree.as_ref().val_bytes(self.index)
+    }
+
+    /// Get the node value as a filesystem path, if it exists. The path is
+    /// made from the [raw bytes](#method.val_bytes) of the value, so paths
+    /// which are not valid UTF-8 are kept intact.
+    #[cfg(unix)]
+    #[inline(always)]
+    pub fn as_path(&self) -> Result<&std::path::Path> {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(std::path::Path::new(std::ffi::OsStr::from_bytes(
+            self.val_bytes()?,
+        )))
+    }
+
+    /// Parse the items of a sequence node into a fixed-size array, e.g. an
+    /// RGBA color as `[f32; 4]`.
+    ///
+    /// Fails with [`Error::NotASeq`] if the node is not a sequence,
+    /// [`Error::WrongLength`] if it does not have exactly `N` items, or
+    /// [`Error::InvalidItem`] for the first item which cannot be parsed.
+    pub fn as_array<I: std::str::FromStr, const N: usize>(&self) -> Result<[I; N]> {
+        let tree = self.tree.as_ref();
+        if self.is_seed() || !tree.is_seq(self.index)? {
+            return Err(Error::NotASeq);
+        }
+        let actual = tree.num_children(self.index)?;
+        if actual != N {
+            return Err(Error::WrongLength {
+                expected: N,
+                actual,
+            });
+        }
+        let mut items: [Option<I>; N] = [(); N].map(|_| None);
+        let mut child = tree.node_data(self.index)?.first_child;
+        for (index, item) in items.iter_mut().enumerate() {
+            let value = tree.val(child)?;
+            *item = Some(value.parse().map_err(|_| Error::InvalidItem {
+                index,
+                value: value.to_owned(),
+            })?);
+            child = tree.node_data(child)?.next_sibling;
+        }
+        Ok(items.map(|item| item.expect("every item was parsed")))
+    }
+
+    int_accessors! {
+        as_i8: i8,
+        as_i16: i16,
+        as_i32: i32,
+        as_i64: i64,
+        as_i128: i128,
+        as_isize: isize,
+        as_u8: u8,
+        as_u16: u16,
+        as_u32: u32,
+        as_u64: u64,
+        as_u128: u128,
+        as_usize: usize,
+    }
+
+    fn val_as_int<I>(&self, target: &'static str) -> Result<I>
+    where
+        I: TryFrom<i128> + TryFrom<u128>,
+    {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.as_ref().val_int(self.index, target)
+    }
+
     /// Get the tag on the node value, if it exists.
     #[inline(always)]
     pub fn val_tag(&self) -> Result<&str> {
@@ -309,6 +825,45 @@ where
         self.tree.as_ref().has_val_tag(self.index)
     }
 
+    /// Check if the node key has exactly the given tag, e.g. `"!list"`. Any
+    /// error, including the node or its tag not existing, counts as no
+    /// match.
+    ///
+    /// With `resolve`, both tags are first expanded, so that the shorthand
+    /// `!!str` matches `tag:yaml.org,2002:str` and `!<tag:example.com,2024:x>`
+    /// matches `tag:example.com,2024:x`.
+    #[inline(always)]
+    pub fn has_key_tag_eq(&self, tag: &str, resolve: bool) -> bool {
+        self.seed.0 == SeedInner::None
+            && matches!(self.has_key_tag(), Ok(true))
+            && self
+                .key_tag()
+                .is_ok_and(|actual| tag_eq(actual, tag, resolve))
+    }
+
+    /// Check if the node value has exactly the given tag, e.g. `"!list"`.
+    /// Any error, including the node or its tag not existing, counts as no
+    /// match. See [`has_key_tag_eq`](#method.has_key_tag_eq) for `resolve`.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("a: !list [1]\nb: !!str 2\n")?;
+    /// let root = tree.root_ref()?;
+    /// assert!(root.get("a")?.has_val_tag_eq("!list", false));
+    /// assert!(root.get("b")?.has_val_tag_eq("tag:yaml.org,2002:str", true));
+    /// assert!(!root.get("a")?.get(0)?.has_val_tag_eq("!list", false));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn has_val_tag_eq(&self, tag: &str, resolve: bool) -> bool {
+        self.seed.0 == SeedInner::None
+            && matches!(self.has_val_tag(), Ok(true))
+            && self
+                .val_tag()
+                .is_ok_and(|actual| tag_eq(actual, tag, resolve))
+    }
+
     /// Check if the node has a key anchor
     #[inline(always)]
     pub fn has_key_anchor(&self) -> Result<bool> {
@@ -375,6 +930,13 @@ where
         self.tree.as_ref().is_quoted(self.index)
     }
 
+    /// Get the style the node value is written in when emitted. See
+    /// [`Tree::val_style`].
+    #[inline(always)]
+    pub fn val_style(&self) -> Result<ScalarStyle> {
+        self.tree.as_ref().val_style(self.index)
+    }
+
     /// Check if the parent is a sequence
     #[inline(always)]
     pub fn parent_is_seq(&self) -> Result<bool> {
@@ -394,6 +956,44 @@ where
         self.tree.as_ref().is_node_empty(self.index)
     }
 
+    /// Returns true if the node has no children, or does not exist yet.
+    ///
+    /// Unlike [`is_empty`](#method.is_empty), this ignores the key and value
+    /// and only reads the node's cached link to its first child, so it is
+    /// O(1) even for huge containers.
+    #[inline(always)]
+    pub fn is_empty_fast(&self) -> Result<bool> {
+        if self.is_seed() {
+            return Ok(true);
+        }
+        Ok(!self.tree.as_ref().has_children(self.index)?)
+    }
+
+    /// Count the children of this node by kind, e.g. to tell the settings of
+    /// a map from its subsections. Each child's type is read straight from
+    /// the tree's node data, in a single pass. A node which does not exist
+    /// yet has no children.
+    pub fn count_children_by_type(&self) -> Result<ChildCounts> {
+        let mut counts = ChildCounts::default();
+        if self.is_seed() {
+            return Ok(counts);
+        }
+        let tree = self.tree.as_ref();
+        let mut child = tree.node_data(self.index)?.first_child;
+        while child != NONE {
+            let data = tree.node_data(child)?;
+            if data.node_type.is_map() {
+                counts.maps += 1;
+            } else if data.node_type.is_seq() {
+                counts.seqs += 1;
+            } else {
+                counts.scalars += 1;
+            }
+            child = data.next_sibling;
+        }
+        Ok(counts)
+    }
+
     /// Returns true if the node is the root of the tree.
     #[inline(always)]
     pub fn is_root(&self) -> Result<bool> {
@@ -406,13 +1006,30 @@ where
         self.tree.as_ref().has_parent(self.index)
     }
 
+    /// Check if the value of this node means the same as the value of another
+    /// node, which may belong to a different tree, ignoring how each is
+    /// written. See [`Tree::val_eq_normalized`] for the normalization rules.
+    #[inline(always)]
+    pub fn val_eq_normalized<'oa, 'ot, U>(&self, other: &NodeRef<'oa, 'ot, '_, U>) -> Result<bool>
+    where
+        U: AsRef<Tree<'oa>> + 'ot,
+        'oa: 'ot,
+    {
+        if self.is_seed() || other.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree
+            .as_ref()
+            .val_eq_normalized(self.index, other.tree(), other.index)
+    }
+
     /// Returns true if the node is a map and has a child with the given key.
     #[inline(always)]
     pub fn has_child(&self, key: &str) -> Result<bool> {
         match self.tree.as_ref().find_child(self.index, key) {
             Ok(_) => Ok(true),
             Err(e) => match e {
-                Error::NodeNotFound => Ok(false),
+                Error::NodeNotFound | Error::NotAMap => Ok(false),
                 _ => Err(e),
             },
         }
@@ -480,36 +1097,21 @@ where
     #[inline(always)]
     pub fn parent<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let parent = self.tree.as_ref().parent(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: parent,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), parent))
     }
 
     /// Returns a [`NodeRef`] to the previous sibling, if it exists.
     #[inline(always)]
     pub fn prev_sibling<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().prev_sibling(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Returns a [`NodeRef`] to the next sibling, if it exists.
     #[inline(always)]
     pub fn next_sibling<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().next_sibling(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Get the number of children of the node.
@@ -528,24 +1130,14 @@ where
     #[inline(always)]
     pub fn first_child<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let child = self.tree.as_ref().first_child(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: child,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), child))
     }
 
     /// Get a [`NodeRef`] to the last child of this node, if it exists.
     #[inline(always)]
     pub fn last_child<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let child = self.tree.as_ref().last_child(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: child,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), child))
     }
 
     /// Get a [`NodeRef`] to the child of this node at the given position, if
@@ -553,12 +1145,7 @@ where
     #[inline(always)]
     pub fn child_at<'r>(&'r self, pos: usize) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let child = self.tree.as_ref().child_at(self.index, pos)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: child,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), child))
     }
 
     /// Get a [`NodeRef`] to the child of this node with the given key, if it
@@ -566,36 +1153,21 @@ where
     #[inline(always)]
     pub fn find_child<'r>(&'r self, key: &str) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let child = self.tree.as_ref().find_child(self.index, key)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: child,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), child))
     }
 
     /// Get a [`NodeRef`] to the first sibling of this node, if it exists.
     #[inline(always)]
     pub fn first_sibling<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().first_sibling(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Get a [`NodeRef`] to the last sibling of this node, if it exists.
     #[inline(always)]
     pub fn last_sibling<'r>(&'r self) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().last_sibling(self.index)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Get a [`NodeRef`] to the sibling of this node at the given position, if
@@ -603,12 +1175,7 @@ where
     #[inline(always)]
     pub fn sibling_at<'r>(&'r self, pos: usize) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().sibling_at(self.index, pos)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Get a [`NodeRef`] to the sibling of this node with the given key, if it
@@ -616,12 +1183,7 @@ where
     #[inline(always)]
     pub fn find_sibling<'r>(&'r self, key: &str) -> Result<NodeRef<'a, 't, '_, &'t Tree<'a>>> {
         let sibling = self.tree.as_ref().find_sibling(self.index, key)?;
-        Ok(NodeRef {
-            tree: tree_ref!(self.tree),
-            index: sibling,
-            seed: Seed(SeedInner::None),
-            _hack: PhantomData,
-        })
+        Ok(NodeRef::new_exists(tree_ref!(self.tree), sibling))
     }
 
     /// Get a [`NodeRef`] to a child of this node by its given key (if this node
@@ -630,6 +1192,7 @@ where
     /// Unlike [`get_mut`](#method.get_mut), this method will return a
     /// `NodeNotFound` error if the child node does not exist. It will also
     /// return this error if the current node does not exist.
+    #[inline(always)]
     pub fn get<'r, 'k2, S: Into<Seed<'k2>>>(
         &'r self,
         lookup: S,
@@ -639,40 +1202,195 @@ where
         }
         let seed = lookup.into();
         match seed.0 {
-            SeedInner::Index(child_pos) => Ok(NodeRef {
-                tree: tree_ref!(self.tree),
-                index: self.tree.as_ref().child_at(self.index, child_pos)?,
-                seed: Seed(SeedInner::None),
-                _hack: PhantomData,
-            }),
-            SeedInner::Key(child_key) => Ok(NodeRef {
-                tree: tree_ref!(self.tree),
-                index: self.tree.as_ref().find_child(self.index, child_key)?,
-                seed: Seed(SeedInner::None),
-                _hack: PhantomData,
-            }),
+            SeedInner::Index(child_pos) => Ok(NodeRef::new_exists(
+                tree_ref!(self.tree),
+                self.tree.as_ref().child_at(self.index, child_pos)?,
+            )),
+            SeedInner::Key(child_key) => Ok(NodeRef::new_exists(
+                tree_ref!(self.tree),
+                self.tree.as_ref().find_child(self.index, child_key)?,
+            )),
             // This is unreachable because the public API does not expose any methods to pass a
             // `Seed` set to `SeedInner::None`.
             _ => unsafe { core::hint::unreachable_unchecked() },
         }
     }
 
+    /// Get a [`NodeRef`] to a child of this node by key or position, as with
+    /// [`get`](#method.get), for terse read-only traversal such as
+    /// `node.child("param_root").child("objects")`.
+    ///
+    /// This stands in for indexing with `node["param_root"]`, which is not
+    /// possible since [`Index`](std::ops::Index) has to return a reference and
+    /// node references are created on demand rather than stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the child does not exist, or if this node is not a container.
+    /// Use [`get`](#method.get) to handle these cases instead.
+    #[inline(always)]
+    #[track_caller]
+    pub fn child<'k2, S: Into<Seed<'k2>> + std::fmt::Debug + Copy>(
+        &self,
+        lookup: S,
+    ) -> NodeRef<'a, 't, 'k2, &'t Tree<'a>> {
+        match self.get(lookup) {
+            Ok(node) => node,
+            Err(e) => panic!("no child {:?}: {}", lookup, e),
+        }
+    }
+
+    /// Iterate over the siblings of this node (the other children of its
+    /// parent) in document order, skipping the node itself. The root node has
+    /// no siblings.
+    #[inline(always)]
+    pub fn siblings<'r>(
+        &'r self,
+    ) -> Result<impl Iterator<Item = NodeRef<'a, 't, 'r, &'t Tree<'a>>>> {
+        if self.seed.0 != SeedInner::None {
+            return Err(Error::NodeNotFound);
+        }
+        let tree = tree_ref!(self.tree);
+        let index = self.index;
+        let (node_index, first) = match tree.parent(index) {
+            Ok(parent) => (parent, tree.node_data(parent)?.first_child),
+            Err(Error::NodeNotFound) => (index, NONE),
+            Err(e) => return Err(e),
+        };
+        Ok(NodeIterator::new(tree, node_index, first).filter(move |node| node.index != index))
+    }
+
+    /// Iterate over the siblings which follow this node in document order,
+    /// not including the node itself. The root node has no siblings.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("[a, b, c, d]")?;
+    /// let b = tree.root_ref()?.get(1)?;
+    /// let mut after = b.siblings_after()?;
+    /// assert_eq!("c", after.next().unwrap().val()?);
+    /// assert_eq!("d", after.next().unwrap().val()?);
+    /// assert!(after.next().is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn siblings_after<'r>(&'r self) -> Result<NodeIterator<'a, 't, 'r, &'t Tree<'a>>> {
+        if self.seed.0 != SeedInner::None {
+            return Err(Error::NodeNotFound);
+        }
+        let tree = tree_ref!(self.tree);
+        let parent = tree.node_data(self.index)?.parent;
+        if parent == NONE {
+            return Ok(NodeIterator::new(tree, self.index, NONE));
+        }
+        Ok(NodeIterator::after(tree, parent, self.index))
+    }
+
     /// Iterate over the children of this node, if it exists and is valid.
     #[inline(always)]
     pub fn iter(&self) -> Result<NodeIterator<'a, 't, '_, &'t Tree<'a>>> {
         if self.seed.0 != SeedInner::None {
             return Err(Error::NodeNotFound);
         }
-        Ok(NodeIterator {
-            tree: tree_ref!(self.tree),
-            node_index: self.index,
-            index: 0,
-            len: self.num_children()?,
-            _hack: PhantomData,
-        })
+        let first = self.tree.as_ref().node_data(self.index)?.first_child;
+        Ok(NodeIterator::new(tree_ref!(self.tree), self.index, first))
+    }
+
+    /// Iterate over the entries of a map node as `(key, child)` pairs.
+    ///
+    /// Fails with [`Error::NotAMap`] if the node is not a map, or with
+    /// [`Error::ComplexKey`] if any of its keys is a
+    /// [complex key](Tree::is_complex_key).
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("{name: ryml, version: 0.3}")?;
+    /// for (key, node) in tree.root_ref()?.iter_map()? {
+    ///     println!("{key} = {}", node.val()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_map<'r>(
+        &'r self,
+    ) -> Result<impl Iterator<Item = (&'t str, NodeRef<'a, 't, 'r, &'t Tree<'a>>)>> {
+        if self.seed.0 != SeedInner::None {
+            return Err(Error::NodeNotFound);
+        }
+        let tree = tree_ref!(self.tree);
+        if !tree.is_map(self.index)? {
+            return Err(Error::NotAMap);
+        }
+        // Check every key up front so the iterator itself cannot fail.
+        let first = tree.node_data(self.index)?.first_child;
+        for node in NodeIterator::new(tree, self.index, first) {
+            tree.key(node.index)?;
+        }
+        Ok(NodeIterator::new(tree, self.index, first).map(move |node| {
+            let key = tree.key(node.index).expect("keys were checked");
+            (key, node)
+        }))
+    }
+
+    /// Count the descendants of this node (not including the node itself)
+    /// which match the predicate, without allocating.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("a: !obj {b: !obj {}, c: 1}\nd: !obj {}\n")?;
+    /// let root = tree.root_ref()?;
+    /// assert_eq!(3, root.count_descendants(|node| node.has_val_tag_eq("!obj", false))?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn count_descendants<F>(&self, pred: F) -> Result<usize>
+    where
+        F: Fn(&NodeRef<'a, 't, '_, &'t Tree<'a>>) -> bool,
+    {
+        Ok(self.descendant_refs()?.filter(|node| pred(node)).count())
+    }
+
+    /// Check whether any descendant of this node matches the predicate,
+    /// stopping at the first which does.
+    pub fn any_descendant<F>(&self, pred: F) -> Result<bool>
+    where
+        F: Fn(&NodeRef<'a, 't, '_, &'t Tree<'a>>) -> bool,
+    {
+        Ok(self.descendant_refs()?.any(|node| pred(&node)))
+    }
+
+    /// Check whether every descendant of this node matches the predicate,
+    /// stopping at the first which does not. Nodes without descendants
+    /// trivially pass.
+    pub fn all_descendants<F>(&self, pred: F) -> Result<bool>
+    where
+        F: Fn(&NodeRef<'a, 't, '_, &'t Tree<'a>>) -> bool,
+    {
+        Ok(self.descendant_refs()?.all(|node| pred(&node)))
+    }
+
+    /// Iterate over the descendants of this node, as with
+    /// [`Tree::descendants`].
+    fn descendant_refs(
+        &self,
+    ) -> Result<impl Iterator<Item = NodeRef<'a, 't, '_, &'t Tree<'a>>> + '_> {
+        if self.seed.0 != SeedInner::None {
+            return Err(Error::NodeNotFound);
+        }
+        let tree = tree_ref!(self.tree);
+        Ok(tree
+            .descendants(self.index)?
+            .map(move |index| NodeRef::new_exists(tree, index)))
     }
 }
 
+/// Compare two tags, optionally expanding the `!!` shorthand and verbatim
+/// `!<...>` tags (which rapidyaml may keep as `<...>`) to full URIs first.
+fn tag_eq(a: &str, b: &str, resolve: bool) -> bool {
+    a == b || (resolve && expand_tag(a) == expand_tag(b))
+}
+
 /// Lazy assignment for a node reference based on its seed. If the node already
 /// exists, we simply use the existing node ID. If the node doesn't exist, we
 /// use the child index or key by which it was queried to construct it, and then
@@ -682,7 +1400,10 @@ macro_rules! maybe_construct {
         match $self.seed.0 {
             SeedInner::None => $self.index,
             SeedInner::Index(idx) => {
-                let after = $self.tree.as_ref().child_at($self.index, idx - 1)?;
+                let after = match idx {
+                    0 => NONE,
+                    _ => $self.tree.as_ref().child_at($self.index, idx - 1)?,
+                };
                 let index = $self.tree.insert_child($self.index, after)?;
                 $self.index = index;
                 $self.seed = Seed(SeedInner::None);
@@ -699,6 +1420,46 @@ macro_rules! maybe_construct {
     };
 }
 
+impl<'a, 't, 'k> NodeRef<'a, 't, 'k, &'t mut Tree<'a>> {
+    /// Get an immutable reference to the same node, e.g. to pass to a
+    /// read-only helper without giving up this one. This reference cannot be
+    /// used to modify the tree again until the immutable one is dropped.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// fn describe<'a, 't>(node: &ryml::NodeRef<'a, 't, '_, &'t ryml::Tree<'a>>) -> String {
+    ///     format!("{} = {}", node.key().unwrap(), node.val().unwrap())
+    /// }
+    ///
+    /// let mut tree = ryml::Tree::parse("a: 1")?;
+    /// let mut node = tree.root_ref_mut()?.get_mut("a")?;
+    /// assert_eq!("a = 1", describe(&node.as_ref()));
+    /// node.set_val("2")?;
+    /// assert_eq!("a = 2", describe(&node.as_ref()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// The immutable reference cannot outlive a later change:
+    ///
+    /// ```compile_fail
+    /// let mut tree = ryml::Tree::parse("a: 1").unwrap();
+    /// let mut node = tree.root_ref_mut().unwrap().get_mut("a").unwrap();
+    /// let view = node.as_ref();
+    /// node.set_val("2").unwrap();
+    /// view.val().unwrap();
+    /// ```
+    #[inline(always)]
+    pub fn as_ref(&self) -> NodeRef<'a, '_, 'k, &Tree<'a>> {
+        NodeRef {
+            tree: &*self.tree,
+            index: self.index,
+            seed: self.seed,
+            _hack: PhantomData,
+        }
+    }
+}
+
 impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
     pub(crate) fn new_exists_mut<'na>(
         tree: &'t mut Tree<'a>,
@@ -722,7 +1483,7 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
     /// valid.
     pub fn data_mut<'r>(&'r mut self) -> Option<&'t mut NodeData<'t>> {
         let tree_ref = tree_ref_mut!(self.tree);
-        let ptr = inner::ffi::Tree::get_mut(tree_ref.inner.pin_mut(), self.index).ok()?;
+        let ptr = inner::ffi::Tree::get_mut(tree_ref.inner_mut(), self.index).ok()?;
         unsafe { ptr.as_mut() }
     }
 
@@ -736,7 +1497,7 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
     pub unsafe fn data_unchecked_mut<'r>(&'r mut self) -> &'t mut NodeData<'t> {
         #[allow(unused_unsafe)]
         let tree_ref = tree_ref_mut!(self.tree);
-        inner::ffi::Tree::get_mut(tree_ref.inner.pin_mut(), self.index)
+        inner::ffi::Tree::get_mut(tree_ref.inner_mut(), self.index)
             .unwrap_unchecked()
             .as_mut()
             .unwrap_unchecked()
@@ -900,6 +1661,14 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         self.tree.change_type(index, node_type)
     }
 
+    /// Set the style the node value is written in when emitted. See
+    /// [`Tree::set_val_style`].
+    #[inline(always)]
+    pub fn set_val_style(&mut self, style: ScalarStyle) -> Result<()> {
+        let index = maybe_construct!(self);
+        self.tree.set_val_style(index, style)
+    }
+
     /// Set flags on the node.
     #[inline(always)]
     pub fn set_type_flags(&mut self, more_flags: NodeType) -> Result<()> {
@@ -907,20 +1676,112 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         self.tree.set_flags(index, more_flags)
     }
 
-    /// Sets the node's key.
+    /// Sets the node's key. The key is copied into the tree's arena, so it
+    /// need not outlive the tree.
     #[inline(always)]
     pub fn set_key(&mut self, key: &str) -> Result<()> {
         let index = maybe_construct!(self);
         self.tree.set_key(index, key)
     }
 
-    /// Sets the node's value.
+    /// Sets the node's value. The value is copied into the tree's arena, so
+    /// it need not outlive the tree.
     #[inline(always)]
     pub fn set_val(&mut self, value: &str) -> Result<()> {
         let index = maybe_construct!(self);
         self.tree.set_val(index, value)
     }
 
+    /// Sets the node's value to the [`Display`](std::fmt::Display) text of
+    /// any value, such as a number. As with [`set_val`](#method.set_val), the
+    /// text is copied into the tree's arena.
+    #[inline(always)]
+    pub fn set_val_typed<V: std::fmt::Display>(&mut self, value: V) -> Result<()> {
+        self.set_val(&value.to_string())
+    }
+
+    /// Sets the node's value to an integer.
+    #[inline(always)]
+    pub fn set_val_int(&mut self, value: i64) -> Result<()> {
+        self.set_val_typed(value)
+    }
+
+    /// Sets the node's value to a float, written so that it reads back as a
+    /// float: whole numbers keep a decimal point, as in `2.0`, and NaN and
+    /// infinity are written `.nan`, `.inf` and `-.inf`. Those are given the
+    /// [plain](ScalarStyle::Plain) style, which the emitter would otherwise
+    /// quote, turning them into strings.
+    #[inline(always)]
+    pub fn set_val_float(&mut self, value: f64) -> Result<()> {
+        self.set_val(&crate::float_text(value))?;
+        if !value.is_finite() {
+            self.set_val_style(ScalarStyle::Plain)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the node's value to `true` or `false`.
+    #[inline(always)]
+    pub fn set_val_bool(&mut self, value: bool) -> Result<()> {
+        self.set_val(if value { "true" } else { "false" })
+    }
+
+    /// Replace the comment written on the lines before the node, as with
+    /// [`Tree::set_leading_comment`]. Fails with [`Error::NodeNotFound`] if
+    /// the node does not exist yet.
+    #[inline(always)]
+    pub fn set_leading_comment(&mut self, text: &str) -> Result<()> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        self.tree.set_leading_comment(self.index, text)
+    }
+
+    /// Sets the node's value only if it does not already have one, returning
+    /// whether the value was set. If the node does not exist yet (because it
+    /// was obtained by [`get_mut`](#method.get_mut) with a new key or index),
+    /// it is created. An existing node counts as absent if it has no value or
+    /// an empty one; containers are always left alone.
+    ///
+    /// This makes it easy to overlay defaults without clobbering user values:
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse("retries: 5\nname:\n")?;
+    /// let mut root = tree.root_ref_mut()?;
+    /// assert!(!root.get_mut("retries")?.set_val_if_absent("3")?);
+    /// assert!(root.get_mut("timeout")?.set_val_if_absent("30")?);
+    /// assert!(root.get_mut("name")?.set_val_if_absent("default")?);
+    /// assert_eq!(tree.emit()?, "retries: 5\nname: default\ntimeout: 30\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn set_val_if_absent(&mut self, default: &str) -> Result<bool> {
+        self.set_val_if(default, true)
+    }
+
+    /// Sets the node's value only if it does not have one at all, returning
+    /// whether the value was set. Unlike
+    /// [`set_val_if_absent`](#method.set_val_if_absent), an existing empty
+    /// value counts as present and is left alone.
+    #[inline(always)]
+    pub fn set_val_if_missing(&mut self, default: &str) -> Result<bool> {
+        self.set_val_if(default, false)
+    }
+
+    fn set_val_if(&mut self, default: &str, empty_is_absent: bool) -> Result<bool> {
+        if !self.is_seed() {
+            let node_type = self.node_type()?;
+            if node_type.is_container()
+                || (node_type.has_val() && !(empty_is_absent && self.val()?.is_empty()))
+            {
+                return Ok(false);
+            }
+        }
+        self.set_val(default)?;
+        Ok(true)
+    }
+
     /// Set the tag on the node key.
     #[inline(always)]
     pub fn set_key_tag(&mut self, v: &str) -> Result<()> {
@@ -963,6 +1824,180 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         self.tree.set_val_ref(index, v)
     }
 
+    /// Make sure the node is a sequence with at least `n` items, appending
+    /// items with the value `fill` as needed. If the node does not exist yet,
+    /// it is created; an existing node which has no value or an empty one is
+    /// turned into a sequence. Fails with [`Error::NotASeq`] for a map or a
+    /// non-empty scalar.
+    ///
+    /// A seed from [`get_mut`](#method.get_mut) with an index can only create
+    /// the item directly after the last one, so to assign to an index past
+    /// the end, pad the sequence first:
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse("ports: []")?;
+    /// let mut ports = tree.root_ref_mut()?.get_mut("ports")?;
+    /// ports.ensure_seq_len(3, "~")?;
+    /// ports.get_mut(2)?.set_val("8080")?;
+    /// assert_eq!(tree.emit()?, "ports:\n  - ~\n  - ~\n  - 8080\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ensure_seq_len(&mut self, n: usize, fill: &str) -> Result<()> {
+        let index = maybe_construct!(self);
+        self.make_container(index, ContainerType::Seq)?;
+        for _ in self.tree.num_children(index)?..n {
+            let child = self.tree.append_child(index)?;
+            self.tree.set_val(child, fill)?;
+        }
+        Ok(())
+    }
+
+    /// Get a mutable [`NodeRef`] to the child of this map with the given key,
+    /// making sure it is a map: an existing child which is a map is returned
+    /// as is, one with no value or an empty one is turned into a map, and a
+    /// missing one is appended. If this node does not exist yet (because it
+    /// was obtained by [`get_mut`](#method.get_mut) with a new key or index),
+    /// it is created as a map first, and the same goes for an existing node
+    /// with no value or an empty one.
+    ///
+    /// Fails with [`Error::NotAMap`] if the child or this node is a sequence
+    /// or a non-empty scalar.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse("server: {host: a}")?;
+    /// let mut root = tree.root_ref_mut()?;
+    /// root.get_or_insert_map("server")?.get_mut("port")?.set_val("80")?;
+    /// root.get_mut("client")?
+    ///     .get_or_insert_seq("hosts")?
+    ///     .get_mut(0)?
+    ///     .set_val("b")?;
+    /// assert_eq!(
+    ///     tree.emit()?,
+    ///     "server:\n  host: a\n  port: 80\nclient:\n  hosts:\n    - b\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline(always)]
+    pub fn get_or_insert_map(
+        &mut self,
+        key: &str,
+    ) -> Result<NodeRef<'a, 't, '_, &'t mut Tree<'a>>> {
+        self.get_or_insert(key, ContainerType::Map)
+    }
+
+    /// Get a mutable [`NodeRef`] to the child of this map with the given key,
+    /// making sure it is a sequence, as with
+    /// [`get_or_insert_map`](#method.get_or_insert_map). Fails with
+    /// [`Error::NotASeq`] if the child is a map or a non-empty scalar, or
+    /// with [`Error::NotAMap`] if this node cannot be a map.
+    #[inline(always)]
+    pub fn get_or_insert_seq(
+        &mut self,
+        key: &str,
+    ) -> Result<NodeRef<'a, 't, '_, &'t mut Tree<'a>>> {
+        self.get_or_insert(key, ContainerType::Seq)
+    }
+
+    fn get_or_insert(
+        &mut self,
+        key: &str,
+        container: ContainerType,
+    ) -> Result<NodeRef<'a, 't, '_, &'t mut Tree<'a>>> {
+        let index = maybe_construct!(self);
+        self.make_container(index, ContainerType::Map)?;
+        let child = match self.tree.find_child(index, key) {
+            Ok(child) => child,
+            Err(Error::NodeNotFound) => {
+                let child = self.tree.append_child(index)?;
+                self.tree.set_key(child, key)?;
+                child
+            }
+            Err(e) => return Err(e),
+        };
+        self.make_container(child, container)?;
+        Ok(NodeRef::new_exists_mut(tree_ref_mut!(self.tree), child))
+    }
+
+    /// Turn the given node into a container of the given type unless it
+    /// already is one, which is only allowed if it has no value or an empty
+    /// one.
+    fn make_container(&mut self, node: usize, container: ContainerType) -> Result<()> {
+        let node_type = self.tree.node_type(node)?;
+        let (matches, error, new_type) = match container {
+            ContainerType::Map => (node_type.is_map(), Error::NotAMap, NodeType::Map),
+            ContainerType::Seq => (node_type.is_seq(), Error::NotASeq, NodeType::Seq),
+        };
+        if !matches {
+            if node_type.is_container() || (node_type.has_val() && !self.tree.val(node)?.is_empty())
+            {
+                return Err(error);
+            }
+            self.tree.change_type(node, new_type)?;
+        }
+        Ok(())
+    }
+
+    /// Mark the node, if it is a container, to be emitted in single-line flow
+    /// style (e.g. `[0,1,2]` or `{a: 0,b: 1}`), optionally also marking
+    /// every container beneath it.
+    #[inline(always)]
+    pub fn set_flow_style(&mut self, recursive: bool) -> Result<()> {
+        self.set_container_style(NodeType::WipStyleFlowSl, recursive)
+    }
+
+    /// Mark the node, if it is a container, to be emitted in block style,
+    /// clearing any flow style, optionally also for every container beneath
+    /// it.
+    #[inline(always)]
+    pub fn set_block_style(&mut self, recursive: bool) -> Result<()> {
+        self.set_container_style(NodeType::NoType, recursive)
+    }
+
+    fn set_container_style(&mut self, style: NodeType, recursive: bool) -> Result<()> {
+        if self.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        let mut nodes = Vec::new();
+        self.tree.walk_preorder(self.index, &mut |index| {
+            nodes.push(index);
+            Ok(recursive)
+        })?;
+        let styles = NodeType::WipStyleFlowSl | NodeType::WipStyleFlowMl | NodeType::WipStyleBlock;
+        for node in nodes {
+            let node_type = self.tree.node_type(node)?;
+            if node_type.is_container() {
+                self.tree.set_flags(node, (node_type & !styles) | style)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Overlay the map entries of another node, which may belong to a
+    /// different tree, onto this one. See [`Tree::overlay`] for how the
+    /// entries are combined. A seed is first created as an empty map.
+    pub fn overlay<'sa, 'st, T>(
+        &mut self,
+        src: &NodeRef<'sa, 'st, '_, T>,
+        seqs: SeqMerge,
+    ) -> Result<()>
+    where
+        T: AsRef<Tree<'sa>> + 'st,
+        'sa: 'st,
+    {
+        if src.is_seed() {
+            return Err(Error::NodeNotFound);
+        }
+        if self.is_seed() {
+            let index = maybe_construct!(self);
+            let node_type = self.tree.node_type(index)?;
+            self.tree.set_flags(index, node_type | NodeType::Map)?;
+        }
+        self.tree.overlay(self.index, src.tree(), src.index, seqs)
+    }
+
     /// Empties the node and removes any children.
     #[inline(always)]
     pub fn clear(&mut self) -> Result<()> {
@@ -1130,6 +2165,23 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         }
     }
 
+    /// Remove this node, including any children, from its parent. This is
+    /// handy when filtering with a [`NodeRef`] to the child in hand. Nothing
+    /// is removed if the node does not exist yet. Fails with
+    /// [`Error::RootRemoval`] for the root node.
+    ///
+    /// Any other [`NodeRef`]s to this node or anything beneath it are
+    /// invalidated.
+    pub fn remove_self(self) -> Result<()> {
+        if self.seed.0 != SeedInner::None {
+            return Ok(());
+        }
+        if self.tree.node_data(self.index)?.parent == NONE {
+            return Err(Error::RootRemoval);
+        }
+        self.tree.remove(self.index)
+    }
+
     /// Change the node's position within its parent.
     #[inline(always)]
     pub fn move_<R: AsRef<Tree<'a>>>(&mut self, after: NodeRef<'a, 't, '_, R>) -> Result<()> {
@@ -1140,6 +2192,74 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         }
     }
 
+    /// Swap the node with its previous sibling. Does nothing if it is
+    /// already the first child.
+    pub fn move_up(&mut self) -> Result<()> {
+        match self.position()? {
+            Some(siblings) if siblings.prev != NONE => {
+                let after = self.tree.node_data(siblings.prev)?.prev_sibling;
+                self.move_after(siblings.parent, after)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Swap the node with its next sibling. Does nothing if it is already the
+    /// last child.
+    pub fn move_down(&mut self) -> Result<()> {
+        match self.position()? {
+            Some(siblings) if siblings.next != NONE => {
+                self.move_after(siblings.parent, siblings.next)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Move the node before all its siblings. Does nothing if it is already
+    /// the first child.
+    pub fn move_to_front(&mut self) -> Result<()> {
+        match self.position()? {
+            Some(siblings) if siblings.prev != NONE => self.move_after(siblings.parent, NONE),
+            _ => Ok(()),
+        }
+    }
+
+    /// Move the node after all its siblings. Does nothing if it is already
+    /// the last child.
+    pub fn move_to_back(&mut self) -> Result<()> {
+        match self.position()? {
+            Some(siblings) if siblings.next != NONE => {
+                self.move_after(siblings.parent, siblings.last)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the position of the node among its siblings, or `None` for the
+    /// root or a node which does not exist yet.
+    fn position(&self) -> Result<Option<Siblings>> {
+        if self.is_seed() {
+            return Ok(None);
+        }
+        let data = self.tree.node_data(self.index)?;
+        if data.parent == NONE {
+            return Ok(None);
+        }
+        Ok(Some(Siblings {
+            parent: data.parent,
+            prev: data.prev_sibling,
+            next: data.next_sibling,
+            last: self.tree.node_data(data.parent)?.last_child,
+        }))
+    }
+
+    /// Reposition the node after `after` (or first, for [`NONE`]) within the
+    /// same parent.
+    fn move_after(&mut self, parent: usize, after: usize) -> Result<()> {
+        // Unlike `move_node`, this allows `NONE` for the position.
+        self.tree.move_node_to_new_parent(self.index, parent, after)
+    }
+
     /// Move the node to a different parent, which may be in a new tree.
     #[inline(always)]
     pub fn move_to_parent<R: AsRef<Tree<'a>>>(
@@ -1148,7 +2268,7 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         after: NodeRef<'a, 't, '_, R>,
     ) -> Result<()> {
         if self.seed.0 == SeedInner::None && parent.seed.0 == SeedInner::None {
-            if self.tree == parent.tree {
+            if std::ptr::eq::<Tree>(self.tree, parent.tree) {
                 self.tree
                     .move_node_to_new_parent(self.index, parent.index, after.index)
             } else {
@@ -1177,7 +2297,7 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         if self.seed.0 != SeedInner::None || parent.seed.0 != SeedInner::None {
             return Err(Error::NodeNotFound);
         }
-        if self.tree == parent.tree {
+        if std::ptr::eq::<Tree>(self.tree, parent.tree) {
             let index = self.tree.duplicate(self.index, parent.index, after.index)?;
             Ok(NodeRef {
                 tree: tree_ref_mut!(self.tree),
@@ -1211,7 +2331,7 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         if self.seed.0 != SeedInner::None || parent.seed.0 != SeedInner::None {
             return Err(Error::NodeNotFound);
         }
-        if self.tree == parent.tree {
+        if std::ptr::eq::<Tree>(self.tree, parent.tree) {
             self.tree
                 .duplicate_children(self.index, parent.index, after.index)?;
         } else {
@@ -1277,6 +2397,105 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         }
     }
 
+    /// Get a mutable [`NodeRef`] to the node at the given path beneath this
+    /// node, creating it and any missing intermediate nodes. The path is
+    /// written as for [`Tree::at`].
+    ///
+    /// A node along the path which is not yet a container is turned into a
+    /// sequence if the next segment is an index or a map if it is a key, so
+    /// `a[0]` makes `a` a sequence while `a.b` makes it a map. An index may
+    /// refer to an existing item or the one directly after the last. Fails
+    /// with [`Error::NotAMap`] or [`Error::NotASeq`] if a node along the path
+    /// has a non-empty scalar value or is the other kind of container, and
+    /// with [`Error::InvalidPath`] if the path cannot be parsed.
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let mut tree = ryml::Tree::parse("{}")?;
+    /// tree.root_ref_mut()?.at_mut("servers[0].port")?.set_val("80")?;
+    /// assert_eq!(tree.emit()?, "servers:\n  - port: 80\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn at_mut(&mut self, path: &str) -> Result<NodeRef<'a, 't, '_, &'t mut Tree<'a>>> {
+        let segments = path::parse_path(path)?;
+        let index = self.walk_path(segments.into_iter().map(|segment| match segment {
+            Segment::Key(key) => (key, ContainerType::Map),
+            Segment::Index(pos) => (Cow::Owned(pos.to_string()), ContainerType::Seq),
+        }))?;
+        Ok(NodeRef::new_exists_mut(tree_ref_mut!(self.tree), index))
+    }
+
+    /// Like [`at_mut`](#method.at_mut), but with each segment paired with the
+    /// type of container it is looked up in: the first with this node, and
+    /// each later one with the node reached by the segment before it. This
+    /// allows e.g. a map key which looks like an index. Fails with
+    /// [`Error::NotAMap`] or [`Error::NotASeq`] if a node along the path
+    /// already exists with a conflicting type.
+    pub fn at_mut_typed(
+        &mut self,
+        path: &[(&str, ContainerType)],
+    ) -> Result<NodeRef<'a, 't, '_, &'t mut Tree<'a>>> {
+        let index = self.walk_path(path.iter().copied())?;
+        Ok(NodeRef::new_exists_mut(tree_ref_mut!(self.tree), index))
+    }
+
+    fn walk_path<S: AsRef<str>>(
+        &mut self,
+        path: impl Iterator<Item = (S, ContainerType)>,
+    ) -> Result<usize> {
+        let mut node = maybe_construct!(self);
+        for (segment, container) in path {
+            let segment = segment.as_ref();
+            self.make_container(node, container)?;
+            node = match container {
+                ContainerType::Map => match self.tree.find_child(node, segment) {
+                    Ok(child) => child,
+                    Err(Error::NodeNotFound) => {
+                        let child = self.tree.append_child(node)?;
+                        self.tree.set_key(child, segment)?;
+                        child
+                    }
+                    Err(e) => return Err(e),
+                },
+                ContainerType::Seq => {
+                    let pos = segment
+                        .parse::<usize>()
+                        .map_err(|_| Error::InvalidInteger {
+                            value: segment.to_owned(),
+                            target: "usize",
+                        })?;
+                    match pos.cmp(&self.tree.num_children(node)?) {
+                        std::cmp::Ordering::Less => self.tree.child_at(node, pos)?,
+                        std::cmp::Ordering::Equal => self.tree.append_child(node)?,
+                        std::cmp::Ordering::Greater => return Err(Error::NodeNotFound),
+                    }
+                }
+            };
+        }
+        Ok(node)
+    }
+
+    /// Iterate mutably over the siblings of this node (the other children of
+    /// its parent) in document order, skipping the node itself. The root node
+    /// has no siblings.
+    pub fn siblings_mut<'r>(
+        &'r mut self,
+    ) -> Result<impl Iterator<Item = NodeRef<'a, 't, 'r, &'t mut Tree<'a>>>> {
+        if self.seed.0 != SeedInner::None {
+            return Err(Error::NodeNotFound);
+        }
+        let index = self.index;
+        let (node_index, first) = match self.tree.parent(index) {
+            Ok(parent) => (parent, self.tree.node_data(parent)?.first_child),
+            Err(Error::NodeNotFound) => (index, NONE),
+            Err(e) => return Err(e),
+        };
+        Ok(
+            NodeIterator::new(tree_ref_mut!(self.tree), node_index, first)
+                .filter(move |node| node.index != index),
+        )
+    }
+
     /// Iterate mutably over the children of this node, if it exists and is
     /// valid.
     #[inline(always)]
@@ -1284,12 +2503,18 @@ impl<'a, 't> NodeRef<'a, 't, '_, &'t mut Tree<'a>> {
         if self.seed.0 != SeedInner::None {
             return Err(Error::NodeNotFound);
         }
-        Ok(NodeIterator {
-            tree: tree_ref_mut!(self.tree),
-            node_index: self.index,
-            index: 0,
-            len: self.num_children()?,
-            _hack: PhantomData,
-        })
+        let first = self.tree.node_data(self.index)?.first_child;
+        Ok(NodeIterator::new(
+            tree_ref_mut!(self.tree),
+            self.index,
+            first,
+        ))
     }
 }
+
+struct Siblings {
+    parent: usize,
+    prev: usize,
+    next: usize,
+    last: usize,
+}
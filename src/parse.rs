@@ -0,0 +1,501 @@
+use crate::{inner, Error, Result, Tree, YamlSchema, NONE};
+use std::collections::HashSet;
+
+/// Options for customizing how YAML source is parsed into a [`Tree`].
+///
+/// The default options behave exactly like [`Tree::parse`](crate::Tree::parse).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail with [`Error::ControlCharacter`] if any key or value scalar
+    /// contains a control character other than a tab, line feed or carriage
+    /// return. This includes control characters written as escape sequences
+    /// in double quoted scalars.
+    pub reject_control_chars: bool,
+    /// The [schema](YamlSchema) by which the tree's typed accessors read
+    /// plain scalars. If not given, the schema is chosen by the document's
+    /// `%YAML` directive, or is YAML 1.1 without one.
+    pub schema: Option<YamlSchema>,
+    /// Keep the comments in the source as extra scalar nodes, so they can
+    /// be inspected and edited like any other node, and written back out
+    /// with [`EmitOptions::comments`](crate::EmitOptions#structfield.comments).
+    ///
+    /// Each comment becomes a sibling of the nodes around it, holding the
+    /// text after the `#` (less one leading space) as its value. In a map,
+    /// its key is `#`. A comment on a line of its own is placed before the
+    /// node which follows it and tagged [`COMMENT_TAG`](crate::COMMENT_TAG).
+    /// A comment at the end of a line is placed after the node on that line
+    /// and tagged [`TRAILING_COMMENT_TAG`](crate::TRAILING_COMMENT_TAG), or
+    /// made the first child if that node is a map or sequence. For example,
+    /// ```yaml
+    /// # Server settings
+    /// server: # required
+    ///   port: 80 # default
+    /// ```
+    /// is parsed as if it were
+    /// ```yaml
+    /// '#': !comment Server settings
+    /// server:
+    ///   '#': !trailing-comment required
+    ///   port: 80
+    ///   '#': !trailing-comment default
+    /// ```
+    ///
+    /// Comment nodes are ordinary nodes, so they are also seen by lookups by
+    /// position, iteration and deserialization.
+    pub retain_comments: bool,
+}
+
+/// A position in YAML source. Lines and columns are zero-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Location {
+    /// The number of bytes from the start of the source.
+    pub offset: usize,
+    /// The line number.
+    pub line: usize,
+    /// The column, in bytes from the start of the line.
+    pub col: usize,
+}
+
+impl Location {
+    /// Find the line and column of the given byte offset into the source.
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let before = &source.as_bytes()[..offset.min(source.len())];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        Self {
+            offset,
+            line: before.iter().filter(|&&b| b == b'\n').count(),
+            col: offset - line_start,
+        }
+    }
+}
+
+/// A recoverable issue found in YAML source which parsed successfully,
+/// reported by [`Tree::parse_with_warnings`](crate::Tree::parse_with_warnings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A map has more than one entry with the same key. Lookups by key only
+    /// ever find the first one.
+    DuplicateKey {
+        /// The repeated key.
+        key: String,
+        /// The location of the repeated entry.
+        location: Location,
+    },
+    /// A tab was used for indentation, which YAML forbids. rapidyaml has no
+    /// way to report this itself, so it is found by scanning the source
+    /// lines in block context, outside of block scalars, flow collections
+    /// and quoted scalars, where tabs are allowed.
+    TabIndentation {
+        /// The location of the tab.
+        location: Location,
+    },
+    /// A tag for one of the YAML 1.1 types which were dropped from YAML 1.2,
+    /// such as `!!set` or `!!omap`.
+    DeprecatedTag {
+        /// The tag.
+        tag: String,
+        /// The location of the tagged node.
+        location: Location,
+    },
+}
+
+impl Warning {
+    /// Get the location the warning refers to.
+    pub fn location(&self) -> Location {
+        match self {
+            Warning::DuplicateKey { location, .. }
+            | Warning::TabIndentation { location }
+            | Warning::DeprecatedTag { location, .. } => *location,
+        }
+    }
+}
+
+/// A [`Write`](std::io::Write) sink which collects YAML source and parses it
+/// into a [`Tree`] on [`finish`](#method.finish), e.g. to feed a download to
+/// the parser with [`std::io::copy`].
+///
+/// rapidyaml cannot parse incrementally, so nothing is parsed until
+/// `finish` is called: every byte written is buffered, and the buffer is
+/// then parsed in place and kept alive by the tree as with
+/// [`Tree::parse_owned_buf`]. Memory use is therefore bounded only by the
+/// size of the document, and nothing is copied after it has been written.
+#[derive(Debug, Clone, Default)]
+pub struct TreeWriter {
+    buf: Vec<u8>,
+}
+
+impl TreeWriter {
+    /// Create a new writer with an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new writer with room for `capacity` bytes of source, e.g.
+    /// from a known content length, to avoid reallocating as it grows.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Parse everything written into a new tree. Fails with
+    /// [`Error::Utf8`] if the source is not valid UTF-8.
+    pub fn finish(self) -> Result<Tree<'static>> {
+        Tree::parse_owned_buf(self.buf)
+    }
+}
+
+impl std::io::Write for TreeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.buf.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// YAML 1.1 types which are not part of YAML 1.2.
+static DEPRECATED_TAGS: &[&str] = &[
+    "binary",
+    "merge",
+    "omap",
+    "pairs",
+    "set",
+    "timestamp",
+    "value",
+    "yaml",
+];
+
+/// Find the tabs used for indentation in block context, returning their
+/// offsets. Lines inside block scalars, flow collections and quoted scalars
+/// are skipped, since tabs are allowed there, as are blank and comment
+/// lines.
+fn tab_indentation(text: &str) -> Vec<usize> {
+    let mut tabs = Vec::new();
+    let mut flow_depth = 0usize;
+    let mut quote = None;
+    // The indentation of the line which started the block scalar being read.
+    let mut block_scalar = None;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let spaces = content.len() - content.trim_start_matches(' ').len();
+        let rest = content.trim_start_matches([' ', '\t']);
+        if let Some(owner) = block_scalar {
+            if rest.is_empty() || spaces > owner {
+                continue;
+            }
+            block_scalar = None;
+        }
+        let indent = &content[..content.len() - rest.len()];
+        if quote.is_none() && flow_depth == 0 && !rest.is_empty() && !rest.starts_with('#') {
+            if let Some(pos) = indent.find('\t') {
+                tabs.push(start + pos);
+            }
+        }
+        // Where a token may start, which is where quotes and flow collections
+        // can open.
+        let token_start = |prev: char| matches!(prev, ' ' | '\t' | '[' | '{' | ',');
+        let mut prev = ' ';
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match quote {
+                Some('\'') => {
+                    if c == '\'' && chars.next_if(|&(_, c)| c == '\'').is_none() {
+                        quote = None;
+                    }
+                }
+                Some(_) => {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == '"' {
+                        quote = None;
+                    }
+                }
+                None => match c {
+                    '\'' | '"' if token_start(prev) => quote = Some(c),
+                    '#' if prev == ' ' || prev == '\t' => break,
+                    '[' | '{' if flow_depth > 0 || token_start(prev) => flow_depth += 1,
+                    ']' | '}' if flow_depth > 0 => flow_depth -= 1,
+                    '|' | '>' if flow_depth == 0 && token_start(prev) => {
+                        let after = rest[i + 1..]
+                            .trim_start_matches(|c: char| {
+                                c.is_ascii_digit() || c == '+' || c == '-'
+                            })
+                            .trim_start_matches([' ', '\t']);
+                        if after.is_empty() || after.starts_with('#') {
+                            block_scalar = Some(spaces);
+                            break;
+                        }
+                    }
+                    _ => {}
+                },
+            }
+            prev = c;
+        }
+    }
+    tabs
+}
+
+impl<'a> Tree<'a> {
+    /// Create a new tree and parse into its root, using the given options.  
+    /// As with [`parse`](#method.parse), the immutable YAML source is first
+    /// copied to the tree's arena, and parsed from there.
+    pub fn parse_with(text: impl AsRef<str>, options: &ParseOptions) -> Result<Tree<'a>> {
+        let text = text.as_ref();
+        let mut tree = Self::parse(text)?;
+        if options.reject_control_chars {
+            tree.check_control_chars()?;
+        }
+        if let Some(schema) = options.schema {
+            tree.set_schema(schema);
+        }
+        if options.retain_comments {
+            tree.comments = true;
+            tree.insert_comments(text)?;
+            // The comment nodes are part of parsing, and lie outside the
+            // source, so node_at_offset still works.
+            tree.modified = false;
+        }
+        Ok(tree)
+    }
+
+    /// Create a new tree and parse into its root, also reporting any
+    /// recoverable issues in the source which a stricter parser would reject
+    /// or which are likely mistakes. See [`Warning`](crate::Warning) for the
+    /// issues detected.  
+    /// As with [`parse`](#method.parse), the immutable YAML source is first
+    /// copied to the tree's arena, and parsed from there.
+    pub fn parse_with_warnings(text: impl AsRef<str>) -> Result<(Tree<'a>, Vec<Warning>)> {
+        let text = text.as_ref();
+        let tree = Self::parse(text)?;
+        let mut warnings: Vec<_> = tab_indentation(text)
+            .into_iter()
+            .map(|offset| Warning::TabIndentation {
+                location: Location::from_offset(text, offset),
+            })
+            .collect();
+        let base = tree.inner.arena().ptr as usize;
+        let locate =
+            |s: &str| Location::from_offset(text, (s.as_ptr() as usize).wrapping_sub(base));
+        tree.walk_preorder(tree.root_id()?, &mut |index| {
+            let data = tree.node_data(index)?;
+            // Tags are normalized, so they may no longer point into the source.
+            let node_location = || {
+                locate(if data.node_type.has_key() {
+                    data.key.scalar
+                } else {
+                    data.value.scalar
+                })
+            };
+            let tags = [
+                (data.node_type.has_key_tag(), data.key.tag),
+                (data.node_type.has_val_tag(), data.value.tag),
+            ];
+            for (present, tag) in tags {
+                let name = tag
+                    .strip_prefix("!!")
+                    .or_else(|| tag.strip_prefix("tag:yaml.org,2002:"));
+                if present && name.is_some_and(|name| DEPRECATED_TAGS.contains(&name)) {
+                    warnings.push(Warning::DeprecatedTag {
+                        tag: tag.to_owned(),
+                        location: node_location(),
+                    });
+                }
+            }
+            if data.node_type.is_map() {
+                let mut keys = HashSet::new();
+                let mut child = data.first_child;
+                while child != NONE {
+                    let child_data = tree.node_data(child)?;
+                    let key = child_data.key.scalar;
+                    if !keys.insert(key) {
+                        warnings.push(Warning::DuplicateKey {
+                            key: key.to_owned(),
+                            location: locate(key),
+                        });
+                    }
+                    child = child_data.next_sibling;
+                }
+            }
+            Ok(true)
+        })?;
+        warnings.sort_by_key(|warning| warning.location().offset);
+        Ok((tree, warnings))
+    }
+
+    /// Check whether the given text is valid YAML, without keeping the parsed
+    /// tree around.
+    #[inline(always)]
+    pub fn is_valid_yaml(text: impl AsRef<str>) -> bool {
+        Self::validate_yaml(text).is_ok()
+    }
+
+    /// Check whether the given text is valid YAML, without keeping the parsed
+    /// tree around, returning the parse error if it is not.
+    #[inline(always)]
+    pub fn validate_yaml(text: impl AsRef<str>) -> Result<()> {
+        inner::ffi::validate(text.as_ref()).map_err(crate::parse_error)
+    }
+
+    /// Find the node under a byte offset into the source the tree was
+    /// parsed from, e.g. the position of a cursor in an editor. This is the
+    /// innermost node whose key or value starts at or before the offset
+    /// and before that of its next sibling, so whitespace and comments after
+    /// a node belong to it, and the root if the offset comes before anything
+    /// else.
+    ///
+    /// Node positions come from where their scalars lie in the source, so
+    /// this only works for trees which have not been modified since they
+    /// were parsed, and whose source the tree knows: those from
+    /// [`parse`](#method.parse) and the other methods which copy the source
+    /// to the arena, and from [`parse_owned_buf`](#method.parse_owned_buf).
+    /// Unlike [`is_dirty`](#method.is_dirty), emitting the tree does not make
+    /// it count as unmodified again. Otherwise, and for an offset past the
+    /// end of the source, returns `None`.
+    ///
+    /// The first call sorts the nodes by their offset, and each call then
+    /// looks the offset up with a binary search.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let src = "server:\n  ports: [80, 443]\n";
+    /// let tree = ryml::Tree::parse(src)?;
+    /// let node = tree.node_at_offset(src.find("443").unwrap() + 1).unwrap();
+    /// assert_eq!("443", tree.val(node)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn node_at_offset(&self, offset: usize) -> Option<usize> {
+        if self.modified || self.is_empty() {
+            return None;
+        }
+        let (base, len) = self.source_range()?;
+        if offset >= len {
+            return None;
+        }
+        let offsets = match self.offsets.get() {
+            Some(offsets) => offsets,
+            None => {
+                let offsets = self.offset_index(base, len).ok()?;
+                self.offsets.get_or_init(|| offsets)
+            }
+        };
+        // In document order a node starts no earlier than its parent and
+        // earlier siblings, so the last node starting at or before the
+        // offset is the innermost one containing it.
+        let found = offsets.partition_point(|&(start, _)| start <= offset);
+        match found {
+            0 => self.root_id().ok(),
+            _ => Some(offsets[found - 1].1),
+        }
+    }
+
+    /// The address and length of the source the tree was parsed from, if it
+    /// knows it.
+    fn source_range(&self) -> Option<(usize, usize)> {
+        match &self._data {
+            crate::TreeData::Owned => {
+                let arena = self.inner.arena();
+                Some((arena.ptr as usize, arena.len))
+            }
+            crate::TreeData::OwnedBuffer(buf) => Some((buf.as_ptr() as usize, buf.len())),
+            crate::TreeData::Borrowed(_) => None,
+        }
+    }
+
+    /// The offsets into the source at which the nodes below the root start,
+    /// with the nodes in document order, sorted by offset.
+    fn offset_index(&self, base: usize, len: usize) -> Result<Vec<(usize, usize)>> {
+        let root = self.root_id()?;
+        let mut offsets = Vec::with_capacity(self.len());
+        self.walk_preorder(root, &mut |index| {
+            if index != root {
+                if let Some(start) = self.source_start(index, base, len) {
+                    offsets.push((start, index));
+                }
+            }
+            Ok(true)
+        })?;
+        // Nodes outside the source, such as comments, are out of order.
+        offsets.sort_by_key(|&(start, _)| start);
+        Ok(offsets)
+    }
+
+    /// The offset into the source of the start of a node: its key, its value
+    /// or its first descendant, ignoring scalars which are not in the
+    /// source.
+    fn source_start(&self, node: usize, base: usize, len: usize) -> Option<usize> {
+        let data = self.node_data(node).ok()?;
+        let offset = |scalar: &str| {
+            let offset = (scalar.as_ptr() as usize).wrapping_sub(base);
+            (offset < len).then_some(offset)
+        };
+        let node_type = data.node_type;
+        if node_type.has_key() && !node_type.is_key_ref() {
+            if let Some(start) = offset(data.key.scalar) {
+                return Some(start);
+            }
+        }
+        if node_type.has_val() && !node_type.is_container() && !node_type.is_val_ref() {
+            return offset(data.value.scalar);
+        }
+        let mut child = data.first_child;
+        while child != NONE {
+            if let Some(start) = self.source_start(child, base, len) {
+                return Some(start);
+            }
+            child = self.node_data(child).ok()?.next_sibling;
+        }
+        None
+    }
+
+    fn check_control_chars(&self) -> Result<()> {
+        // The source is the first thing copied to the arena, so offsets into
+        // the arena are offsets into the source.
+        let base = self.inner.arena().ptr as usize;
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            let data = self.node_data(index)?;
+            let scalars = [
+                (data.node_type.has_key(), data.key.scalar),
+                (data.node_type.has_val(), data.value.scalar),
+            ];
+            for (_, scalar) in scalars.into_iter().filter(|(present, _)| *present) {
+                if let Some((pos, character)) = scalar
+                    .char_indices()
+                    .find(|(_, c)| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+                {
+                    return Err(Error::ControlCharacter {
+                        offset: (scalar.as_ptr() as usize).wrapping_sub(base) + pos,
+                        character,
+                    });
+                }
+            }
+            Ok(true)
+        })?;
+        Ok(())
+    }
+}
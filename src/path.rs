@@ -0,0 +1,193 @@
+use crate::{Error, NodeRef, Result, Tree};
+use std::borrow::Cow;
+
+/// One step of a path given to [`Tree::at`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Segment<'p> {
+    /// A map key.
+    Key(Cow<'p, str>),
+    /// A sequence index.
+    Index(usize),
+}
+
+/// Split a path like `a.b[0]['c.d']` into its segments.
+pub(crate) fn parse_path(path: &str) -> Result<Vec<Segment<'_>>> {
+    let invalid = |reason: &'static str| Error::InvalidPath {
+        path: path.to_owned(),
+        reason,
+    };
+    let mut segments = Vec::new();
+    let mut rest = path;
+    let mut after_dot = false;
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[').filter(|_| !after_dot) {
+            let (segment, after) = match bracketed.chars().next() {
+                Some(quote @ ('\'' | '"')) => parse_quoted(&bracketed[1..], quote)
+                    .ok_or_else(|| invalid("unterminated quoted key"))?,
+                _ => {
+                    let end = bracketed
+                        .find(']')
+                        .ok_or_else(|| invalid("unterminated index"))?;
+                    let index = bracketed[..end]
+                        .parse()
+                        .map_err(|_| invalid("index is not a number"))?;
+                    (Segment::Index(index), &bracketed[end..])
+                }
+            };
+            rest = after
+                .strip_prefix(']')
+                .ok_or_else(|| invalid("expected `]`"))?;
+            segments.push(segment);
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid("empty key"));
+            }
+            segments.push(Segment::Key(Cow::Borrowed(&rest[..end])));
+            rest = &rest[end..];
+        }
+        after_dot = false;
+        if let Some(after) = rest.strip_prefix('.') {
+            if after.is_empty() {
+                return Err(invalid("empty key"));
+            }
+            rest = after;
+            after_dot = true;
+        }
+    }
+    Ok(segments)
+}
+
+/// Read a quoted key up to its closing quote, returning it and the rest of
+/// the path after the quote. A backslash escapes the next character.
+fn parse_quoted(text: &str, quote: char) -> Option<(Segment<'_>, &str)> {
+    let mut key = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => key.push(chars.next()?.1),
+            c if c == quote => {
+                let key = if key.len() == i {
+                    Cow::Borrowed(&text[..i])
+                } else {
+                    Cow::Owned(key)
+                };
+                return Some((Segment::Key(key), &text[i + 1..]));
+            }
+            c => key.push(c),
+        }
+    }
+    None
+}
+
+/// Append a map key to a path, in the form read by [`Tree::at`]. A key
+/// which would not read back as a plain key is quoted in brackets.
+pub(crate) fn push_key(path: &mut String, key: &str) {
+    if key.is_empty() || key.contains(['.', '[']) {
+        path.push_str("['");
+        for c in key.chars() {
+            if matches!(c, '\\' | '\'') {
+                path.push('\\');
+            }
+            path.push(c);
+        }
+        path.push_str("']");
+    } else {
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key);
+    }
+}
+
+/// Append a sequence index to a path, in the form read by [`Tree::at`].
+pub(crate) fn push_index(path: &mut String, pos: usize) {
+    path.push('[');
+    path.push_str(&pos.to_string());
+    path.push(']');
+}
+
+impl<'a> Tree<'a> {
+    /// Get a [`NodeRef`] to the node at the given path from the root. Map
+    /// keys are separated by `.`, and sequence indices are given in brackets,
+    /// e.g. `param_root.objects.DemoAIActionIdx[0]`. A key containing `.`,
+    /// `[` or `]` can be quoted in brackets instead, as in `a['weird.key']`
+    /// or `a["weird.key"]`, with `\` escaping the quote. An empty path is the
+    /// root itself.
+    ///
+    /// Fails with [`Error::NodeNotFound`] if any segment of the path is
+    /// missing, including a key looked up in something other than a map or
+    /// an index in something other than a sequence, and with
+    /// [`Error::InvalidPath`] if the path cannot be parsed.
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), ryml::Error> {
+    /// let tree = ryml::Tree::parse("servers: [{host: a, tags: {x.y: 1}}]")?;
+    /// assert_eq!("a", tree.at("servers[0].host")?.val()?);
+    /// assert_eq!("1", tree.at("servers[0].tags['x.y']")?.val()?);
+    /// assert!(matches!(tree.at("servers[1]"), Err(ryml::Error::NodeNotFound)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn at<'t>(&'t self, path: &str) -> Result<NodeRef<'a, 't, 't, &'t Self>> {
+        let mut node = self.root_id()?;
+        for segment in parse_path(path)? {
+            let node_type = self.node_type(node)?;
+            node = match segment {
+                Segment::Key(key) if node_type.is_map() => self.find_child(node, &key)?,
+                Segment::Index(pos) if node_type.is_seq() => self.child_at(node, pos)?,
+                _ => return Err(Error::NodeNotFound),
+            };
+        }
+        Ok(NodeRef::new_exists(self, node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_paths() {
+        use Segment::*;
+        let key = |k| Key(Cow::Borrowed(k));
+        assert_eq!(Vec::<Segment>::new(), parse_path("").unwrap());
+        assert_eq!(
+            vec![key("a"), key("b"), Index(0), Index(12)],
+            parse_path("a.b[0][12]").unwrap()
+        );
+        assert_eq!(
+            vec![Index(1), key("weird.key"), key("x"), key("it's]")],
+            parse_path(r#"[1]['weird.key'].x["it's]"]"#).unwrap()
+        );
+        assert_eq!(
+            vec![Key(Cow::Owned("a'b".into()))],
+            parse_path(r"['a\'b']").unwrap()
+        );
+        let mut path = String::new();
+        push_index(&mut path, 1);
+        for key in ["a", "weird.key", "", r"it's\[x]"] {
+            push_key(&mut path, key);
+        }
+        assert_eq!(r"[1].a['weird.key']['']['it\'s\\[x]']", path);
+        assert_eq!(
+            vec![
+                Index(1),
+                key("a"),
+                key("weird.key"),
+                key(""),
+                Key(Cow::Owned(r"it's\[x]".into()))
+            ],
+            parse_path(&path).unwrap()
+        );
+        for bad in [
+            "a.", ".a", "a..b", "a.[0]", "a[", "a[x]", "a['b", "a['b'c]", "a[-1]",
+        ] {
+            assert!(
+                matches!(parse_path(bad), Err(Error::InvalidPath { .. })),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{inner, NodeData, Result, Tree, NONE};
+use std::collections::HashSet;
+
+/// What [`Tree::repair`] fixed. Each list holds node indices in the order
+/// the nodes were visited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Nodes whose parent index was corrected.
+    pub parents: Vec<usize>,
+    /// Nodes whose previous or next sibling index was corrected.
+    pub siblings: Vec<usize>,
+    /// Containers whose first or last child index was corrected.
+    pub children: Vec<usize>,
+    /// Nodes whose sibling chain was cut short, because it led back to a node
+    /// already in the tree or to an index which is out of range.
+    pub broken_chains: Vec<usize>,
+}
+
+impl RepairReport {
+    /// Whether nothing needed fixing.
+    pub fn is_clean(&self) -> bool {
+        self.parents.is_empty()
+            && self.siblings.is_empty()
+            && self.children.is_empty()
+            && self.broken_chains.is_empty()
+    }
+}
+
+impl<'a> Tree<'a> {
+    /// Make the links between the nodes of the tree agree with each other
+    /// again, e.g. after they were edited by hand through
+    /// [`NodeRef::data_unchecked_mut`](crate::NodeRef::data_unchecked_mut),
+    /// and report what was fixed. This is a best-effort recovery tool.
+    ///
+    /// Starting from the root, the children of each node are taken to be the
+    /// chain of `next_sibling` links from its `first_child`, or if that is
+    /// missing, the chain of `prev_sibling` links back from its
+    /// `last_child`. Every other link is then made to match: the `parent`
+    /// and sibling links of each child, and the `first_child` and
+    /// `last_child` of each node. A chain is cut short where it would visit
+    /// a node a second time or leave the node array. Nodes which cannot be
+    /// reached from the root are left alone.
+    pub fn repair(&mut self) -> Result<RepairReport> {
+        let mut report = RepairReport::default();
+        if self.is_empty() {
+            return Ok(report);
+        }
+        let capacity = self.inner.capacity();
+        let in_range = |index: usize| index != NONE && index < capacity;
+        let root = self.root_id()?;
+        let mut visited = HashSet::from([root]);
+        let mut pending = vec![root];
+        while let Some(node) = pending.pop() {
+            let data = *self.node_data(node)?;
+            // Follow the forward chain if there is one, otherwise the
+            // backward chain, stopping where it breaks.
+            let backward = !in_range(data.first_child) && in_range(data.last_child);
+            let mut children = Vec::new();
+            let mut child = if backward {
+                data.last_child
+            } else {
+                data.first_child
+            };
+            let mut last_good = NONE;
+            while child != NONE {
+                if !in_range(child) || !visited.insert(child) {
+                    if last_good != NONE {
+                        report.broken_chains.push(last_good);
+                    }
+                    break;
+                }
+                children.push(child);
+                last_good = child;
+                let child_data = self.node_data(child)?;
+                child = if backward {
+                    child_data.prev_sibling
+                } else {
+                    child_data.next_sibling
+                };
+            }
+            if backward {
+                children.reverse();
+            }
+            let first = children.first().copied().unwrap_or(NONE);
+            let last = children.last().copied().unwrap_or(NONE);
+            if data.first_child != first || data.last_child != last {
+                let links = self.links_mut(node)?;
+                links.first_child = first;
+                links.last_child = last;
+                report.children.push(node);
+            }
+            for (pos, &child) in children.iter().enumerate() {
+                let prev = if pos == 0 { NONE } else { children[pos - 1] };
+                let next = children.get(pos + 1).copied().unwrap_or(NONE);
+                let child_data = self.node_data(child)?;
+                let (parent, prev_sibling, next_sibling) = (
+                    child_data.parent,
+                    child_data.prev_sibling,
+                    child_data.next_sibling,
+                );
+                if parent != node {
+                    self.links_mut(child)?.parent = node;
+                    report.parents.push(child);
+                }
+                if prev_sibling != prev || next_sibling != next {
+                    let links = self.links_mut(child)?;
+                    links.prev_sibling = prev;
+                    links.next_sibling = next;
+                    report.siblings.push(child);
+                }
+            }
+            // Visit the children in document order.
+            pending.extend(children.into_iter().rev());
+        }
+        Ok(report)
+    }
+
+    /// Get the node data to edit its links, marking the tree
+    /// [dirty](#method.is_dirty).
+    fn links_mut(&mut self, node: usize) -> Result<&mut NodeData<'static>> {
+        let data = inner::ffi::Tree::get_mut(self.inner_mut(), node)?;
+        // SAFETY: the node exists, and only its links are changed.
+        Ok(unsafe { &mut *data.cast::<NodeData<'static>>() })
+    }
+}
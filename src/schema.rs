@@ -0,0 +1,278 @@
+use crate::{diff::NormalizedVal, Error, NodeType, Result, Tree};
+use std::borrow::Cow;
+
+/// The rules by which plain (unquoted) scalars are read as nulls, booleans
+/// and numbers, which differ between versions of YAML. Used by the typed
+/// accessors such as [`Tree::val_bool`](crate::Tree::val_bool) and by the
+/// serde deserializer.
+///
+/// A tree parsed from a document starting with a `%YAML` directive uses the
+/// schema of that version, and otherwise [`V1_1`](YamlSchema::V1_1), unless
+/// another is given in [`ParseOptions`](crate::ParseOptions) or set with
+/// [`Tree::set_schema`](crate::Tree::set_schema). Without any of these,
+/// [`Tree::resolved_tag`](crate::Tree::resolved_tag) uses
+/// [`Core`](YamlSchema::Core) instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum YamlSchema {
+    /// YAML 1.1: booleans include `yes`/`no`, `on`/`off` and `y`/`n`,
+    /// integers with a leading `0` are octal, `0b` introduces binary, and
+    /// numbers may contain `_` separators.
+    #[default]
+    V1_1,
+    /// YAML 1.2, whose recommended schema is the [core](YamlSchema::Core)
+    /// schema.
+    V1_2,
+    /// The JSON schema of YAML 1.2: only `true`, `false`, `null` and numbers
+    /// as JSON writes them.
+    Json,
+    /// The core schema of YAML 1.2: booleans are `true` or `false` (also
+    /// capitalized or upper case), and octal integers are written `0o17`.
+    Core,
+}
+
+impl YamlSchema {
+    /// Find the schema named by a `%YAML` directive at the start of YAML
+    /// source, if there is one.
+    pub(crate) fn from_directive(text: &str) -> Option<Self> {
+        for line in text.lines() {
+            let line = line.trim_end();
+            if let Some(version) = line.strip_prefix("%YAML") {
+                return match version.split_whitespace().next()? {
+                    "1.1" | "1.0" => Some(Self::V1_1),
+                    _ => Some(Self::V1_2),
+                };
+            }
+            // Directives and comments may only come before the document.
+            if !(line.is_empty() || line.starts_with('%') || line.starts_with('#')) {
+                return None;
+            }
+        }
+        None
+    }
+
+    pub(crate) fn is_null(self, scalar: &str) -> bool {
+        match self {
+            Self::Json => scalar == "null",
+            _ => matches!(scalar, "" | "~" | "null" | "Null" | "NULL"),
+        }
+    }
+
+    pub(crate) fn to_bool(self, scalar: &str) -> Option<bool> {
+        match (self, scalar) {
+            (Self::Json, "true") => Some(true),
+            (Self::Json, "false") => Some(false),
+            (Self::Json, _) => None,
+            (_, "true" | "True" | "TRUE") => Some(true),
+            (_, "false" | "False" | "FALSE") => Some(false),
+            (Self::V1_1, "y" | "Y" | "yes" | "Yes" | "YES" | "on" | "On" | "ON") => Some(true),
+            (Self::V1_1, "n" | "N" | "no" | "No" | "NO" | "off" | "Off" | "OFF") => Some(false),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_int(self, scalar: &str) -> Option<i128> {
+        match self.int_parts(scalar)? {
+            (true, Some(magnitude)) => 0i128.checked_sub_unsigned(magnitude),
+            (false, Some(magnitude)) => i128::try_from(magnitude).ok(),
+            (_, None) => None,
+        }
+    }
+
+    /// Read an integer scalar as whether it is negative and its magnitude,
+    /// which is `None` if the integer is too big for a `u128`.
+    pub(crate) fn int_parts(self, scalar: &str) -> Option<(bool, Option<u128>)> {
+        let (negative, unsigned) = match scalar.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None if self == Self::Json => (false, scalar),
+            None => (false, scalar.strip_prefix('+').unwrap_or(scalar)),
+        };
+        let signed = unsigned.len() != scalar.len();
+        let (radix, digits) = match self {
+            Self::Json if unsigned.len() > 1 && unsigned.starts_with('0') => return None,
+            Self::Json => (10, unsigned),
+            Self::V1_1 => {
+                let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x") {
+                    (16, hex)
+                } else if let Some(binary) = unsigned.strip_prefix("0b") {
+                    (2, binary)
+                } else if unsigned.len() > 1 && unsigned.starts_with('0') {
+                    (8, &unsigned[1..])
+                } else {
+                    (10, unsigned)
+                };
+                if digits.starts_with('_') {
+                    return None;
+                }
+                return parse_digits(&digits.replace('_', ""), radix, negative);
+            }
+            Self::V1_2 | Self::Core => {
+                if let Some(hex) = unsigned.strip_prefix("0x").filter(|_| !signed) {
+                    (16, hex)
+                } else if let Some(octal) = unsigned.strip_prefix("0o").filter(|_| !signed) {
+                    (8, octal)
+                } else {
+                    (10, unsigned)
+                }
+            }
+        };
+        parse_digits(digits, radix, negative)
+    }
+
+    /// Whether `.inf`, `-.inf` and `.nan` (and their capitalized spellings)
+    /// are floats.
+    pub(crate) fn has_special_floats(self) -> bool {
+        self != Self::Json
+    }
+}
+
+fn parse_digits(digits: &str, radix: u32, negative: bool) -> Option<(bool, Option<u128>)> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    Some((negative, u128::from_str_radix(digits, radix).ok()))
+}
+
+/// The type of a node once its tag is resolved, from
+/// [`Tree::resolved_tag`](crate::Tree::resolved_tag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YamlType {
+    /// `!!null`, e.g. `~` or an empty value.
+    Null,
+    /// `!!bool`, e.g. `true`.
+    Bool,
+    /// `!!int`, e.g. `123`.
+    Int,
+    /// `!!float`, e.g. `1.5` or `.inf`.
+    Float,
+    /// `!!str`, including every quoted scalar.
+    Str,
+    /// `!!map`.
+    Map,
+    /// `!!seq`.
+    Seq,
+}
+
+impl YamlType {
+    /// The type named by a tag in the standard `tag:yaml.org,2002:`
+    /// namespace, written as `!!int` or in full.
+    fn from_tag(tag: &str) -> Option<Self> {
+        match expand_tag(tag).strip_prefix("tag:yaml.org,2002:")? {
+            "null" => Some(Self::Null),
+            "bool" => Some(Self::Bool),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "str" => Some(Self::Str),
+            "map" => Some(Self::Map),
+            "seq" => Some(Self::Seq),
+            _ => None,
+        }
+    }
+}
+
+/// Expand a tag to its full form: `!!str` is in the standard
+/// `tag:yaml.org,2002:` namespace, verbatim tags like `!<tag:x>` (which
+/// rapidyaml may keep as `<tag:x>`) lose their brackets, and local tags like
+/// `!foo` are kept as they are.
+pub(crate) fn expand_tag(tag: &str) -> Cow<'_, str> {
+    if let Some(name) = tag.strip_prefix("!!") {
+        format!("tag:yaml.org,2002:{name}").into()
+    } else if let Some(verbatim) = tag
+        .strip_prefix("!<")
+        .or_else(|| tag.strip_prefix('<'))
+        .and_then(|tag| tag.strip_suffix('>'))
+    {
+        verbatim.into()
+    } else {
+        tag.into()
+    }
+}
+
+impl<'a> Tree<'a> {
+    /// Resolve the type of the given node. Maps and sequences are
+    /// [`Map`](YamlType::Map) and [`Seq`](YamlType::Seq). A scalar with a
+    /// standard tag such as `!!int` or `!!str` has the type it names,
+    /// whatever it looks like, and a quoted scalar or one with the
+    /// non-specific tag `!` is a string. Any other scalar, including one with
+    /// a custom tag, is resolved from its text: `~` is null, `true` a boolean,
+    /// `123` an integer and so on. This follows the
+    /// [core](YamlSchema::Core) schema of YAML 1.2, unless the document has a
+    /// `%YAML` directive or a schema was given in
+    /// [`ParseOptions`](crate::ParseOptions) or with
+    /// [`set_schema`](#method.set_schema), in which case that schema is
+    /// used. So unlike the typed accessors, this reads `yes` as a string by
+    /// default. An alias has the type of its anchored node.
+    ///
+    /// ```rust
+    /// # use ryml::{Tree, YamlType};
+    /// let tree = Tree::parse("[123, '123', !!str 123, ~, 1.5, {}]")?;
+    /// let types = (0..6)
+    ///     .map(|i| tree.root_ref()?.get(i)?.resolved_tag())
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(
+    ///     vec![YamlType::Int, YamlType::Str, YamlType::Str, YamlType::Null, YamlType::Float, YamlType::Map],
+    ///     types
+    /// );
+    /// # Ok::<(), ryml::Error>(())
+    /// ```
+    pub fn resolved_tag(&self, node: usize) -> Result<YamlType> {
+        let data = self.node_data(node)?;
+        let node_type = data.node_type;
+        if node_type.is_map() {
+            return Ok(YamlType::Map);
+        } else if node_type.is_seq() {
+            return Ok(YamlType::Seq);
+        } else if node_type.is_val_ref() {
+            return self.resolved_tag(self.anchored_before(node, data.value.anchor)?);
+        }
+        if node_type.has_val_tag() {
+            match data.value.tag {
+                "!" => return Ok(YamlType::Str),
+                tag => {
+                    if let Some(resolved) = YamlType::from_tag(tag) {
+                        return Ok(resolved);
+                    }
+                }
+            }
+        }
+        if !node_type.has_val() {
+            return Ok(YamlType::Null);
+        }
+        if node_type.is_val_quoted()
+            || (node_type & (NodeType::WipValSquo | NodeType::WipValDquo)) != NodeType::NoType
+        {
+            return Ok(YamlType::Str);
+        }
+        Ok(
+            match NormalizedVal::from_plain_in(data.value.scalar, self.tag_schema()) {
+                NormalizedVal::Null => YamlType::Null,
+                NormalizedVal::Bool(_) => YamlType::Bool,
+                NormalizedVal::Int(_) => YamlType::Int,
+                NormalizedVal::Float(_) => YamlType::Float,
+                NormalizedVal::Str(_) | NormalizedVal::Alias(_) => YamlType::Str,
+            },
+        )
+    }
+
+    /// The schema by which [`resolved_tag`](#method.resolved_tag) reads plain
+    /// scalars: the core schema unless another was chosen.
+    pub(crate) fn tag_schema(&self) -> YamlSchema {
+        self.schema.unwrap_or(YamlSchema::Core)
+    }
+
+    /// Find the node an alias refers to: the last node with the anchor
+    /// before the alias in document order.
+    pub(crate) fn anchored_before(&self, alias: usize, anchor: &str) -> Result<usize> {
+        let mut anchored = None;
+        self.walk_preorder(self.root_id()?, &mut |index| {
+            if index == alias {
+                return Ok(false);
+            }
+            let data = self.node_data(index)?;
+            if data.node_type.has_val_anchor() && data.value.anchor == anchor {
+                anchored = Some(index);
+            }
+            Ok(true)
+        })?;
+        anchored.ok_or_else(|| Error::AnchorNotFound(anchor.to_owned()))
+    }
+}
@@ -0,0 +1,620 @@
+//! Serialize any [`Serialize`] type straight into a [`Tree`], without going
+//! through YAML text.
+//!
+//! Structs and maps become YAML maps, sequences and tuples become sequences,
+//! and scalars are written as their plain text representation. Strings which
+//! would otherwise read back as another type (e.g. `"true"` or `"42"`) are
+//! marked to be emitted single quoted. Enum variants other than unit
+//! variants become a single-entry map from the variant name to its content.
+//! ```rust
+//! # fn main() -> Result<(), ryml::Error> {
+//! #[derive(serde::Serialize)]
+//! struct Server {
+//!     host: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let server = Server { host: "localhost".into(), ports: vec![80, 443] };
+//! assert_eq!(
+//!     ryml::ser::to_string(&server)?,
+//!     "host: localhost\nports:\n  - 80\n  - 443\n"
+//! );
+//! # Ok(())
+//! # }
+//! ```
+use crate::inner::NodeType;
+use crate::{diff::NormalizedVal, float_text, Error, Result, Tree};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Other {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Options for serializing values into a [`Tree`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Serializer {
+    /// Leave out map entries and struct fields whose value is `None`, rather
+    /// than writing them as `~`. A `None` in a sequence is always written as
+    /// `~`, to keep the positions of the other items.
+    pub skip_none: bool,
+}
+
+impl Serializer {
+    /// Serialize a value into a new tree with these options.
+    pub fn to_tree<T: Serialize + ?Sized>(&self, value: &T) -> Result<Tree<'static>> {
+        let mut tree = Tree::default();
+        tree.reserve(16);
+        let root = tree.root_id()?;
+        value.serialize(NodeSerializer {
+            tree: &mut tree,
+            node: root,
+            options: self,
+        })?;
+        Ok(tree)
+    }
+
+    /// Serialize a value into YAML text with these options.
+    pub fn to_string<T: Serialize + ?Sized>(&self, value: &T) -> Result<String> {
+        self.to_tree(value)?.emit()
+    }
+}
+
+/// Serialize a value into a new tree with the default options.
+pub fn to_tree<T: Serialize + ?Sized>(value: &T) -> Result<Tree<'static>> {
+    Serializer::default().to_tree(value)
+}
+
+/// Serialize a value into YAML text with the default options.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Serializer::default().to_string(value)
+}
+
+/// The characters which YAML gives a meaning at the start of a plain scalar.
+const INDICATORS: [char; 19] = [
+    '|', '>', '@', '`', '%', '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '\'', '"',
+];
+
+/// Whether a string would not read back as a string if emitted plain.
+fn needs_quotes(text: &str) -> bool {
+    !matches!(NormalizedVal::from_plain(text), NormalizedVal::Str(_))
+        || text.starts_with(INDICATORS)
+}
+
+/// What was written to a node, so that containers can leave out `None`.
+#[derive(PartialEq, Eq)]
+enum Written {
+    Value,
+    None,
+}
+
+/// Writes a value into an existing, empty node.
+struct NodeSerializer<'s> {
+    tree: &'s mut Tree<'static>,
+    node: usize,
+    options: &'s Serializer,
+}
+
+impl<'s> NodeSerializer<'s> {
+    fn scalar(self, text: &str, quote: bool) -> Result<Written> {
+        self.tree.set_val(self.node, text)?;
+        if quote {
+            let node_type = self.tree.node_type(self.node)?;
+            self.tree
+                .set_flags(self.node, node_type | NodeType::WipValSquo)?;
+        }
+        Ok(Written::Value)
+    }
+
+    fn container(self, kind: NodeType) -> Result<Compound<'s>> {
+        let node_type = self.tree.node_type(self.node)?;
+        self.tree.set_flags(self.node, node_type | kind)?;
+        Ok(Compound {
+            tree: self.tree,
+            node: self.node,
+            options: self.options,
+            key: None,
+        })
+    }
+
+    /// Turn the node into a map with a single entry for the given variant,
+    /// returning a serializer for the entry.
+    fn variant(self, variant: &str) -> Result<NodeSerializer<'s>> {
+        let map = self.container(NodeType::Map)?;
+        let child = map.tree.append_child(map.node)?;
+        map.tree.set_key(child, variant)?;
+        Ok(NodeSerializer {
+            tree: map.tree,
+            node: child,
+            options: map.options,
+        })
+    }
+}
+
+impl<'s> ser::Serializer for NodeSerializer<'s> {
+    type Ok = Written;
+    type Error = Error;
+    type SerializeSeq = Compound<'s>;
+    type SerializeTuple = Compound<'s>;
+    type SerializeTupleStruct = Compound<'s>;
+    type SerializeTupleVariant = Compound<'s>;
+    type SerializeMap = Compound<'s>;
+    type SerializeStruct = Compound<'s>;
+    type SerializeStructVariant = Compound<'s>;
+
+    fn serialize_bool(self, v: bool) -> Result<Written> {
+        self.scalar(if v { "true" } else { "false" }, false)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Written> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Written> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Written> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Written> {
+        self.scalar(&v.to_string(), false)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Written> {
+        self.scalar(&v.to_string(), false)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Written> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Written> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Written> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Written> {
+        self.scalar(&v.to_string(), false)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Written> {
+        self.scalar(&v.to_string(), false)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Written> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Written> {
+        self.scalar(&float_text(v), false)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Written> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Written> {
+        self.scalar(v, needs_quotes(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Written> {
+        let mut seq = self.container(NodeType::Seq)?;
+        for byte in v {
+            ser::SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        ser::SerializeSeq::end(seq)
+    }
+
+    fn serialize_none(self) -> Result<Written> {
+        self.scalar("~", false)?;
+        Ok(Written::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Written> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Written> {
+        self.scalar("~", false)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Written> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Written> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Written> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Written> {
+        value.serialize(self.variant(variant)?)?;
+        Ok(Written::Value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s>> {
+        self.container(NodeType::Seq)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s>> {
+        self.container(NodeType::Seq)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s>> {
+        self.container(NodeType::Seq)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s>> {
+        self.variant(variant)?.container(NodeType::Seq)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s>> {
+        self.container(NodeType::Map)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s>> {
+        self.container(NodeType::Map)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s>> {
+        self.variant(variant)?.container(NodeType::Map)
+    }
+}
+
+/// Writes the children of a map or sequence node.
+struct Compound<'s> {
+    tree: &'s mut Tree<'static>,
+    node: usize,
+    options: &'s Serializer,
+    /// The key of the map entry being written, and whether it needs quotes.
+    key: Option<(String, bool)>,
+}
+
+impl Compound<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let child = self.tree.append_child(self.node)?;
+        value.serialize(NodeSerializer {
+            tree: self.tree,
+            node: child,
+            options: self.options,
+        })?;
+        Ok(())
+    }
+
+    fn entry<T: Serialize + ?Sized>(&mut self, key: &str, quote: bool, value: &T) -> Result<()> {
+        let child = self.tree.append_child(self.node)?;
+        self.tree.set_key(child, key)?;
+        if quote {
+            let node_type = self.tree.node_type(child)?;
+            self.tree
+                .set_flags(child, node_type | NodeType::WipKeySquo)?;
+        }
+        let written = value.serialize(NodeSerializer {
+            tree: self.tree,
+            node: child,
+            options: self.options,
+        })?;
+        if written == Written::None && self.options.skip_none {
+            self.tree.remove(child)?;
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let (key, quote) = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("map value serialized before its key"))?;
+        self.entry(&key, quote, value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entry(key, needs_quotes(key), value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = Written;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entry(key, needs_quotes(key), value)
+    }
+
+    fn end(self) -> Result<Written> {
+        Ok(Written::Value)
+    }
+}
+
+/// Renders a map key as text, along with whether it needs quotes. Only
+/// scalar keys are supported.
+struct KeySerializer;
+
+fn key_error() -> Error {
+    <Error as ser::Error>::custom("map keys must be scalars")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = (String, bool);
+    type Error = Error;
+    type SerializeSeq = Impossible<(String, bool), Error>;
+    type SerializeTuple = Impossible<(String, bool), Error>;
+    type SerializeTupleStruct = Impossible<(String, bool), Error>;
+    type SerializeTupleVariant = Impossible<(String, bool), Error>;
+    type SerializeMap = Impossible<(String, bool), Error>;
+    type SerializeStruct = Impossible<(String, bool), Error>;
+    type SerializeStructVariant = Impossible<(String, bool), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(String, bool)> {
+        Ok((v.to_string(), false))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(String, bool)> {
+        Ok((float_text(v as f64), false))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(String, bool)> {
+        Ok((float_text(v), false))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(String, bool)> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(String, bool)> {
+        Ok((v.to_owned(), needs_quotes(v)))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(String, bool)> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<(String, bool)> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(String, bool)> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(String, bool)> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(String, bool)> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(String, bool)> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(String, bool)> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(String, bool)> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_error())
+    }
+}
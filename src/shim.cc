@@ -1,3 +1,6 @@
+// The rapidyaml implementation is compiled with the bridge, not here.
+#undef RYML_SINGLE_HDR_DEFINE_NOW
+#undef C4CORE_SINGLE_HDR_DEFINE_NOW
 #include "ryml/src/inner.rs.h"
 // #include "ryml/include/shim.h"
 
@@ -22,7 +25,7 @@ namespace shimmy
 
         inline void _do_write(char c)
         {
-            m_inner->_do_write_char(c);
+            m_inner->_do_write(c4::csubstr(&c, 1));
         }
 
         inline void _do_write(c4::yml::RepC rep)
@@ -33,8 +36,10 @@ namespace shimmy
         template <size_t N>
         inline void _do_write(const char (&a)[N])
         {
-            rust::Slice<const char> slice(a, N);
-            m_inner->_do_write_slice(slice);
+            // Leave out the terminating null of the literal. Passing the bytes
+            // as a csubstr rather than a slice of `char` keeps the bridge free
+            // of `c_char`, whose signedness differs between targets.
+            m_inner->_do_write(c4::csubstr(a, N - 1));
         }
     };
 
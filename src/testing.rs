@@ -0,0 +1,57 @@
+//! Helpers for checking in your own test suites that YAML fixtures survive a
+//! trip through the parser and emitter.
+//! ```rust
+//! ryml::testing::assert_roundtrips("servers:\n  - host: localhost\n    port: 80\n");
+//! ```
+use crate::Tree;
+
+/// Parse the YAML, emit it and parse the result again, panicking unless both
+/// trees have the same [content](crate::Tree::content_eq). The panic message
+/// shows the first difference and the emitted text.
+#[track_caller]
+pub fn assert_roundtrips(yaml: &str) {
+    let tree = Tree::parse(yaml).expect("Failed to parse the YAML");
+    let emitted = tree.emit().expect("Failed to emit the parsed YAML");
+    let reparsed = Tree::parse(&emitted).expect("Failed to parse the emitted YAML");
+    if let Some(difference) = tree
+        .first_difference(&reparsed)
+        .expect("Failed to compare the trees")
+    {
+        panic!(
+            "YAML did not round-trip, {}\nemitted:\n{}",
+            difference, emitted
+        );
+    }
+}
+
+/// Parse the YAML and emit it, panicking unless the emitted text is exactly
+/// the same as the original. The panic message shows the first line which
+/// differs.
+#[track_caller]
+pub fn assert_roundtrips_exact(yaml: &str) {
+    let emitted = Tree::parse(yaml)
+        .and_then(|tree| tree.emit())
+        .expect("Failed to parse and emit the YAML");
+    if emitted != yaml {
+        let (line, (original, emitted_line)) = yaml
+            .split_inclusive('\n')
+            .map(Some)
+            .chain(std::iter::repeat(None))
+            .zip(
+                emitted
+                    .split_inclusive('\n')
+                    .map(Some)
+                    .chain(std::iter::repeat(None)),
+            )
+            .enumerate()
+            .find(|(_, (a, b))| a != b)
+            .expect("Texts differ but no line differs");
+        panic!(
+            "YAML did not round-trip exactly, at line {}: {:?} != {:?}\nemitted:\n{}",
+            line + 1,
+            original.unwrap_or_default(),
+            emitted_line.unwrap_or_default(),
+            emitted
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{path, Tree, NONE};
+use std::collections::HashMap;
+
+/// The kind of node a [`Schema`] accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    /// A map.
+    Map,
+    /// A sequence.
+    Seq,
+    /// A scalar, including an empty value.
+    Scalar,
+}
+
+impl SchemaKind {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Map => "a map",
+            Self::Seq => "a sequence",
+            Self::Scalar => "a scalar",
+        }
+    }
+}
+
+/// The expected shape of a node, for [`Tree::validate`]. Every constraint is
+/// optional, so the default schema accepts anything.
+///
+/// ```
+/// use ryml::{Schema, SchemaKind};
+///
+/// let schema = Schema {
+///     kind: Some(SchemaKind::Map),
+///     required: vec!["name".into(), "level".into()],
+///     properties: [(
+///         "level".to_owned(),
+///         Schema {
+///             allowed: vec!["debug".into(), "info".into()],
+///             ..Default::default()
+///         },
+///     )]
+///     .into(),
+///     ..Default::default()
+/// };
+/// let tree = ryml::Tree::parse("name: app\nlevel: trace\n")?;
+/// let errors = tree.validate(&schema).unwrap_err();
+/// assert_eq!("level", errors[0].path);
+/// # Ok::<(), ryml::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Schema {
+    /// The kind of node required, if any.
+    pub kind: Option<SchemaKind>,
+    /// Keys which must be present. Implies that the node is a map.
+    pub required: Vec<String>,
+    /// Schemas for the values of particular keys of a map. Keys without a
+    /// schema here are accepted as they are.
+    pub properties: HashMap<String, Schema>,
+    /// The schema for every item of a sequence.
+    pub items: Option<Box<Schema>>,
+    /// The scalar values allowed, if not empty. Implies that the node is a
+    /// scalar.
+    pub allowed: Vec<String>,
+}
+
+/// A way in which a node does not match a [`Schema`], from
+/// [`Tree::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The path of the node, written as for
+    /// [`Tree::key_diff`](crate::Tree::key_diff), or empty for the root.
+    pub path: String,
+    /// What is wrong with the node.
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "at the root: {}", self.message)
+        } else {
+            write!(f, "at {}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl<'a> Tree<'a> {
+    /// Check the tree against a [`Schema`], starting from the root. Rather
+    /// than stopping at the first problem, every node the schema describes
+    /// is checked, and all the errors are returned in document order. A node
+    /// of the wrong kind is reported once, without checking what is inside
+    /// it.
+    pub fn validate(&self, schema: &Schema) -> std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        match self.root_id() {
+            Ok(root) => self.validate_node(root, schema, "", &mut errors),
+            Err(_) => errors.push(ValidationError {
+                path: String::new(),
+                message: "the tree is empty".into(),
+            }),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate_node(
+        &self,
+        node: usize,
+        schema: &Schema,
+        path: &str,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let error = |errors: &mut Vec<ValidationError>, message: String| {
+            errors.push(ValidationError {
+                path: path.to_owned(),
+                message,
+            })
+        };
+        let data = match self.node_data(node) {
+            Ok(data) => data,
+            Err(e) => return error(errors, e.to_string()),
+        };
+        let node_type = data.node_type;
+        let kind = if node_type.is_map() {
+            SchemaKind::Map
+        } else if node_type.is_seq() {
+            SchemaKind::Seq
+        } else {
+            SchemaKind::Scalar
+        };
+        let expected = schema.kind.or(if !schema.required.is_empty() {
+            Some(SchemaKind::Map)
+        } else if !schema.allowed.is_empty() {
+            Some(SchemaKind::Scalar)
+        } else {
+            None
+        });
+        if let Some(expected) = expected.filter(|&expected| expected != kind) {
+            return error(
+                errors,
+                format!(
+                    "expected {}, found {}",
+                    expected.describe(),
+                    kind.describe()
+                ),
+            );
+        }
+        match kind {
+            SchemaKind::Scalar => {
+                let val = if node_type.has_val() {
+                    data.value.scalar
+                } else {
+                    ""
+                };
+                if !schema.allowed.is_empty() && !schema.allowed.iter().any(|a| a == val) {
+                    error(
+                        errors,
+                        format!(
+                            "{:?} is not one of the allowed values: {}",
+                            val,
+                            schema.allowed.join(", ")
+                        ),
+                    );
+                }
+            }
+            SchemaKind::Map => {
+                for key in &schema.required {
+                    if self.find_child(node, key).is_err() {
+                        error(errors, format!("missing required key {:?}", key));
+                    }
+                }
+                let mut child = data.first_child;
+                while child != NONE {
+                    let Ok(child_data) = self.node_data(child) else {
+                        break;
+                    };
+                    let key = child_data.key.scalar;
+                    if let Some(schema) = schema.properties.get(key) {
+                        let mut child_path = path.to_owned();
+                        path::push_key(&mut child_path, key);
+                        self.validate_node(child, schema, &child_path, errors);
+                    }
+                    child = child_data.next_sibling;
+                }
+            }
+            SchemaKind::Seq => {
+                let Some(items) = &schema.items else {
+                    return;
+                };
+                let mut child = data.first_child;
+                let mut pos = 0;
+                while child != NONE {
+                    let mut child_path = path.to_owned();
+                    path::push_index(&mut child_path, pos);
+                    self.validate_node(child, items, &child_path, errors);
+                    let Ok(child_data) = self.node_data(child) else {
+                        break;
+                    };
+                    child = child_data.next_sibling;
+                    pos += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Vendor the cases listed in `cases` into `data`, from a pinned release of
+# the YAML test suite's data branch. Needs git and network access.
+set -eu
+TAG=data-2022-01-17
+here=$(cd "$(dirname "$0")" && pwd)
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+git clone -q --depth 1 -b "$TAG" https://github.com/yaml/yaml-test-suite "$tmp/suite"
+rm -rf "$here/data"
+mkdir "$here/data"
+sed -e 's/#.*//' -e 's/[[:space:]]//g' "$here/cases" | grep -v '^$' | while read -r id; do
+    cp -R "$tmp/suite/$id" "$here/data/$id"
+done
+echo "$TAG" > "$here/data/VERSION"
@@ -0,0 +1,56 @@
+//! Checks that navigating a tree does not allocate. This installs a global
+//! allocator, so it lives in its own test binary.
+
+use ryml::{Error, Tree};
+use std::cell::Cell;
+
+static SRC: &str = include_str!("../test/AIScheduleAnchor.aiprog.yml");
+
+/// Counts the allocations made by each thread, so tests can check that
+/// code does not allocate without being thrown off by tests running in
+/// parallel.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { std::alloc::System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+fn navigation_does_not_allocate() -> Result<(), Error> {
+    let tree = Tree::parse(SRC)?;
+    let before = ALLOCATIONS.with(Cell::get);
+    let root = tree.root_ref()?;
+    let param_root = root.get("param_root")?;
+    let objects = param_root.child("objects").child("DemoAIActionIdx");
+    let first = objects.first_child()?;
+    assert_eq!(first.index(), objects.child_at(0)?.index());
+    assert_eq!(objects.index(), first.parent()?.index());
+    let next = first.next_sibling()?;
+    assert_eq!(first.index(), next.prev_sibling()?.index());
+    assert!(first.last_sibling()?.find_sibling(first.key()?).is_ok());
+    assert!(param_root.find_child("missing").is_err());
+    assert!(matches!(root.get(99), Err(Error::NodeNotFound)));
+    assert_eq!(objects.num_children()?, objects.iter()?.count());
+    assert_eq!(objects.num_children()? - 1, first.siblings()?.count());
+    for node in param_root.iter()?.rev() {
+        node.key()?;
+        node.node_type()?;
+        node.data();
+    }
+    assert_eq!(before, ALLOCATIONS.with(Cell::get));
+    Ok(())
+}
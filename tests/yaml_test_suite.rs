@@ -0,0 +1,137 @@
+//! Runs cases in the layout of the YAML test suite's `data` branch: each case
+//! is a directory holding `in.yaml`, and either `test.event` or an `error`
+//! file if the input is invalid.
+//!
+//! `test/suite` holds hand-written cases. `test/yaml-test-suite/data` holds
+//! the cases listed in `test/yaml-test-suite/cases`, vendored from a pinned
+//! release of <https://github.com/yaml/yaml-test-suite> by
+//! `test/yaml-test-suite/fetch.sh`.
+
+use ryml::Tree;
+use std::path::{Path, PathBuf};
+
+/// Bring an event line from the YAML test suite down to what
+/// [`Tree::emit_events`] can reproduce, dropping document markers, flow
+/// markers and scalar styles.
+fn normalize_event(line: &str) -> String {
+    if let Some(rest) = line.strip_prefix("=VAL ") {
+        let mut value_at = 0;
+        for prop in rest.split(' ') {
+            if !prop.starts_with(['&', '<']) {
+                break;
+            }
+            value_at += prop.len() + 1;
+        }
+        if value_at < rest.len() {
+            return format!("=VAL {}:{}", &rest[..value_at], &rest[value_at + 1..]);
+        }
+    }
+    line.replace(" ---", "")
+        .replace(" ...", "")
+        .replace(" {}", "")
+        .replace(" []", "")
+}
+
+/// Find every case under the given directory.
+fn find_cases(dir: &Path) -> Vec<PathBuf> {
+    let mut pending = vec![dir.to_path_buf()];
+    let mut cases = Vec::new();
+    while let Some(path) = pending.pop() {
+        if path.join("in.yaml").is_file() {
+            cases.push(path);
+        } else if path.is_dir() {
+            pending.extend(std::fs::read_dir(&path).unwrap().map(|e| e.unwrap().path()));
+        }
+    }
+    cases.sort();
+    cases
+}
+
+/// Run a single case, returning whether it passed.
+fn run_case(case: &Path) -> bool {
+    let source = std::fs::read_to_string(case.join("in.yaml")).unwrap_or_default();
+    let parsed = Tree::parse(&source);
+    if case.join("error").exists() {
+        parsed.is_err()
+    } else if let Ok(tree) = parsed {
+        let expected = std::fs::read_to_string(case.join("test.event")).unwrap();
+        let actual = tree.emit_events().unwrap();
+        expected
+            .lines()
+            .map(normalize_event)
+            .eq(actual.lines().map(normalize_event))
+    } else {
+        false
+    }
+}
+
+/// Read a list of case names, one per line, with `#` starting a comment.
+fn read_list(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Run every case under the given directory, and check that exactly the
+/// cases listed in the given `known-failures` file fail. Returns the number
+/// of cases run.
+fn check_suite(dir: &Path, known_failures: &Path) -> usize {
+    let known = read_list(known_failures);
+    let cases = find_cases(dir);
+    let failures: Vec<_> = cases
+        .iter()
+        .filter(|case| !run_case(case))
+        .map(|case| {
+            let name = case.strip_prefix(dir).unwrap().to_string_lossy();
+            name.replace('\\', "/")
+        })
+        .collect();
+    let unexpected: Vec<_> = failures.iter().filter(|f| !known.contains(f)).collect();
+    let fixed: Vec<_> = known.iter().filter(|k| !failures.contains(k)).collect();
+    assert!(
+        unexpected.is_empty(),
+        "unexpected failures: {:?}",
+        unexpected
+    );
+    assert!(
+        fixed.is_empty(),
+        "known failures which now pass: {:?}",
+        fixed
+    );
+    cases.len()
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test")
+        .join(name)
+}
+
+#[test]
+fn hand_written_cases() {
+    let dir = test_dir("suite");
+    assert!(check_suite(&dir, &dir.join("known-failures")) > 0);
+}
+
+#[test]
+#[ignore = "the cases are not vendored yet; run test/yaml-test-suite/fetch.sh first"]
+fn vendored_cases() {
+    let dir = test_dir("yaml-test-suite");
+    let data = dir.join("data");
+    let cases = read_list(&dir.join("cases"));
+    assert!(!cases.is_empty());
+    let missing: Vec<_> = cases
+        .iter()
+        .filter(|case| !data.join(case).is_dir())
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "cases missing from {}: {:?}",
+        data.display(),
+        missing
+    );
+    check_suite(&data, &dir.join("known-failures"));
+}